@@ -0,0 +1,138 @@
+//! Accessibility options, shared by both games.
+//!
+//! Space Invaders can be drawn in colour-blind-safe or high-contrast palettes, explosions can
+//! fade out instead of flashing, the whole game can be slowed down, and holding fire can keep
+//! firing instead of needing a press for every shot. The options are part of the `Settings`,
+//! changed on the settings screen.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::netplay::NetplaySession;
+
+/// The slowest the game can be slowed to
+pub const MIN_TIME_SCALE: f32 = 0.25;
+
+/// The accessibility options
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct Accessibility {
+    pub palette: Palette,
+    /// Fade explosions out instead of flashing them
+    pub reduce_flashing: bool,
+    /// How fast the game runs, from `MIN_TIME_SCALE` up to full speed at 1
+    pub time_scale: f32,
+    /// Keep firing for as long as fire is held
+    pub auto_fire: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Accessibility { palette: Palette::Standard, reduce_flashing: false, time_scale: 1.0, auto_fire: false }
+    }
+}
+
+impl Accessibility {
+    /// The options with any out of range values brought into range
+    pub fn clamped(self) -> Self {
+        Accessibility { time_scale: self.time_scale.clamp(MIN_TIME_SCALE, 1.0), ..self }
+    }
+}
+
+/// The colours Space Invaders is drawn in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    /// The theme's own colours
+    #[default]
+    Standard,
+    /// Blues against oranges, for deuteranopia and protanopia
+    RedGreenSafe,
+    /// Cyans against reds, for tritanopia
+    BlueYellowSafe,
+    /// Bright colours on black
+    HighContrast,
+}
+
+/// What each kind of sprite is tinted in a palette
+pub struct PaletteColours {
+    /// `None` keeps the theme's background
+    pub background: Option<Color>,
+    pub turret: Color,
+    pub player_shot: Color,
+    /// One for each `InvaderType`, A to C
+    pub invaders: [Color; 3],
+    pub invader_shot: Color,
+}
+
+impl Palette {
+    const ALL: [Palette; 4] =
+        [Palette::Standard, Palette::RedGreenSafe, Palette::BlueYellowSafe, Palette::HighContrast];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Standard => "STANDARD",
+            Palette::RedGreenSafe => "RED-GREEN",
+            Palette::BlueYellowSafe => "BLUE-YELLOW",
+            Palette::HighContrast => "HIGH CONTRAST",
+        }
+    }
+
+    pub fn next(self) -> Palette {
+        let index = Palette::ALL.iter().position(|palette| *palette == self).unwrap_or(0);
+        Palette::ALL[(index + 1) % Palette::ALL.len()]
+    }
+
+    /// The palette's colours, or `None` to leave the colouring to the theme
+    pub fn colours(self) -> Option<PaletteColours> {
+        // The colour-blind palettes are picked from Okabe and Ito's, which stay apart under
+        // every common kind of colour blindness
+        let colours = match self {
+            Palette::Standard => return None,
+            Palette::RedGreenSafe => PaletteColours {
+                background: None,
+                turret: Color::srgb_u8(86, 180, 233),
+                player_shot: Color::srgb_u8(86, 180, 233),
+                invaders: [Color::srgb_u8(230, 159, 0), Color::srgb_u8(240, 228, 66), Color::srgb_u8(204, 121, 167)],
+                invader_shot: Color::srgb_u8(213, 94, 0),
+            },
+            Palette::BlueYellowSafe => PaletteColours {
+                background: None,
+                turret: Color::srgb_u8(0, 158, 115),
+                player_shot: Color::srgb_u8(0, 158, 115),
+                invaders: [Color::srgb_u8(213, 94, 0), Color::srgb_u8(204, 121, 167), Color::WHITE],
+                invader_shot: Color::srgb_u8(213, 94, 0),
+            },
+            Palette::HighContrast => PaletteColours {
+                background: Some(Color::BLACK),
+                turret: Color::srgb(0.0, 1.0, 1.0),
+                player_shot: Color::WHITE,
+                invaders: [Color::srgb(1.0, 1.0, 0.0); 3],
+                invader_shot: Color::srgb(1.0, 0.0, 1.0),
+            },
+        };
+        Some(colours)
+    }
+}
+
+/// Slows the game down to the accessibility options' speed
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Accessibility>().add_systems(Update, apply_time_scale);
+    }
+}
+
+/// Slows virtual time, and with it everything on the fixed timestep. Netplay always runs at full
+/// speed, since the other player's game can't be slowed along with it.
+fn apply_time_scale(
+    accessibility: Res<Accessibility>,
+    netplay: Option<Res<NetplaySession>>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    let speed = if netplay.is_some() { 1.0 } else { accessibility.time_scale };
+    if time.relative_speed() != speed {
+        time.set_relative_speed(speed);
+    }
+}
@@ -0,0 +1,284 @@
+//! A scripted player that can stand in for the keyboard.
+//!
+//! Every decision it works out which invader bullets will reach its turret and where they land,
+//! picks the most attractive column to shoot at (the highest-scoring invaders, and above all the
+//! ones that have come furthest down), then moves towards the closest safe position to it and fires
+//! once lined up. Its output goes into `LocalInputs` just like keypresses, so it can play in any
+//! mode, netplay included.
+//!
+//! The arcade's players chase the UFO for its bonus, but this game has no UFO, so the autopilot
+//! only ever aims at the formation.
+
+use bevy::prelude::*;
+
+use crate::{
+    difficulty::ActiveDifficulty,
+    input::{InputSource, InputSources, LocalInputs, PlayerInput},
+    Board, BoardId, Boards, Invader, InvaderBullet, InvaderDirection, InvaderMoveTimer, PlayerId, Turret,
+    INVADER_BULLET_SIZE, INVADER_STEP_SIZE, TURRET_PADDING, TURRET_SIZE, TURRET_SPEED,
+};
+
+// Spacing of the turret positions considered when looking for somewhere safe
+const CANDIDATE_SPACING: f32 = 4.;
+// Extra priority per pixel an invader has descended from the top of the board
+const LOW_INVADER_PRIORITY: f32 = 0.1;
+// Priority lost per pixel the turret would have to travel to reach a column
+const DISTANCE_PENALTY: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutopilotSkill {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl AutopilotSkill {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(AutopilotSkill::Easy),
+            "normal" => Some(AutopilotSkill::Normal),
+            "hard" => Some(AutopilotSkill::Hard),
+            _ => None,
+        }
+    }
+
+    fn tuning(self) -> Tuning {
+        match self {
+            AutopilotSkill::Easy => Tuning {
+                decision_interval: 8,
+                threat_horizon: 0.4,
+                safety_margin: 0.,
+                aim_tolerance: 14.,
+                lead_targets: false,
+            },
+            AutopilotSkill::Normal => Tuning {
+                decision_interval: 3,
+                threat_horizon: 0.8,
+                safety_margin: 6.,
+                aim_tolerance: 8.,
+                lead_targets: true,
+            },
+            AutopilotSkill::Hard => Tuning {
+                decision_interval: 1,
+                threat_horizon: 1.5,
+                safety_margin: 12.,
+                aim_tolerance: 4.,
+                lead_targets: true,
+            },
+        }
+    }
+}
+
+struct Tuning {
+    /// Fixed ticks between decisions, the previous input is held in between
+    decision_interval: u32,
+    /// How far ahead, in seconds, incoming bullets are noticed
+    threat_horizon: f32,
+    /// Clearance kept from incoming bullets on top of the turret's own width
+    safety_margin: f32,
+    /// How close to a target column the turret has to be before firing
+    aim_tolerance: f32,
+    /// Whether to aim where the formation will be by the time the bullet gets there
+    lead_targets: bool,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct AutopilotState {
+    held: PlayerInput,
+    ticks_until_decision: u32,
+}
+
+/// An invader bullet on course for the turret's row
+struct Threat {
+    x: f32,
+    /// Seconds until the bullet reaches the top of the turret
+    arrival: f32,
+    /// Seconds until it has passed the bottom of the turret
+    departure: f32,
+}
+
+pub fn drive_autopilots(
+    sources: Res<InputSources>,
+    mut local_inputs: ResMut<LocalInputs>,
+    mut states: Local<Vec<AutopilotState>>,
+    time: Res<Time>,
+    boards: Res<Boards>,
+    board_query: Query<(&Board, &InvaderDirection, &InvaderMoveTimer)>,
+    turret_query: Query<(&Transform, &PlayerId, &BoardId), With<Turret>>,
+    invader_query: Query<(&Transform, &BoardId, &Invader)>,
+    invader_bullet_query: Query<(&Transform, &BoardId), With<InvaderBullet>>,
+    difficulty: Res<ActiveDifficulty>,
+) {
+    let speeds = &difficulty.tuning;
+    states.resize(sources.0.len(), AutopilotState::default());
+
+    for (turret_transform, player_id, board_id) in turret_query.iter() {
+        let InputSource::Autopilot(skill) = sources.0[player_id.0] else {
+            continue;
+        };
+        let tuning = skill.tuning();
+        let state = &mut states[player_id.0];
+
+        if state.ticks_until_decision > 0 {
+            state.ticks_until_decision -= 1;
+        } else {
+            let Ok((board, direction, move_timer)) = board_query.get(boards.0[board_id.0]) else {
+                continue;
+            };
+
+            let turret_x = turret_transform.translation.x;
+            let turret_top = turret_transform.translation.y + TURRET_SIZE.y / 2.;
+            let turret_bottom = turret_transform.translation.y - TURRET_SIZE.y / 2.;
+
+            let threats: Vec<Threat> = invader_bullet_query
+                .iter()
+                .filter(|(_, bullet_board)| *bullet_board == board_id)
+                .filter_map(|(transform, _)| {
+                    let bullet_bottom = transform.translation.y - INVADER_BULLET_SIZE.y / 2.;
+                    let bullet_top = transform.translation.y + INVADER_BULLET_SIZE.y / 2.;
+                    let arrival = ((bullet_bottom - turret_top) / speeds.invader_bullet_speed).max(0.);
+                    let departure = (bullet_top - turret_bottom) / speeds.invader_bullet_speed;
+                    (departure > 0. && arrival <= tuning.threat_horizon).then_some(Threat {
+                        x: transform.translation.x,
+                        arrival,
+                        departure,
+                    })
+                })
+                .collect();
+
+            // Only the lowest invader in each column can be hit from below
+            let mut invaders: Vec<(Vec2, u32)> = invader_query
+                .iter()
+                .filter(|(_, invader_board, _)| *invader_board == board_id)
+                .map(|(transform, _, invader)| (transform.translation.truncate(), invader.invader_type.points()))
+                .collect();
+            invaders.sort_by(|(a, _), (b, _)| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+            invaders.dedup_by(|(later, _), (earlier, _)| (later.x - earlier.x).abs() < 1.);
+
+            let formation_velocity = match direction {
+                InvaderDirection::Left => -INVADER_STEP_SIZE,
+                InvaderDirection::Right => INVADER_STEP_SIZE,
+            };
+
+            let target = invaders
+                .iter()
+                .map(|(position, points)| {
+                    let mut aim_x = position.x;
+                    if tuning.lead_targets {
+                        let travel_time = (position.y - turret_top) / speeds.bullet_speed;
+                        aim_x += formation_velocity * steps_within(move_timer, travel_time) as f32;
+                    }
+                    let priority = *points as f32 + (board.area.max.y - position.y) * LOW_INVADER_PRIORITY
+                        - (aim_x - turret_x).abs() * DISTANCE_PENALTY;
+                    (aim_x, priority)
+                })
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(aim_x, _)| aim_x);
+
+            let left_bound = board.area.min.x + TURRET_SIZE.x / 2. + TURRET_PADDING;
+            let right_bound = board.area.max.x - TURRET_SIZE.x / 2. - TURRET_PADDING;
+
+            state.held = decide(turret_x, left_bound, right_bound, target, &threats, &tuning, time.delta_seconds());
+            state.ticks_until_decision = tuning.decision_interval - 1;
+        }
+
+        local_inputs.0[player_id.0] = state.held;
+    }
+}
+
+/// Number of times the formation will step within `seconds`
+fn steps_within(move_timer: &InvaderMoveTimer, seconds: f32) -> u32 {
+    let remaining = move_timer.timer.remaining_secs();
+    if seconds < remaining {
+        return 0;
+    }
+    1 + ((seconds - remaining) / move_timer.timer.duration().as_secs_f32()) as u32
+}
+
+fn decide(
+    turret_x: f32,
+    left_bound: f32,
+    right_bound: f32,
+    target: Option<f32>,
+    threats: &[Threat],
+    tuning: &Tuning,
+    delta_seconds: f32,
+) -> PlayerInput {
+    let desired_x = target.unwrap_or(turret_x).clamp(left_bound, right_bound);
+    let danger_half_width = INVADER_BULLET_SIZE.x / 2. + TURRET_SIZE.x / 2. + tuning.safety_margin;
+
+    // Where the turret will be `seconds` from now if it heads straight for `destination`
+    let position_at = |destination: f32, seconds: f32| {
+        let distance = destination - turret_x;
+        turret_x + distance.signum() * distance.abs().min(TURRET_SPEED * seconds)
+    };
+
+    // Smallest gap left between the turret and any bullet on its way to `destination`
+    let clearance = |destination: f32| {
+        threats
+            .iter()
+            .flat_map(|threat| {
+                [threat.arrival, threat.departure]
+                    .map(|seconds| (position_at(destination, seconds) - threat.x).abs() - danger_half_width)
+            })
+            .fold(f32::INFINITY, f32::min)
+    };
+
+    let candidate_count = ((right_bound - left_bound) / CANDIDATE_SPACING) as usize;
+    let candidates =
+        (0..=candidate_count).map(|index| left_bound + index as f32 * CANDIDATE_SPACING).chain([desired_x, turret_x]);
+
+    let safe_destination = candidates.clone().filter(|candidate| clearance(*candidate) > 0.).min_by(|a, b| {
+        (a - desired_x)
+            .abs()
+            .total_cmp(&(b - desired_x).abs())
+            .then((a - turret_x).abs().total_cmp(&(b - turret_x).abs()))
+    });
+
+    // With nowhere safe, go wherever leaves the most room
+    let destination = safe_destination
+        .unwrap_or_else(|| candidates.max_by(|a, b| clearance(*a).total_cmp(&clearance(*b))).unwrap_or(turret_x));
+
+    let dead_zone = TURRET_SPEED * delta_seconds / 2.;
+    let offset = destination - turret_x;
+
+    PlayerInput {
+        left: offset < -dead_zone,
+        right: offset > dead_zone,
+        fire: target.is_some_and(|target_x| (target_x - turret_x).abs() <= tuning.aim_tolerance),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::difficulty::Difficulty;
+
+    const TICK: f32 = 1. / 64.;
+
+    #[test]
+    fn heads_for_the_target_and_fires_once_lined_up() {
+        let tuning = AutopilotSkill::Hard.tuning();
+        let input = decide(0., -300., 300., Some(100.), &[], &tuning, TICK);
+        assert_eq!(input, PlayerInput { left: false, right: true, fire: false });
+
+        let input = decide(99., -300., 300., Some(100.), &[], &tuning, TICK);
+        assert_eq!(input, PlayerInput { left: false, right: false, fire: true });
+    }
+
+    #[test]
+    fn steps_out_from_under_a_bullet() {
+        let tuning = AutopilotSkill::Hard.tuning();
+        let threat = Threat { x: 0., arrival: 0.2, departure: 0.3 };
+        let input = decide(0., -300., 300., Some(0.), &[threat], &tuning, TICK);
+        assert!(input.left != input.right, "{input:?}");
+    }
+
+    #[test]
+    fn steps_within_counts_the_formations_steps() {
+        let move_timer = InvaderMoveTimer::new(&Difficulty::default().tuning());
+        let interval = move_timer.timer.duration().as_secs_f32();
+        assert_eq!(steps_within(&move_timer, interval / 2.), 0);
+        assert_eq!(steps_within(&move_timer, interval * 2.5), 2);
+    }
+}
@@ -1,14 +1,53 @@
 //! A simplified implementation of the classic game "Breakout".
 //!
-//! Demonstrates Bevy's stepping capabilities if compiled with the `bevy_debug_stepping` feature.
+//! Each ball is served from the paddle by pressing Space, and holding it down doesn't serve the
+//! next one. Letting it fall past the paddle costs a life, and the game is over when the last one
+//! is gone.
+//!
+//! Where the ball lands on the paddle, and which way the paddle is moving, decides the angle it
+//! comes back at. As in the arcade game, the ball speeds up after a number of paddle hits and on
+//! reaching the upper brick rows, and the paddle shrinks once the ball hits the top wall. The
+//! speed-ups last until the ball is lost, the smaller paddle until the level is over.
+//!
+//! Bricks are worth more the higher their row. Tough bricks take several hits and darken less
+//! with each one, explosive bricks take their neighbours with them, setting off any other
+//! explosive bricks in a chain, and grey bricks never break. Broken bricks sometimes drop
+//! power-up capsules, see `power_ups`.
+//!
+//! The bricks are laid out by level files, see `levels`. Breaking every brick that can be broken
+//! moves on to the next level. The files can be made in the level editor, see `editor`.
+//!
+//! Escape pauses, and debug builds can step the gameplay one tick at a time from the pause menu.
+
+mod editor;
+mod levels;
+mod power_ups;
+
+use std::path::PathBuf;
 
 use bevy::{
-    math::bounding::{Aabb2d, BoundingCircle, BoundingVolume, IntersectsVolume},
+    asset::LoadState,
+    audio::Volume,
+    math::bounding::{Aabb2d, BoundingCircle, BoundingVolume},
     prelude::*,
     sprite::MaterialMesh2dBundle,
 };
+use rand::prelude::*;
 
-// mod stepping;
+use crate::{
+    damage::{self, CollisionLayer, CollisionMask, DamageEvent, DeathEvent, Health},
+    display,
+    effects::{EffectsPlugin, ExplosionEvent},
+    input::KeyBindings,
+    launcher::AppState,
+    pause::{PauseState, RestartEvent},
+    settings::AudioSettings,
+    GameOver, PlayerDied, PlayerInput, ScoreChanged,
+};
+use editor::TestLevel;
+use levels::{BestLevelScores, BrickGrid, Level, LevelLoader, Levels};
+use power_ups::{ActivePowerUps, CapsuleRng, Caught, PowerUp};
+pub(crate) use power_ups::{Capsule, Laser};
 
 // These constants are defined in `Transform` units.
 // Using the default 2D camera they correspond 1:1 with screen pixels.
@@ -17,14 +56,36 @@ const GAP_BETWEEN_PADDLE_AND_FLOOR: f32 = 60.0;
 const PADDLE_SPEED: f32 = 500.0;
 // How close can the paddle get to the wall
 const PADDLE_PADDING: f32 = 10.0;
+// How much of its width the paddle keeps once the ball has hit the top wall
+const SHRUNK_PADDLE_SCALE: f32 = 0.5;
 
 // We set the z-value of the ball to 1 so it renders on top in the case of overlapping sprites.
-const BALL_STARTING_POSITION: Vec3 = Vec3::new(0.0, -50.0, 1.0);
-const BALL_DIAMETER: f32 = 30.;
+const BALL_Z: f32 = 1.0;
+pub(crate) const BALL_DIAMETER: f32 = 30.;
 const BALL_SPEED: f32 = 400.0;
-const INITIAL_BALL_DIRECTION: Vec2 = Vec2::new(0.5, -0.5);
+const SERVE_DIRECTION: Vec2 = Vec2::new(0.5, 0.5);
+// Angle from straight up that the ball leaves the edge of the paddle at, in radians. Also the
+// steepest angle the paddle can send the ball off at, so it never ends up almost horizontal.
+const MAX_BOUNCE_ANGLE: f32 = std::f32::consts::PI / 3.0;
+// Radians of extra angle per unit of paddle speed when the paddle is moving as the ball lands
+const PADDLE_SPIN: f32 = 0.0004;
+// Each speed-up multiplies the ball's speed by this much
+const BALL_SPEED_UP: f32 = 1.15;
+// Paddle hits after which the ball speeds up
+const SPEED_UP_PADDLE_HITS: [u32; 2] = [4, 12];
+// The ball speeds up the first time it breaks a brick in each of this many rows from the top
+const UPPER_BRICK_ROWS: usize = 2;
+// Space between a served ball and the paddle it rests on
+const SERVE_GAP: f32 = 1.0;
+// Most times a ball can bounce in one tick, so one wedged in a tight spot can't stall the game
+const MAX_BOUNCES_PER_TICK: usize = 8;
+const BALL_MASK: CollisionMask =
+    CollisionMask::of(&[CollisionLayer::WALL, CollisionLayer::PADDLE, CollisionLayer::BRICK]);
+const BALL_DAMAGE: u32 = 1;
+const STARTING_LIVES: u32 = 3;
 
 const WALL_THICKNESS: f32 = 10.0;
+// The standalone game's walls
 // x coordinates
 const LEFT_WALL: f32 = -450.;
 const RIGHT_WALL: f32 = 450.;
@@ -32,119 +93,388 @@ const RIGHT_WALL: f32 = 450.;
 const BOTTOM_WALL: f32 = -300.;
 const TOP_WALL: f32 = 300.;
 
-const BRICK_SIZE: Vec2 = Vec2::new(100., 30.);
-// These values are exact
-const GAP_BETWEEN_PADDLE_AND_BRICKS: f32 = 270.0;
 const GAP_BETWEEN_BRICKS: f32 = 5.0;
-// These values are lower bounds, as the number of bricks is computed
-const GAP_BETWEEN_BRICKS_AND_CEILING: f32 = 20.0;
-const GAP_BETWEEN_BRICKS_AND_SIDES: f32 = 20.0;
 
 const SCOREBOARD_FONT_SIZE: f32 = 40.0;
 const SCOREBOARD_TEXT_PADDING: Val = Val::Px(5.0);
+const GAME_OVER_FONT_SIZE: f32 = 60.0;
+const GAME_OVER_HINT_FONT_SIZE: f32 = 24.0;
+// How long the level number shows before the level starts
+const LEVEL_INTRO_SECONDS: f32 = 2.0;
 
 const BACKGROUND_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
 const PADDLE_COLOR: Color = Color::srgb(0.3, 0.3, 0.7);
 const BALL_COLOR: Color = Color::srgb(1.0, 0.5, 0.5);
-const BRICK_COLOR: Color = Color::srgb(0.5, 0.5, 1.0);
+// Brick colours and score values by row, from the top, each taking `ROWS_PER_BRICK_COLOR` rows.
+// Rows below the last keep its colour.
+const BRICK_ROW_COLORS: [(Color, usize); 4] = [
+    (Color::srgb(0.8, 0.2, 0.2), 7),
+    (Color::srgb(0.9, 0.5, 0.1), 5),
+    (Color::srgb(0.2, 0.7, 0.3), 3),
+    (Color::srgb(0.8, 0.7, 0.1), 1),
+];
+const ROWS_PER_BRICK_COLOR: usize = 2;
+const TOUGH_BRICK_HIT_POINTS: u32 = 3;
+// How much darker a tough brick is for each hit it has left beyond the last
+const TOUGH_BRICK_DARKENING: f32 = 0.12;
+// Enough to break any brick that can be broken
+const EXPLOSION_DAMAGE: u32 = TOUGH_BRICK_HIT_POINTS;
+const INDESTRUCTIBLE_BRICK_COLOR: Color = Color::srgb(0.55, 0.55, 0.6);
+const EXPLOSIVE_BRICK_COLOR: Color = Color::srgb(0.15, 0.15, 0.15);
 const WALL_COLOR: Color = Color::srgb(0.8, 0.8, 0.8);
 const TEXT_COLOR: Color = Color::srgb(0.5, 0.5, 1.0);
 const SCORE_COLOR: Color = Color::srgb(1.0, 0.5, 0.5);
 
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        // .add_plugins(
-        //     stepping::SteppingPlugin::default()
-        //         .add_schedule(Update)
-        //         .add_schedule(FixedUpdate)
-        //         .at(Val::Percent(35.0), Val::Percent(50.0)),
-        // )
-        .insert_resource(Score(0))
-        .insert_resource(ClearColor(BACKGROUND_COLOR))
-        .add_event::<CollisionEvent>()
-        .add_systems(Startup, setup)
-        // Add our gameplay simulation systems to the fixed timestep schedule
-        // which runs at 64 Hz by default
-        .add_systems(
-            FixedUpdate,
-            (
-                apply_velocity,
-                move_paddle,
-                check_for_collisions,
-                play_collision_sound,
+/// How a game of Breakout is set up
+#[derive(Clone)]
+pub struct BreakoutConfig {
+    /// Where the game is played, in world space, measured between the middles of the walls
+    pub playfield: Rect,
+    /// Folder the sounds and levels are loaded from, relative to the app's asset folder
+    pub asset_root: PathBuf,
+    pub input: PaddleSource,
+    /// Seeds the power-ups that broken bricks drop, so that a game can be played out the same way again
+    pub seed: Option<u64>,
+    /// Loads the best score on each level from the config folder, and saves new bests there
+    pub keep_best_scores: bool,
+}
+
+impl Default for BreakoutConfig {
+    fn default() -> Self {
+        BreakoutConfig {
+            playfield: Rect::new(LEFT_WALL, BOTTOM_WALL, RIGHT_WALL, TOP_WALL),
+            asset_root: PathBuf::new(),
+            input: PaddleSource::Keyboard,
+            seed: None,
+            keep_best_scores: false,
+        }
+    }
+}
+
+/// Who moves the paddle. Breakout has no autopilot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaddleSource {
+    #[default]
+    Keyboard,
+    /// A program connected through `control`, or a host app writing `PaddleInput` during `InputSet`
+    External,
+}
+
+/// Breakout, played while `AppState` is `AppState::Breakout`
+#[derive(Default)]
+pub struct BreakoutPlugin {
+    pub config: BreakoutConfig,
+}
+
+impl Plugin for BreakoutPlugin {
+    fn build(&self, app: &mut App) {
+        let config = &self.config;
+
+        // Without a launcher to pick the game, play it straight away
+        if !app.world().contains_resource::<State<AppState>>() {
+            app.insert_state(AppState::Breakout).enable_state_scoped_entities::<AppState>();
+        }
+
+        if !app.is_plugin_added::<EffectsPlugin>() {
+            app.add_plugins(EffectsPlugin);
+        }
+
+        app.add_sub_state::<GameState>()
+            .enable_state_scoped_entities::<GameState>()
+            .configure_sets(FixedUpdate, (InputSet, GameplaySet).chain().run_if(in_state(AppState::Breakout)))
+            .configure_sets(
+                FixedUpdate,
+                GameplaySet.run_if(not(in_state(GameState::GameOver)).and_then(not(state_change_pending))),
             )
-                // `chain`ing systems together runs them in order
-                .chain(),
-        )
-        .add_systems(Update, update_scoreboard)
-        .run();
+            .insert_resource(Arena(config.playfield))
+            .insert_resource(AssetRoot(config.asset_root.clone()))
+            .insert_resource(CapsuleSeed(config.seed))
+            .init_resource::<PaddleInput>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<AudioSettings>()
+            .insert_resource(if config.keep_best_scores { BestLevelScores::load() } else { BestLevelScores::default() })
+            .init_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .add_event::<CollisionEvent>()
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<RestartEvent>()
+            .add_event::<ScoreChanged>()
+            .add_event::<PlayerDied>()
+            .add_event::<GameOver>()
+            .add_systems(OnEnter(AppState::Breakout), (setup, power_ups::spawn_power_up_hud))
+            .add_systems(OnEnter(GameState::LevelIntro), spawn_level_intro)
+            .add_systems(OnEnter(GameState::GameOver), (spawn_game_over_screen, send_game_over))
+            .add_systems(OnExit(AppState::Breakout), clean_up)
+            // Add our gameplay simulation systems to the fixed timestep schedule
+            // which runs at 64 Hz by default
+            .add_systems(
+                FixedUpdate,
+                (
+                    apply_velocity,
+                    move_paddle,
+                    (hold_served_ball, launch_ball).chain().run_if(in_state(GameState::Serving)),
+                    (
+                        power_ups::hold_caught_balls,
+                        power_ups::fire_lasers,
+                        move_balls,
+                        power_ups::check_laser_hits,
+                        damage::apply_damage,
+                        break_bricks,
+                        shade_bricks,
+                        power_ups::catch_capsules,
+                        check_ball_lost,
+                        check_level_cleared,
+                        power_ups::tick_power_ups,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Playing)),
+                    apply_ball_speed_and_paddle_size,
+                    play_collision_sound,
+                )
+                    // `chain`ing systems together runs them in order
+                    .chain()
+                    .in_set(GameplaySet),
+            )
+            .add_systems(
+                Update,
+                (update_scoreboard, power_ups::update_power_up_hud, send_score_events, restart_game)
+                    .run_if(in_state(AppState::Breakout)),
+            )
+            .add_systems(Update, start_level.run_if(in_state(GameState::LevelIntro)))
+            .add_plugins(editor::LevelEditorPlugin);
+
+        if config.input == PaddleSource::Keyboard {
+            app.init_resource::<KeyboardPaddle>()
+                .add_systems(Update, latch_keyboard.run_if(in_state(AppState::Breakout)))
+                .add_systems(FixedUpdate, read_keyboard.in_set(InputSet))
+                .add_systems(OnExit(PauseState::Paused), clear_keyboard_latch);
+        }
+    }
 }
 
-#[derive(Component)]
-struct Paddle;
+/// The systems that fill in `PaddleInput` before each tick, in `FixedUpdate`. A host driving the
+/// paddle with `PaddleSource::External` writes `PaddleInput` from a system in this set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputSet;
+
+/// The systems that advance Breakout by one tick, in `FixedUpdate`
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameplaySet;
+
+/// What the paddle is doing this tick. Pressing fire serves the ball and lets go of caught balls,
+/// and holding it fires lasers.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaddleInput {
+    pub left: bool,
+    pub right: bool,
+    /// Fire is held down
+    pub fire: bool,
+    /// Fire was pressed since the last tick
+    pub fire_pressed: bool,
+}
+
+impl PaddleInput {
+    /// Takes `input` as this tick's, with fire counting as pressed if it wasn't held last tick
+    pub fn set(&mut self, input: PlayerInput) {
+        *self = PaddleInput {
+            left: input.left,
+            right: input.right,
+            fire: input.fire,
+            fire_pressed: input.fire && !self.fire,
+        };
+    }
+
+    pub fn to_player_input(self) -> PlayerInput {
+        PlayerInput { left: self.left, right: self.right, fire: self.fire }
+    }
+}
+
+/// The keyboard's paddle input, with a fire press latched until a tick picks it up, since a frame
+/// can run without a tick
+#[derive(Resource, Default)]
+struct KeyboardPaddle(PaddleInput);
+
+/// Whether a level is about to start, the ball is waiting on the paddle, it's in play, or all
+/// lives are gone. Only exists while Breakout is being played.
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(AppState = AppState::Breakout)]
+enum GameState {
+    #[default]
+    LevelIntro,
+    Serving,
+    Playing,
+    GameOver,
+}
+
+/// `GameState` only changes between frames, and a frame can run several ticks. Once a tick has
+/// changed the state, the rest of the frame's ticks wait for it rather than carry on in the old
+/// state, which could see a level cleared or a ball lost all over again.
+fn state_change_pending(next_state: Res<NextState<GameState>>) -> bool {
+    matches!(*next_state, NextState::Pending(_))
+}
+
+/// Where the game is played, measured between the middles of the walls
+#[derive(Resource, Clone, Copy)]
+struct Arena(Rect);
+
+/// Folder the sounds and levels are loaded from
+#[derive(Resource)]
+struct AssetRoot(PathBuf);
+
+/// Seeds `CapsuleRng` on entering Breakout, or `None` for different power-ups every time
+#[derive(Resource)]
+struct CapsuleSeed(Option<u64>);
+
+/// How a game in progress is going, for hosts that play Breakout from outside the app
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Status {
+    pub(crate) score: usize,
+    pub(crate) lives: u32,
+    /// How many levels have been started this game, so 1 on the first level
+    pub(crate) level: usize,
+}
+
+impl Status {
+    /// `None` when Breakout isn't being played
+    pub(crate) fn of(world: &World) -> Option<Status> {
+        Some(Status {
+            score: world.get_resource::<Score>()?.0,
+            lives: world.get_resource::<Lives>()?.0,
+            level: world.get_resource::<Levels>()?.number,
+        })
+    }
+}
+
+#[derive(Component, Default)]
+pub(crate) struct Paddle {
+    /// How fast the paddle moved sideways last tick, after stopping at the walls
+    velocity: f32,
+}
 
 #[derive(Component)]
-struct Ball;
+pub(crate) struct Ball;
 
 #[derive(Component, Deref, DerefMut)]
 struct Velocity(Vec2);
 
-#[derive(Component)]
-struct Collider;
-
 #[derive(Event, Default)]
 struct CollisionEvent;
 
 #[derive(Component)]
-struct Brick;
+pub(crate) struct Brick {
+    /// Counting down from the top row, which is 0
+    row: usize,
+    pub(crate) kind: BrickKind,
+}
+
+impl Brick {
+    fn new(row: usize, kind: BrickKind) -> Self {
+        Brick { row, kind }
+    }
+
+    /// Points for breaking it, going by the colour of its row
+    fn value(&self) -> usize {
+        brick_row_color(self.row).1
+    }
+
+    /// Its colour with `hit_points` hits left
+    fn color(&self, hit_points: u32) -> Color {
+        match self.kind {
+            BrickKind::Plain => brick_row_color(self.row).0,
+            BrickKind::Tough => {
+                brick_row_color(self.row).0.darker(TOUGH_BRICK_DARKENING * hit_points.saturating_sub(1) as f32)
+            }
+            BrickKind::Indestructible => INDESTRUCTIBLE_BRICK_COLOR,
+            BrickKind::Explosive => EXPLOSIVE_BRICK_COLOR,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BrickKind {
+    /// Breaks in one hit
+    Plain,
+    /// Takes `TOUGH_BRICK_HIT_POINTS` hits
+    Tough,
+    /// Never breaks
+    Indestructible,
+    /// Breaks in one hit and breaks the bricks around it too
+    Explosive,
+}
+
+impl BrickKind {
+    const ALL: [BrickKind; 4] = [BrickKind::Plain, BrickKind::Tough, BrickKind::Indestructible, BrickKind::Explosive];
+
+    /// Hits it takes to break, or `None` if it never breaks
+    fn hit_points(self) -> Option<u32> {
+        match self {
+            BrickKind::Tough => Some(TOUGH_BRICK_HIT_POINTS),
+            BrickKind::Plain | BrickKind::Explosive => Some(1),
+            BrickKind::Indestructible => None,
+        }
+    }
+}
+
+/// The colour and score value of bricks in a row, counting from the top
+fn brick_row_color(row: usize) -> (Color, usize) {
+    BRICK_ROW_COLORS[(row / ROWS_PER_BRICK_COLOR).min(BRICK_ROW_COLORS.len() - 1)]
+}
+
+/// The wall whose first hit shrinks the paddle
+#[derive(Component)]
+struct TopWall;
 
 #[derive(Resource, Deref)]
 struct CollisionSound(Handle<AudioSource>);
 
+/// The ball's look, shared by every ball that gets served
+#[derive(Resource)]
+struct BallAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+}
+
 // This bundle is a collection of the components that define a "wall" in our game
 #[derive(Bundle)]
 struct WallBundle {
     // You can nest bundles inside of other bundles like this
     // Allowing you to compose their functionality
     sprite_bundle: SpriteBundle,
-    collider: Collider,
+    layer: CollisionLayer,
+    scope: StateScoped<AppState>,
 }
 
 /// Which side of the arena is this wall located on?
+/// There's no wall at the bottom, that's where the ball is lost.
 enum WallLocation {
     Left,
     Right,
-    Bottom,
     Top,
 }
 
 impl WallLocation {
     /// Location of the *center* of the wall, used in `transform.translation()`
-    fn position(&self) -> Vec2 {
+    fn position(&self, arena: Rect) -> Vec2 {
+        let center = arena.center();
         match self {
-            WallLocation::Left => Vec2::new(LEFT_WALL, 0.),
-            WallLocation::Right => Vec2::new(RIGHT_WALL, 0.),
-            WallLocation::Bottom => Vec2::new(0., BOTTOM_WALL),
-            WallLocation::Top => Vec2::new(0., TOP_WALL),
+            WallLocation::Left => Vec2::new(arena.min.x, center.y),
+            WallLocation::Right => Vec2::new(arena.max.x, center.y),
+            WallLocation::Top => Vec2::new(center.x, arena.max.y),
         }
     }
 
     /// (x, y) dimensions of the wall, used in `transform.scale()`
-    fn size(&self) -> Vec2 {
-        let arena_height = TOP_WALL - BOTTOM_WALL;
-        let arena_width = RIGHT_WALL - LEFT_WALL;
+    fn size(&self, arena: Rect) -> Vec2 {
+        let arena_height = arena.height();
+        let arena_width = arena.width();
         // Make sure we haven't messed up our constants
         assert!(arena_height > 0.0);
         assert!(arena_width > 0.0);
 
         match self {
-            WallLocation::Left | WallLocation::Right => {
-                Vec2::new(WALL_THICKNESS, arena_height + WALL_THICKNESS)
-            }
-            WallLocation::Bottom | WallLocation::Top => {
-                Vec2::new(arena_width + WALL_THICKNESS, WALL_THICKNESS)
-            }
+            WallLocation::Left | WallLocation::Right => Vec2::new(WALL_THICKNESS, arena_height + WALL_THICKNESS),
+            WallLocation::Top => Vec2::new(arena_width + WALL_THICKNESS, WALL_THICKNESS),
         }
     }
 }
@@ -152,26 +482,24 @@ impl WallLocation {
 impl WallBundle {
     // This "builder method" allows us to reuse logic across our wall entities,
     // making our code easier to read and less prone to bugs when we change the logic
-    fn new(location: WallLocation) -> WallBundle {
+    fn new(location: WallLocation, arena: Rect) -> WallBundle {
         WallBundle {
             sprite_bundle: SpriteBundle {
                 transform: Transform {
                     // We need to convert our Vec2 into a Vec3, by giving it a z-coordinate
                     // This is used to determine the order of our sprites
-                    translation: location.position().extend(0.0),
+                    translation: location.position(arena).extend(0.0),
                     // The z-scale of 2D objects must always be 1.0,
                     // or their ordering will be affected in surprising ways.
                     // See https://github.com/bevyengine/bevy/issues/4149
-                    scale: location.size().extend(1.0),
-                    ..default()
-                },
-                sprite: Sprite {
-                    color: WALL_COLOR,
+                    scale: location.size(arena).extend(1.0),
                     ..default()
                 },
+                sprite: Sprite { color: WALL_COLOR, ..default() },
                 ..default()
             },
-            collider: Collider,
+            layer: CollisionLayer::WALL,
+            scope: StateScoped(AppState::Breakout),
         }
     }
 }
@@ -180,6 +508,26 @@ impl WallBundle {
 #[derive(Resource, Deref, DerefMut)]
 struct Score(usize);
 
+#[derive(Resource, Deref, DerefMut)]
+struct Lives(u32);
+
+/// Progress towards the arcade speed-ups and paddle shrink
+#[derive(Resource, Default)]
+struct Rally {
+    paddle_hits: u32,
+    /// Rows from the top that the ball has broken a brick in, up to `UPPER_BRICK_ROWS`
+    upper_rows_reached: Vec<usize>,
+    speed_ups: i32,
+    paddle_shrunk: bool,
+}
+
+impl Rally {
+    /// How fast balls go, before any power-ups
+    fn ball_speed(&self) -> f32 {
+        BALL_SPEED * BALL_SPEED_UP.powi(self.speed_ups)
+    }
+}
+
 #[derive(Component)]
 struct ScoreboardUi;
 
@@ -189,232 +537,703 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
+    mut level_assets: ResMut<Assets<Level>>,
+    arena: Res<Arena>,
+    asset_root: Res<AssetRoot>,
+    seed: Res<CapsuleSeed>,
+    test_level: Option<Res<TestLevel>>,
 ) {
-    // Camera
-    commands.spawn(Camera2dBundle::default());
+    commands.insert_resource(Score(0));
+    commands.insert_resource(Lives(STARTING_LIVES));
+    commands.init_resource::<Rally>();
+    commands.init_resource::<ActivePowerUps>();
+    commands.insert_resource(CapsuleRng(match seed.0 {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }));
+    commands.insert_resource(match test_level {
+        Some(test_level) => Levels::test(level_assets.add(test_level.0.clone())),
+        None => Levels::load(&asset_server, &asset_root.0),
+    });
+    let ball_assets = BallAssets { mesh: meshes.add(Circle::default()), material: materials.add(BALL_COLOR) };
+
+    spawn_camera(&mut commands, arena.0, AppState::Breakout);
 
     // Sound
-    let ball_collision_sound = asset_server.load("sounds/breakout_collision.ogg");
+    let ball_collision_sound = asset_server.load(asset_root.0.join("sounds/breakout_collision.ogg"));
     commands.insert_resource(CollisionSound(ball_collision_sound));
 
-    // Paddle
-    let paddle_y = BOTTOM_WALL + GAP_BETWEEN_PADDLE_AND_FLOOR;
-
-    commands.spawn((
-        SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(0.0, paddle_y, 0.0),
-                scale: PADDLE_SIZE.extend(1.0),
-                ..default()
-            },
-            sprite: Sprite {
-                color: PADDLE_COLOR,
-                ..default()
-            },
-            ..default()
-        },
-        Paddle,
-        Collider,
-    ));
-
-    // Ball
-    commands.spawn((
-        MaterialMesh2dBundle {
-            mesh: meshes.add(Circle::default()).into(),
-            material: materials.add(BALL_COLOR),
-            transform: Transform::from_translation(BALL_STARTING_POSITION)
-                .with_scale(Vec2::splat(BALL_DIAMETER).extend(1.)),
-            ..default()
-        },
-        Ball,
-        Velocity(INITIAL_BALL_DIRECTION.normalize() * BALL_SPEED),
-    ));
-
     // Scoreboard
     commands.spawn((
         ScoreboardUi,
+        StateScoped(AppState::Breakout),
         TextBundle::from_sections([
+            TextSection::new("Score: ", TextStyle { font_size: SCOREBOARD_FONT_SIZE, color: TEXT_COLOR, ..default() }),
+            TextSection::from_style(TextStyle { font_size: SCOREBOARD_FONT_SIZE, color: SCORE_COLOR, ..default() }),
             TextSection::new(
-                "Score: ",
-                TextStyle {
-                    font_size: SCOREBOARD_FONT_SIZE,
-                    color: TEXT_COLOR,
-                    ..default()
-                },
+                "   Lives: ",
+                TextStyle { font_size: SCOREBOARD_FONT_SIZE, color: TEXT_COLOR, ..default() },
             ),
-            TextSection::from_style(TextStyle {
-                font_size: SCOREBOARD_FONT_SIZE,
-                color: SCORE_COLOR,
-                ..default()
-            }),
+            TextSection::from_style(TextStyle { font_size: SCOREBOARD_FONT_SIZE, color: SCORE_COLOR, ..default() }),
+            TextSection::new(
+                "   Level: ",
+                TextStyle { font_size: SCOREBOARD_FONT_SIZE, color: TEXT_COLOR, ..default() },
+            ),
+            TextSection::from_style(TextStyle { font_size: SCOREBOARD_FONT_SIZE, color: SCORE_COLOR, ..default() }),
         ])
-            .with_style(Style {
-                position_type: PositionType::Absolute,
-                top: SCOREBOARD_TEXT_PADDING,
-                left: SCOREBOARD_TEXT_PADDING,
-                ..default()
-            }),
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: SCOREBOARD_TEXT_PADDING,
+            left: SCOREBOARD_TEXT_PADDING,
+            ..default()
+        }),
     ));
 
     // Walls
-    commands.spawn(WallBundle::new(WallLocation::Left));
-    commands.spawn(WallBundle::new(WallLocation::Right));
-    commands.spawn(WallBundle::new(WallLocation::Bottom));
-    commands.spawn(WallBundle::new(WallLocation::Top));
-
-    // Bricks
-    let total_width_of_bricks = (RIGHT_WALL - LEFT_WALL) - 2. * GAP_BETWEEN_BRICKS_AND_SIDES;
-    let bottom_edge_of_bricks = paddle_y + GAP_BETWEEN_PADDLE_AND_BRICKS;
-    let total_height_of_bricks = TOP_WALL - bottom_edge_of_bricks - GAP_BETWEEN_BRICKS_AND_CEILING;
-
-    assert!(total_width_of_bricks > 0.0);
-    assert!(total_height_of_bricks > 0.0);
-
-    // Given the space available, compute how many rows and columns of bricks we can fit
-    let n_columns = (total_width_of_bricks / (BRICK_SIZE.x + GAP_BETWEEN_BRICKS)).floor() as usize;
-    let n_rows = (total_height_of_bricks / (BRICK_SIZE.y + GAP_BETWEEN_BRICKS)).floor() as usize;
-    let n_vertical_gaps = n_columns - 1;
-
-    // Because we need to round the number of columns,
-    // the space on the top and sides of the bricks only captures a lower bound, not an exact value
-    let center_of_bricks = (LEFT_WALL + RIGHT_WALL) / 2.0;
-    let left_edge_of_bricks = center_of_bricks
-        // Space taken up by the bricks
-        - (n_columns as f32 / 2.0 * BRICK_SIZE.x)
-        // Space taken up by the gaps
-        - n_vertical_gaps as f32 / 2.0 * GAP_BETWEEN_BRICKS;
-
-    // In Bevy, the `translation` of an entity describes the center point,
-    // not its bottom-left corner
-    let offset_x = left_edge_of_bricks + BRICK_SIZE.x / 2.;
-    let offset_y = bottom_edge_of_bricks + BRICK_SIZE.y / 2.;
-
-    for row in 0..n_rows {
-        for column in 0..n_columns {
-            let brick_position = Vec2::new(
-                offset_x + column as f32 * (BRICK_SIZE.x + GAP_BETWEEN_BRICKS),
-                offset_y + row as f32 * (BRICK_SIZE.y + GAP_BETWEEN_BRICKS),
-            );
-
-            // brick
-            commands.spawn((
+    commands.spawn(WallBundle::new(WallLocation::Left, arena.0));
+    commands.spawn(WallBundle::new(WallLocation::Right, arena.0));
+    commands.spawn((WallBundle::new(WallLocation::Top, arena.0), TopWall));
+
+    spawn_paddle(&mut commands, arena.0);
+    commands.insert_resource(ball_assets);
+}
+
+/// Spawns a camera on the arena and its walls, for as long as `AppState` is `state`
+fn spawn_camera(commands: &mut Commands, arena: Rect, state: AppState) {
+    let playfield = Rect::from_center_size(arena.center(), arena.size() + WALL_THICKNESS);
+    display::spawn_playfield_camera(commands, playfield, BACKGROUND_COLOR, state);
+}
+
+/// Where a ball waiting to be served rests on top of the paddle
+fn serve_position(paddle_translation: Vec3) -> Vec3 {
+    Vec3::new(
+        paddle_translation.x,
+        paddle_translation.y + PADDLE_SIZE.y / 2.0 + BALL_DIAMETER / 2.0 + SERVE_GAP,
+        BALL_Z,
+    )
+}
+
+/// Spawns a ball at rest, waiting to be served
+fn spawn_ball(commands: &mut Commands, ball_assets: &BallAssets, translation: Vec3) -> Entity {
+    commands
+        .spawn((
+            MaterialMesh2dBundle {
+                mesh: ball_assets.mesh.clone().into(),
+                material: ball_assets.material.clone(),
+                transform: Transform::from_translation(translation).with_scale(Vec2::splat(BALL_DIAMETER).extend(1.)),
+                ..default()
+            },
+            Ball,
+            BALL_MASK,
+            Velocity(Vec2::ZERO),
+            StateScoped(AppState::Breakout),
+        ))
+        .id()
+}
+
+/// Where the paddle starts each game
+fn paddle_start(arena: Rect) -> Vec3 {
+    Vec3::new(arena.center().x, arena.min.y + GAP_BETWEEN_PADDLE_AND_FLOOR, 0.0)
+}
+
+fn spawn_paddle(commands: &mut Commands, arena: Rect) {
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform { translation: paddle_start(arena), scale: PADDLE_SIZE.extend(1.0), ..default() },
+            sprite: Sprite { color: PADDLE_COLOR, ..default() },
+            ..default()
+        },
+        Paddle::default(),
+        CollisionLayer::PADDLE,
+        StateScoped(AppState::Breakout),
+    ));
+}
+
+/// Spawns the bricks of a level
+fn spawn_bricks(commands: &mut Commands, level: &Level, arena: &Arena) {
+    let grid = BrickGrid::new(arena, level.columns(), level.rows.len());
+    for (row, bricks) in level.rows.iter().enumerate() {
+        for (column, kind) in bricks.iter().enumerate() {
+            let Some(kind) = kind else {
+                continue;
+            };
+
+            let brick = Brick::new(row, *kind);
+            let mut entity = commands.spawn((
                 SpriteBundle {
-                    sprite: Sprite {
-                        color: BRICK_COLOR,
-                        ..default()
-                    },
+                    sprite: Sprite { color: brick.color(kind.hit_points().unwrap_or(0)), ..default() },
                     transform: Transform {
-                        translation: brick_position.extend(0.0),
-                        scale: Vec3::new(BRICK_SIZE.x, BRICK_SIZE.y, 1.0),
+                        translation: grid.position(row, column).extend(0.0),
+                        scale: grid.brick_size.extend(1.0),
                         ..default()
                     },
                     ..default()
                 },
-                Brick,
-                Collider,
+                brick,
+                CollisionLayer::BRICK,
+                StateScoped(AppState::Breakout),
             ));
+            if let Some(hit_points) = kind.hit_points() {
+                entity.insert(Health::new(hit_points));
+            }
         }
     }
 }
 
-fn move_paddle(
+#[derive(Resource, Deref, DerefMut)]
+struct LevelIntroTimer(Timer);
+
+fn spawn_level_intro(mut commands: Commands, levels: Res<Levels>, best_scores: Res<BestLevelScores>) {
+    commands.insert_resource(LevelIntroTimer(Timer::from_seconds(LEVEL_INTRO_SECONDS, TimerMode::Once)));
+
+    let text_style = |font_size| TextStyle { font_size, color: TEXT_COLOR, ..default() };
+
+    commands
+        .spawn((
+            StateScoped(GameState::LevelIntro),
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn(TextBundle::from_section(format!("LEVEL {}", levels.number), text_style(GAME_OVER_FONT_SIZE)));
+            if let Some(best) = levels.file_index().and_then(|index| best_scores.get(index)) {
+                screen.spawn(TextBundle::from_section(format!("BEST {best}"), text_style(GAME_OVER_HINT_FONT_SIZE)));
+            }
+        });
+}
+
+/// Lays out the level's bricks and puts a ball on the paddle once the intro is over and the
+/// level file has loaded. A level file that fails to load is swapped for `fallback_level`.
+fn start_level(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut timer: ResMut<LevelIntroTimer>,
+    asset_server: Res<AssetServer>,
+    level_assets: Res<Assets<Level>>,
+    levels: Res<Levels>,
+    arena: Res<Arena>,
+    ball_assets: Res<BallAssets>,
+    paddle_query: Query<&Transform, With<Paddle>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !timer.tick(time.delta()).finished() {
+        return;
+    }
+
+    let fallback;
+    let level = match level_assets.get(levels.current()) {
+        Some(level) => level,
+        None if matches!(asset_server.load_state(levels.current()), LoadState::Failed(_)) => {
+            error!("Couldn't load level {}, playing a fallback level instead", levels.number);
+            fallback = levels::fallback_level();
+            &fallback
+        }
+        None => return,
+    };
+
+    spawn_bricks(&mut commands, level, &arena);
+    spawn_ball(&mut commands, &ball_assets, serve_position(paddle_query.single().translation));
+    next_state.set(GameState::Serving);
+}
+
+/// Moves on to the next level once every brick that can be broken is gone. A level being
+/// test-played goes back to the editor instead.
+fn check_level_cleared(
+    mut commands: Commands,
+    score: Res<Score>,
+    mut levels: ResMut<Levels>,
+    mut best_scores: ResMut<BestLevelScores>,
+    mut rally: ResMut<Rally>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    brick_query: Query<(Entity, &Brick)>,
+    ball_query: Query<Entity, With<Ball>>,
+    leftovers: Query<Entity, Or<(With<Capsule>, With<Laser>)>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    if brick_query.iter().any(|(_, brick)| brick.kind != BrickKind::Indestructible) {
+        return;
+    }
+
+    let Some(index) = levels.file_index() else {
+        next_app_state.set(AppState::BreakoutEditor);
+        return;
+    };
+
+    best_scores.record(index, **score - levels.starting_score);
+
+    for entity in brick_query.iter().map(|(entity, _)| entity).chain(ball_query.iter()) {
+        commands.entity(entity).despawn();
+    }
+    power_ups::clear_power_ups(&mut commands, &mut power_ups, &leftovers);
+    *rally = Rally::default();
+
+    levels.number += 1;
+    levels.starting_score = **score;
+    next_state.set(GameState::LevelIntro);
+}
+
+/// Removes the resources `setup` added, the entities go by themselves
+fn clean_up(mut commands: Commands) {
+    commands.remove_resource::<Score>();
+    commands.remove_resource::<Lives>();
+    commands.remove_resource::<Rally>();
+    commands.remove_resource::<ActivePowerUps>();
+    commands.remove_resource::<CapsuleRng>();
+    commands.remove_resource::<Levels>();
+    commands.remove_resource::<LevelIntroTimer>();
+    commands.remove_resource::<TestLevel>();
+    commands.remove_resource::<CollisionSound>();
+    commands.remove_resource::<BallAssets>();
+}
+
+/// Starts over when Enter is pressed on the game over screen, or on request from the pause menu.
+/// Enter goes back to the editor instead when test-playing.
+fn restart_game(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut restart_events: EventReader<RestartEvent>,
+    mut score: ResMut<Score>,
+    mut lives: ResMut<Lives>,
+    mut rally: ResMut<Rally>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    mut levels: ResMut<Levels>,
+    arena: Res<Arena>,
+    level_query: Query<Entity, Or<(With<Ball>, With<Brick>)>>,
+    mut paddle_query: Query<&mut Transform, With<Paddle>>,
+    leftovers: Query<Entity, Or<(With<Capsule>, With<Laser>)>>,
+) {
+    let requested = restart_events.read().count() > 0;
+    let play_again = *state.get() == GameState::GameOver && keyboard_input.just_pressed(KeyCode::Enter);
+    if !requested && !play_again {
+        return;
+    }
+
+    if play_again && levels.testing {
+        next_app_state.set(AppState::BreakoutEditor);
+        return;
+    }
+
+    **score = 0;
+    **lives = STARTING_LIVES;
+    *rally = Rally::default();
+    power_ups::clear_power_ups(&mut commands, &mut power_ups, &leftovers);
+    levels.number = 1;
+    levels.starting_score = 0;
+    for entity in level_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    paddle_query.single_mut().translation = paddle_start(arena.0);
+    next_state.set(GameState::LevelIntro);
+}
+
+fn latch_keyboard(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<&mut Transform, With<Paddle>>,
+    bindings: Res<KeyBindings>,
+    mut keyboard: ResMut<KeyboardPaddle>,
+) {
+    let controls = bindings.breakout;
+    let input = &mut keyboard.0;
+    input.left = keyboard_input.pressed(controls.left);
+    input.right = keyboard_input.pressed(controls.right);
+    input.fire = keyboard_input.pressed(controls.fire);
+    input.fire_pressed |= keyboard_input.just_pressed(controls.fire);
+}
+
+fn read_keyboard(mut keyboard: ResMut<KeyboardPaddle>, mut input: ResMut<PaddleInput>) {
+    *input = keyboard.0;
+    keyboard.0.fire_pressed = false;
+}
+
+/// Forgets a fire press latched while the game was paused, so that it doesn't serve the ball once
+/// it's running again
+fn clear_keyboard_latch(mut keyboard: ResMut<KeyboardPaddle>) {
+    keyboard.0 = PaddleInput::default();
+}
+
+/// Keeps a ball waiting to be served on top of the paddle as it moves
+fn hold_served_ball(
+    paddle_query: Query<&Transform, With<Paddle>>,
+    mut ball_query: Query<&mut Transform, (With<Ball>, Without<Paddle>)>,
+) {
+    let paddle_transform = paddle_query.single();
+    for mut ball_transform in ball_query.iter_mut() {
+        ball_transform.translation = serve_position(paddle_transform.translation);
+    }
+}
+
+fn launch_ball(
+    input: Res<PaddleInput>,
+    rally: Res<Rally>,
+    power_ups: Res<ActivePowerUps>,
+    mut ball_query: Query<&mut Velocity, With<Ball>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !input.fire_pressed {
+        return;
+    }
+
+    for mut velocity in ball_query.iter_mut() {
+        **velocity = SERVE_DIRECTION.normalize() * rally.ball_speed() * power_ups.ball_speed_scale();
+    }
+    next_state.set(GameState::Playing);
+}
+
+/// Despawns balls that fell past the paddle. Losing the last one costs a life and, with lives
+/// left, puts a new ball on the paddle to serve.
+fn check_ball_lost(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    mut lives: ResMut<Lives>,
+    mut rally: ResMut<Rally>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    ball_assets: Res<BallAssets>,
+    ball_query: Query<(Entity, &Transform), With<Ball>>,
+    paddle_query: Query<&Transform, With<Paddle>>,
+    leftovers: Query<Entity, Or<(With<Capsule>, With<Laser>)>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut death_events: EventWriter<PlayerDied>,
+) {
+    let mut balls_left = 0;
+    for (ball_entity, ball_transform) in ball_query.iter() {
+        if ball_transform.translation.y < arena.0.min.y - BALL_DIAMETER / 2.0 {
+            commands.entity(ball_entity).despawn();
+        } else {
+            balls_left += 1;
+        }
+    }
+
+    if balls_left > 0 {
+        return;
+    }
+
+    **lives = lives.saturating_sub(1);
+    // The next ball starts slow again, but the paddle stays the size it was
+    *rally = Rally { paddle_shrunk: rally.paddle_shrunk, ..default() };
+    power_ups::clear_power_ups(&mut commands, &mut power_ups, &leftovers);
+    death_events.send(PlayerDied { player: 0, lives: **lives });
+
+    if **lives == 0 {
+        next_state.set(GameState::GameOver);
+    } else {
+        spawn_ball(&mut commands, &ball_assets, serve_position(paddle_query.single().translation));
+        next_state.set(GameState::Serving);
+    }
+}
+
+fn spawn_game_over_screen(mut commands: Commands, score: Res<Score>, levels: Res<Levels>) {
+    let text_style = |font_size| TextStyle { font_size, color: TEXT_COLOR, ..default() };
+
+    commands
+        .spawn((
+            StateScoped(GameState::GameOver),
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|screen| {
+            screen.spawn(TextBundle::from_section("GAME OVER", text_style(GAME_OVER_FONT_SIZE)));
+            screen.spawn(TextBundle::from_section(
+                format!("FINAL SCORE {}", **score),
+                text_style(GAME_OVER_HINT_FONT_SIZE),
+            ));
+            let hint =
+                if levels.testing { "PRESS ENTER TO GO BACK TO THE EDITOR" } else { "PRESS ENTER TO PLAY AGAIN" };
+            screen.spawn(TextBundle::from_section(hint, text_style(GAME_OVER_HINT_FONT_SIZE)));
+        });
+}
+
+fn send_game_over(score: Res<Score>, mut game_over_events: EventWriter<GameOver>) {
+    game_over_events.send(GameOver { scores: vec![**score as u32], difficulty: None });
+}
+
+fn move_paddle(
+    input: Res<PaddleInput>,
+    mut query: Query<(&mut Transform, &mut Paddle)>,
+    arena: Res<Arena>,
     time: Res<Time>,
 ) {
-    let mut paddle_transform = query.single_mut();
+    let (mut paddle_transform, mut paddle) = query.single_mut();
     let mut direction = 0.0;
 
-    if keyboard_input.pressed(KeyCode::ArrowLeft) {
+    if input.left {
         direction -= 1.0;
     }
 
-    if keyboard_input.pressed(KeyCode::ArrowRight) {
+    if input.right {
         direction += 1.0;
     }
 
     // Calculate the new horizontal paddle position based on player input
-    let new_paddle_position =
-        paddle_transform.translation.x + direction * PADDLE_SPEED * time.delta_seconds();
+    let new_paddle_position = paddle_transform.translation.x + direction * PADDLE_SPEED * time.delta_seconds();
 
     // Update the paddle position,
     // making sure it doesn't cause the paddle to leave the arena
-    let left_bound = LEFT_WALL + WALL_THICKNESS / 2.0 + PADDLE_SIZE.x / 2.0 + PADDLE_PADDING;
-    let right_bound = RIGHT_WALL - WALL_THICKNESS / 2.0 - PADDLE_SIZE.x / 2.0 - PADDLE_PADDING;
+    // The paddle's scale is its size, which shrinks during the game
+    let half_width = paddle_transform.scale.x / 2.0;
+    let left_bound = arena.0.min.x + WALL_THICKNESS / 2.0 + half_width + PADDLE_PADDING;
+    let right_bound = arena.0.max.x - WALL_THICKNESS / 2.0 - half_width - PADDLE_PADDING;
 
+    let old_paddle_position = paddle_transform.translation.x;
     paddle_transform.translation.x = new_paddle_position.clamp(left_bound, right_bound);
+    paddle.velocity = if time.delta_seconds() > 0.0 {
+        (paddle_transform.translation.x - old_paddle_position) / time.delta_seconds()
+    } else {
+        0.0
+    };
+}
+
+/// The velocity a ball leaves the paddle with. Hitting the middle sends it straight up, and
+/// the further out it lands the more it's angled that way, nudged along by the paddle's own
+/// movement, up to `MAX_BOUNCE_ANGLE`.
+fn paddle_bounce(ball_x: f32, paddle_transform: &Transform, paddle: &Paddle, speed: f32) -> Vec2 {
+    let half_width = paddle_transform.scale.x / 2.0;
+    let offset = ((ball_x - paddle_transform.translation.x) / half_width).clamp(-1.0, 1.0);
+    let angle = (offset * MAX_BOUNCE_ANGLE + paddle.velocity * PADDLE_SPIN).clamp(-MAX_BOUNCE_ANGLE, MAX_BOUNCE_ANGLE);
+    Vec2::new(angle.sin(), angle.cos()) * speed
 }
 
-fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time: Res<Time>) {
+// Balls move in `move_balls`, which stops them at whatever they hit
+fn apply_velocity(mut query: Query<(&mut Transform, &Velocity), Without<Ball>>, time: Res<Time>) {
     for (mut transform, velocity) in &mut query {
         transform.translation.x += velocity.x * time.delta_seconds();
         transform.translation.y += velocity.y * time.delta_seconds();
     }
 }
 
-fn update_scoreboard(score: Res<Score>, mut query: Query<&mut Text, With<ScoreboardUi>>) {
+fn update_scoreboard(
+    score: Res<Score>,
+    lives: Res<Lives>,
+    levels: Res<Levels>,
+    mut query: Query<&mut Text, With<ScoreboardUi>>,
+) {
     let mut text = query.single_mut();
     text.sections[1].value = score.to_string();
+    text.sections[3].value = lives.to_string();
+    text.sections[5].value = levels.number.to_string();
 }
 
-fn check_for_collisions(
+fn send_score_events(score: Res<Score>, mut score_events: EventWriter<ScoreChanged>) {
+    if score.is_changed() {
+        score_events.send(ScoreChanged { player: 0, score: **score as u32 });
+    }
+}
+
+/// Moves each ball along its velocity for the tick, bouncing it off anything it reaches on the
+/// way. The ball is swept from where it is to where it's going so that nothing is too thin, and
+/// no ball too fast, for it to pass through.
+fn move_balls(
     mut commands: Commands,
-    mut score: ResMut<Score>,
-    mut ball_query: Query<(&mut Velocity, &Transform), With<Ball>>,
-    collider_query: Query<(Entity, &Transform, Option<&Brick>), With<Collider>>,
+    time: Res<Time>,
+    mut rally: ResMut<Rally>,
+    power_ups: Res<ActivePowerUps>,
+    mut ball_query: Query<(Entity, &mut Velocity, &mut Transform, &CollisionMask), (With<Ball>, Without<Caught>)>,
+    collider_query: Query<
+        (Entity, &Transform, &CollisionLayer, Option<&Brick>, Option<&Paddle>, Has<TopWall>),
+        Without<Ball>,
+    >,
     mut collision_events: EventWriter<CollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
 ) {
-    let (mut ball_velocity, ball_transform) = ball_query.single_mut();
-
-    for (collider_entity, collider_transform, maybe_brick) in &collider_query {
-        let collision = ball_collision(
-            BoundingCircle::new(ball_transform.translation.truncate(), BALL_DIAMETER / 2.),
-            Aabb2d::new(
-                collider_transform.translation.truncate(),
-                collider_transform.scale.truncate() / 2.,
-            ),
-        );
+    for (ball_entity, mut ball_velocity, mut ball_transform, ball_mask) in ball_query.iter_mut() {
+        let mut seconds_left = time.delta_seconds();
+        let mut last_normal: Option<Vec2> = None;
+        // Any time left after the last bounce is lost, which only matters when the ball is
+        // wedged somewhere
+        for _ in 0..MAX_BOUNCES_PER_TICK {
+            let movement = **ball_velocity * seconds_left;
+            if movement == Vec2::ZERO {
+                break;
+            }
+
+            // The first thing the ball reaches is the only one it can hit before bouncing
+            let ball = BoundingCircle::new(ball_transform.translation.truncate(), BALL_DIAMETER / 2.);
+            let first_contact = collider_query
+                .iter()
+                .filter(|(_, _, layer, ..)| ball_mask.hits(**layer))
+                .filter_map(|collider| {
+                    let (_, collider_transform, ..) = collider;
+                    let bounding_box = Aabb2d::new(
+                        collider_transform.translation.truncate(),
+                        collider_transform.scale.truncate() / 2.,
+                    );
+                    ball_contact(ball, movement, bounding_box).map(|contact| (contact, collider))
+                })
+                .min_by(|(a, _), (b, _)| a.time.total_cmp(&b.time));
+
+            let Some((contact, (collider_entity, collider_transform, _, maybe_brick, maybe_paddle, is_top_wall))) =
+                first_contact
+            else {
+                ball_transform.translation += movement.extend(0.0);
+                break;
+            };
+            ball_transform.translation += (movement * contact.time).extend(0.0);
+            seconds_left *= 1.0 - contact.time;
 
-        if let Some(collision) = collision {
             // Sends a collision event so that other systems can react to the collision
             collision_events.send_default();
 
-            // Bricks should be despawned and increment the scoreboard on collision
-            if maybe_brick.is_some() {
-                commands.entity(collider_entity).despawn();
-                **score += 1;
+            // Anything with `Health` takes the hit in `apply_damage`
+            damage_events.send(DamageEvent { target: collider_entity, source: ball_entity, amount: BALL_DAMAGE });
+
+            if let Some(brick) = maybe_brick {
+                if brick.row < UPPER_BRICK_ROWS && !rally.upper_rows_reached.contains(&brick.row) {
+                    rally.upper_rows_reached.push(brick.row);
+                    rally.speed_ups += 1;
+                }
             }
 
-            // Reflect the ball's velocity when it collides
-            let mut reflect_x = false;
-            let mut reflect_y = false;
-
-            // Reflect only if the velocity is in the opposite direction of the collision
-            // This prevents the ball from getting stuck inside the bar
-            match collision {
-                Collision::Left => reflect_x = ball_velocity.x > 0.0,
-                Collision::Right => reflect_x = ball_velocity.x < 0.0,
-                Collision::Top => reflect_y = ball_velocity.y < 0.0,
-                Collision::Bottom => reflect_y = ball_velocity.y > 0.0,
+            if is_top_wall && !rally.paddle_shrunk {
+                rally.paddle_shrunk = true;
             }
 
-            // Reflect velocity on the x-axis if we hit something on the x-axis
-            if reflect_x {
-                ball_velocity.x = -ball_velocity.x;
+            // Landing on top of the paddle aims the ball rather than just bouncing it
+            if let Some(paddle) = maybe_paddle {
+                if contact.normal.y > contact.normal.x.abs() && ball_velocity.y < 0.0 {
+                    rally.paddle_hits += 1;
+                    if SPEED_UP_PADDLE_HITS.contains(&rally.paddle_hits) {
+                        rally.speed_ups += 1;
+                    }
+
+                    if power_ups.is_active(PowerUp::Catch) {
+                        power_ups::catch_ball(
+                            &mut commands,
+                            ball_entity,
+                            ball_transform.translation.x,
+                            collider_transform,
+                        );
+                        break;
+                    }
+
+                    **ball_velocity = paddle_bounce(
+                        ball_transform.translation.x,
+                        collider_transform,
+                        paddle,
+                        rally.ball_speed() * power_ups.ball_speed_scale(),
+                    );
+                    continue;
+                }
             }
 
-            // Reflect velocity on the y-axis if we hit something on the y-axis
-            if reflect_y {
-                ball_velocity.y = -ball_velocity.y;
+            // Squeezed between two things that it's touching, such as a wall and the paddle that
+            // moved up against it, the ball would bounce between them for ever. It slips out
+            // along them instead, at the same speed.
+            let squeezed = contact.time == 0.0 && last_normal.is_some_and(|last| last.dot(contact.normal) < 0.0);
+            last_normal = Some(contact.normal);
+            if squeezed {
+                let along = **ball_velocity - ball_velocity.dot(contact.normal) * contact.normal;
+                **ball_velocity = along.try_normalize().unwrap_or(Vec2::NEG_Y) * ball_velocity.length();
+                continue;
+            }
+
+            // Reflect the ball's velocity off the surface it hit. On a corner that's the line
+            // from the corner to the ball's centre, so glancing blows only turn it a little.
+            let into_surface = ball_velocity.dot(contact.normal);
+            **ball_velocity -= 2.0 * into_surface * contact.normal;
+        }
+    }
+}
+
+/// Scores the bricks that have been broken, dropping capsules from some of them. Explosive bricks
+/// that break damage every breakable brick touching them, setting off any other explosive bricks
+/// in a chain. The whole chain goes off in the same tick.
+fn break_bricks(
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    mut capsule_rng: ResMut<CapsuleRng>,
+    mut death_events: EventReader<DeathEvent>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+    mut brick_query: Query<(Entity, &Transform, &Brick, &mut Health)>,
+    looks_query: Query<(&Sprite, &Handle<Image>)>,
+) {
+    // Explosions take health off their neighbours straight away rather than through
+    // `apply_damage`, so that whatever they break joins the bricks still to be dealt with
+    let mut broken: Vec<Entity> = death_events.read().map(|death| death.entity).collect();
+    while let Some(entity) = broken.pop() {
+        let Ok((_, &transform, brick, _)) = brick_query.get(entity) else {
+            continue;
+        };
+
+        **score += brick.value();
+        power_ups::drop_capsule(&mut commands, &mut capsule_rng, transform.translation.truncate());
+        commands.entity(entity).despawn();
+
+        if brick.kind != BrickKind::Explosive {
+            continue;
+        }
+
+        if let Ok((sprite, texture)) = looks_query.get(entity) {
+            explosion_events.send(ExplosionEvent { sprite: sprite.clone(), texture: texture.clone(), transform });
+        }
+
+        // Anything closer than two bricks away in both directions is a neighbour. Indestructible
+        // bricks have no health, so they're left alone.
+        let centre = transform.translation.truncate();
+        let reach = (transform.scale.truncate() + GAP_BETWEEN_BRICKS) * 1.5;
+        for (other, other_transform, _, mut other_health) in brick_query.iter_mut() {
+            let offset = (other_transform.translation.truncate() - centre).abs();
+            if offset.x < reach.x && offset.y < reach.y && other_health.take(EXPLOSION_DAMAGE) {
+                broken.push(other);
             }
         }
     }
 }
 
+/// Darkens tough bricks as they take hits
+fn shade_bricks(mut brick_query: Query<(&Brick, &Health, &mut Sprite), Changed<Health>>) {
+    for (brick, health, mut sprite) in brick_query.iter_mut() {
+        sprite.color = brick.color(health.current);
+    }
+}
+
+/// Sizes the paddle and sets the balls' speed for the speed-ups, paddle shrink and power-ups
+fn apply_ball_speed_and_paddle_size(
+    rally: Res<Rally>,
+    power_ups: Res<ActivePowerUps>,
+    mut paddle_query: Query<&mut Transform, With<Paddle>>,
+    mut ball_query: Query<&mut Velocity, With<Ball>>,
+) {
+    if !rally.is_changed() && !power_ups.is_changed() {
+        return;
+    }
+
+    let shrink = if rally.paddle_shrunk { SHRUNK_PADDLE_SCALE } else { 1.0 };
+    for mut paddle_transform in paddle_query.iter_mut() {
+        paddle_transform.scale.x = PADDLE_SIZE.x * shrink * power_ups.paddle_width_scale();
+    }
+
+    // Balls waiting to be served or caught stay put
+    let speed = rally.ball_speed() * power_ups.ball_speed_scale();
+    for mut velocity in ball_query.iter_mut() {
+        **velocity = velocity.normalize_or_zero() * speed;
+    }
+}
+
 fn play_collision_sound(
     mut commands: Commands,
     mut collision_events: EventReader<CollisionEvent>,
     sound: Res<CollisionSound>,
+    audio: Res<AudioSettings>,
 ) {
     // Play a sound once per frame if a collision occurred.
     if !collision_events.is_empty() {
@@ -423,39 +1242,324 @@ fn play_collision_sound(
         commands.spawn(AudioBundle {
             source: sound.clone(),
             // auto-despawn the entity when playback finishes
-            settings: PlaybackSettings::DESPAWN,
+            settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(audio.effects_volume)),
         });
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-enum Collision {
-    Left,
-    Right,
-    Top,
-    Bottom,
+/// Where a moving ball first touches a box
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Contact {
+    /// How far through its movement the ball gets before touching, from 0 to 1
+    time: f32,
+    /// Points out of the box, through the centre of the ball at the moment it touches
+    normal: Vec2,
 }
 
-// Returns `Some` if `ball` collides with `bounding_box`.
-// The returned `Collision` is the side of `bounding_box` that `ball` hit.
-fn ball_collision(ball: BoundingCircle, bounding_box: Aabb2d) -> Option<Collision> {
-    if !ball.intersects(&bounding_box) {
+// Returns `Some` if `ball` runs into `bounding_box` when it moves by `movement`.
+// This sweeps the ball's centre along `movement` against the box grown by the ball's radius,
+// which has rounded corners, so the ball can't pass through the box however far it moves.
+// A ball already touching the box only hits it if it's moving further in.
+fn ball_contact(ball: BoundingCircle, movement: Vec2, bounding_box: Aabb2d) -> Option<Contact> {
+    let start = ball.center();
+    let radius = ball.radius();
+
+    let closest = bounding_box.closest_point(start);
+    let offset = start - closest;
+    if offset.length_squared() < radius * radius {
+        let normal = if offset == Vec2::ZERO {
+            // The centre is inside the box, so push it out of the nearest side
+            let to_min = start - bounding_box.min;
+            let to_max = bounding_box.max - start;
+            let nearest = to_min.min(to_max).min_element();
+            if nearest == to_min.x {
+                Vec2::NEG_X
+            } else if nearest == to_max.x {
+                Vec2::X
+            } else if nearest == to_min.y {
+                Vec2::NEG_Y
+            } else {
+                Vec2::Y
+            }
+        } else {
+            offset.normalize()
+        };
+        return (movement.dot(normal) < 0.0).then_some(Contact { time: 0.0, normal });
+    }
+
+    // Where the centre enters the grown box, with its corners still square
+    let grown = bounding_box.grow(Vec2::splat(radius));
+    let mut enter = 0.0_f32;
+    let mut exit = 1.0_f32;
+    let mut normal = Vec2::ZERO;
+    for axis in 0..2 {
+        if movement[axis] == 0.0 {
+            if start[axis] < grown.min[axis] || start[axis] > grown.max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let to_min = (grown.min[axis] - start[axis]) / movement[axis];
+        let to_max = (grown.max[axis] - start[axis]) / movement[axis];
+        if to_min.min(to_max) > enter {
+            enter = to_min.min(to_max);
+            normal = Vec2::ZERO;
+            normal[axis] = -movement[axis].signum();
+        }
+        exit = exit.min(to_min.max(to_max));
+    }
+    if enter > exit {
         return None;
     }
 
-    let closest = bounding_box.closest_point(ball.center());
-    let offset = ball.center() - closest;
-    let side = if offset.x.abs() > offset.y.abs() {
-        if offset.x < 0. {
-            Collision::Left
-        } else {
-            Collision::Right
+    // Entering along one of the box's sides is hitting that side
+    let entry = start + movement * enter;
+    let beside_side = |axis: usize| entry[axis] >= bounding_box.min[axis] && entry[axis] <= bounding_box.max[axis];
+    if (normal.x != 0.0 && beside_side(1)) || (normal.y != 0.0 && beside_side(0)) {
+        return Some(Contact { time: enter, normal });
+    }
+
+    // Otherwise it's in a corner of the grown box, which is really a circle around the box's corner
+    let corner = Vec2::new(
+        if entry.x < bounding_box.center().x { bounding_box.min.x } else { bounding_box.max.x },
+        if entry.y < bounding_box.center().y { bounding_box.min.y } else { bounding_box.max.y },
+    );
+    let from_corner = start - corner;
+    let a = movement.length_squared();
+    let b = from_corner.dot(movement);
+    let c = from_corner.length_squared() - radius * radius;
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let time = (-b - discriminant.sqrt()) / a;
+    (0.0..=1.0).contains(&time).then(|| Contact { time, normal: (from_corner + movement * time).normalize() })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    const TICK: Duration = Duration::from_micros(15_625);
+
+    fn ball_at(position: Vec2) -> BoundingCircle {
+        BoundingCircle::new(position, BALL_DIAMETER / 2.)
+    }
+
+    /// A world with a ball and some boxes to hit, ready to run `move_balls`
+    fn world_with_ball(position: Vec2, velocity: Vec2, boxes: &[(Vec2, Vec2)]) -> (World, Entity) {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(TICK);
+        world.insert_resource(time);
+        world.init_resource::<Rally>();
+        world.init_resource::<ActivePowerUps>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.init_resource::<Events<DamageEvent>>();
+
+        for (centre, size) in boxes {
+            world.spawn((
+                Transform::from_translation(centre.extend(0.0)).with_scale(size.extend(1.0)),
+                CollisionLayer::WALL,
+            ));
         }
-    } else if offset.y > 0. {
-        Collision::Top
-    } else {
-        Collision::Bottom
-    };
+        let ball = world
+            .spawn((Ball, BALL_MASK, Velocity(velocity), Transform::from_translation(position.extend(BALL_Z))))
+            .id();
+        (world, ball)
+    }
 
-    Some(side)
-}
\ No newline at end of file
+    fn ball_position(world: &World, ball: Entity) -> Vec2 {
+        world.get::<Transform>(ball).unwrap().translation.truncate()
+    }
+
+    #[test]
+    fn fast_ball_stops_at_a_thin_wall() {
+        let wall = Aabb2d::new(Vec2::new(500.0, 0.0), Vec2::new(WALL_THICKNESS / 2.0, 300.0));
+        let contact = ball_contact(ball_at(Vec2::ZERO), Vec2::new(2000.0, 0.0), wall).unwrap();
+
+        assert_eq!(contact.normal, Vec2::NEG_X);
+        // The ball's edge reaches the wall's near side
+        let reached = 2000.0 * contact.time + BALL_DIAMETER / 2.;
+        assert!((reached - (500.0 - WALL_THICKNESS / 2.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn corner_hit_bounces_off_the_corner() {
+        let brick = Aabb2d::new(Vec2::ZERO, Vec2::new(50.0, 15.0));
+        let contact = ball_contact(ball_at(Vec2::new(60.0, 100.0)), Vec2::new(0.0, -200.0), brick).unwrap();
+
+        // Between straight up and straight right, pointing from the corner to the ball
+        assert!(contact.normal.x > 0.0 && contact.normal.y > 0.0);
+        assert!((contact.normal.length() - 1.0).abs() < 1e-5);
+        let centre = Vec2::new(60.0, 100.0 - 200.0 * contact.time);
+        assert!((centre.distance(Vec2::new(50.0, 15.0)) - BALL_DIAMETER / 2.).abs() < 0.01);
+    }
+
+    #[test]
+    fn ball_passing_close_to_a_corner_misses() {
+        let brick = Aabb2d::new(Vec2::ZERO, Vec2::new(50.0, 15.0));
+        // Cuts across the grown box's square corner without reaching the rounded one
+        assert_eq!(ball_contact(ball_at(Vec2::new(100.0, -10.0)), Vec2::new(-200.0, 200.0), brick), None);
+    }
+
+    #[test]
+    fn ball_leaving_a_box_it_touches_is_not_hit_again() {
+        let brick = Aabb2d::new(Vec2::ZERO, Vec2::new(50.0, 15.0));
+        let touching = ball_at(Vec2::new(0.0, -15.0 - BALL_DIAMETER / 2. + 0.001));
+        assert_eq!(ball_contact(touching, Vec2::new(0.0, -5000.0), brick), None);
+        assert!(ball_contact(touching, Vec2::new(0.0, 5000.0), brick).is_some());
+    }
+
+    #[test]
+    fn fast_ball_breaks_a_brick_instead_of_passing_through_it() {
+        let brick = (Vec2::new(0.0, 200.0), Vec2::new(100.0, 10.0));
+        // Far enough in one tick to pass the brick completely
+        let (mut world, ball) = world_with_ball(Vec2::ZERO, Vec2::new(0.0, 40_000.0), &[brick]);
+        let brick_entity = world.query_filtered::<Entity, With<CollisionLayer>>().single(&world);
+        world.entity_mut(brick_entity).insert((Brick::new(5, BrickKind::Plain), CollisionLayer::BRICK));
+
+        world.run_system_once(move_balls);
+
+        let hits: Vec<_> = world.resource_mut::<Events<DamageEvent>>().drain().collect();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].target, brick_entity);
+        assert_eq!(hits[0].source, ball);
+        assert!(world.get::<Velocity>(ball).unwrap().y < 0.0);
+        assert!(ball_position(&world, ball).y < 200.0 - 5.0 - BALL_DIAMETER / 2. + 0.01);
+    }
+
+    #[test]
+    fn fast_ball_bounces_several_times_in_one_tick() {
+        let walls = [
+            (Vec2::new(-100.0, 0.0), Vec2::new(WALL_THICKNESS, 400.0)),
+            (Vec2::new(100.0, 0.0), Vec2::new(WALL_THICKNESS, 400.0)),
+        ];
+        // About 1000 units this tick, in a corridor with 160 to move in
+        let (mut world, ball) = world_with_ball(Vec2::ZERO, Vec2::new(64_000.0, 0.0), &walls);
+
+        world.run_system_once(move_balls);
+
+        let bounces = world.resource_mut::<Events<CollisionEvent>>().drain().count();
+        assert!(bounces > 1);
+        let x = ball_position(&world, ball).x;
+        let limit = 100.0 - WALL_THICKNESS / 2.0 - BALL_DIAMETER / 2.;
+        assert!((-limit - 0.01..=limit + 0.01).contains(&x), "ball escaped to {x}");
+        assert_eq!(world.get::<Velocity>(ball).unwrap().length(), 64_000.0);
+    }
+
+    #[test]
+    fn ball_squeezed_against_a_wall_slips_out() {
+        // The paddle has moved up against the ball while it's touching the wall
+        let boxes = [
+            (Vec2::new(-450.0, 0.0), Vec2::new(WALL_THICKNESS, 600.0)),
+            (Vec2::new(-405.0, -240.0), Vec2::new(PADDLE_SIZE.x / 2., PADDLE_SIZE.y)),
+        ];
+        let (mut world, ball) = world_with_ball(Vec2::new(-435.0, -243.0), Vec2::new(-520.0, -300.0), &boxes);
+
+        for _ in 0..10 {
+            world.run_system_once(move_balls);
+        }
+
+        assert!(ball_position(&world, ball).y < -243.0 - BALL_DIAMETER);
+    }
+
+    #[test]
+    fn explosive_chain_goes_off_in_one_tick() {
+        let mut world = World::new();
+        world.insert_resource(Score(0));
+        world.insert_resource(CapsuleRng(StdRng::seed_from_u64(0)));
+        world.init_resource::<Events<DeathEvent>>();
+        world.init_resource::<Events<ExplosionEvent>>();
+
+        // A row of explosive bricks with a tough one at the end, and a plain one too far away
+        let brick_size = Vec2::new(60.0, 20.0);
+        let mut spawn_brick = |column: f32, kind: BrickKind| {
+            let position = Vec2::new(column * (brick_size.x + GAP_BETWEEN_BRICKS), 0.0);
+            world
+                .spawn((
+                    Transform::from_translation(position.extend(0.0)).with_scale(brick_size.extend(1.0)),
+                    Brick::new(6, kind),
+                    Health::new(kind.hit_points().unwrap()),
+                ))
+                .id()
+        };
+        let first = spawn_brick(0.0, BrickKind::Explosive);
+        for column in 1..5 {
+            spawn_brick(column as f32, BrickKind::Explosive);
+        }
+        spawn_brick(5.0, BrickKind::Tough);
+        let far = spawn_brick(7.0, BrickKind::Plain);
+
+        world.entity_mut(first).get_mut::<Health>().unwrap().current = 0;
+        world.send_event(DeathEvent { entity: first, source: first });
+        world.run_system_once(break_bricks);
+
+        let left: Vec<Entity> = world.query_filtered::<Entity, With<Brick>>().iter(&world).collect();
+        assert_eq!(left, vec![far]);
+        assert_eq!(**world.resource::<Score>(), 6 * brick_row_color(6).1);
+    }
+
+    #[test]
+    fn fast_ball_stays_in_the_arena() {
+        let arena = Rect::new(LEFT_WALL, BOTTOM_WALL, RIGHT_WALL, TOP_WALL);
+        let walls = [
+            (Vec2::new(arena.min.x, 0.0), Vec2::new(WALL_THICKNESS, arena.height() + WALL_THICKNESS)),
+            (Vec2::new(arena.max.x, 0.0), Vec2::new(WALL_THICKNESS, arena.height() + WALL_THICKNESS)),
+            (Vec2::new(0.0, arena.min.y), Vec2::new(arena.width() + WALL_THICKNESS, WALL_THICKNESS)),
+            (Vec2::new(0.0, arena.max.y), Vec2::new(arena.width() + WALL_THICKNESS, WALL_THICKNESS)),
+        ];
+        let (mut world, ball) = world_with_ball(Vec2::ZERO, Vec2::new(31_000.0, 23_000.0), &walls);
+
+        let inside = arena.inflate(-(WALL_THICKNESS / 2.0 + BALL_DIAMETER / 2.) + 0.01);
+        for _ in 0..200 {
+            world.run_system_once(move_balls);
+            let position = ball_position(&world, ball);
+            assert!(inside.contains(position), "ball escaped to {position}");
+        }
+    }
+
+    #[test]
+    fn fire_counts_as_pressed_only_on_the_tick_it_goes_down() {
+        let fire = PlayerInput { fire: true, ..default() };
+        let mut input = PaddleInput::default();
+        input.set(fire);
+        assert!(input.fire && input.fire_pressed);
+        input.set(fire);
+        assert!(input.fire && !input.fire_pressed);
+        input.set(PlayerInput::default());
+        input.set(fire);
+        assert!(input.fire_pressed);
+    }
+
+    #[test]
+    fn holding_fire_does_not_serve_or_let_go_of_caught_balls() {
+        let mut world = World::new();
+        world.init_resource::<Rally>();
+        world.init_resource::<ActivePowerUps>();
+        world.init_resource::<NextState<GameState>>();
+        world.insert_resource(PaddleInput { fire: true, ..default() });
+        world.spawn((Paddle::default(), Transform::from_scale(PADDLE_SIZE.extend(1.0))));
+        let ball = world.spawn((Ball, Velocity(Vec2::ZERO), Transform::default())).id();
+
+        world.run_system_once(launch_ball);
+        assert_eq!(**world.get::<Velocity>(ball).unwrap(), Vec2::ZERO);
+
+        world.run_system_once(move |mut commands: Commands, paddle_query: Query<&Transform, With<Paddle>>| {
+            power_ups::catch_ball(&mut commands, ball, 0.0, paddle_query.single());
+        });
+        world.run_system_once(power_ups::hold_caught_balls);
+        assert!(world.get::<Caught>(ball).is_some());
+
+        world.resource_mut::<PaddleInput>().fire_pressed = true;
+        world.run_system_once(power_ups::hold_caught_balls);
+        assert!(world.get::<Caught>(ball).is_none());
+        assert!(world.get::<Velocity>(ball).unwrap().y > 0.0);
+    }
+}
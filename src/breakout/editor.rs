@@ -0,0 +1,543 @@
+//! A level editor for Breakout, opened from the launcher.
+//!
+//! The editor shows one of the files in `LEVEL_FILES` on a grid of brick slots, as many full sized
+//! ones as fit in the arena, or more and smaller to fit a bigger level. Left clicking or dragging
+//! paints the chosen kind of brick, picked with 1 to 4, and right clicking erases. Each stroke can
+//! be undone with Ctrl+Z and redone with Ctrl+Y. Ctrl+S saves the level back to its file, keeping
+//! the comments at the top and at least the size it was, and Page Up and Page Down move between
+//! the files. F5 or the test play button plays the level as it stands, and F5 again, or
+//! the end of the game, comes back to the editor.
+//!
+//! Saving writes to the asset folder on disk, so it's meant for running from the source tree.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{asset::io::file::FileAssetReader, prelude::*, window::PrimaryWindow};
+
+use super::{
+    levels::{BrickGrid, Level, COMMENT, LEVEL_FILES},
+    spawn_camera, Arena, AssetRoot, Brick, BrickKind, TEXT_COLOR,
+};
+use crate::{
+    display::cursor_to_world,
+    launcher::AppState,
+    pause::{highlight_buttons, spawn_menu_button, PauseState},
+};
+
+// `AssetPlugin`'s default folder, which level files are saved under
+const ASSET_FOLDER: &str = "assets";
+const TEST_PLAY_KEY: KeyCode = KeyCode::F5;
+
+const HUD_FONT_SIZE: f32 = 20.0;
+const HUD_PADDING: Val = Val::Px(8.0);
+const BUTTON_GAP: Val = Val::Px(10.0);
+const SLOT_COLOR: Color = Color::srgb(0.75, 0.75, 0.75);
+const HOVERED_SLOT_COLOR: Color = Color::srgb(0.1, 0.1, 0.1);
+const WALL_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+
+pub(super) struct LevelEditorPlugin;
+
+impl Plugin for LevelEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::BreakoutEditor), setup)
+            .add_systems(
+                Update,
+                (
+                    (
+                        handle_buttons,
+                        choose_brush,
+                        paint,
+                        undo_redo,
+                        switch_file,
+                        save_on_request,
+                        test_play_on_request,
+                    )
+                        .run_if(in_state(PauseState::Running)),
+                    highlight_buttons,
+                    redraw_bricks,
+                    draw_grid,
+                    update_hud,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::BreakoutEditor)),
+            )
+            .add_systems(
+                Update,
+                leave_test_play.run_if(in_state(AppState::Breakout).and_then(resource_exists::<TestLevel>)),
+            );
+    }
+}
+
+/// The level being test-played from the editor. While this exists Breakout plays just this level.
+#[derive(Resource)]
+pub(super) struct TestLevel(pub(super) Level);
+
+/// The level being edited. It stays around while the level is test-played, and after leaving the
+/// editor so that unsaved changes are still there on coming back.
+#[derive(Resource)]
+struct Editor {
+    /// Which of `LEVEL_FILES` is open
+    file: usize,
+    /// The comment lines at the top of the file, written back on saving
+    header: String,
+    /// The columns and rows in the file, which it's never saved smaller than
+    file_size: (usize, usize),
+    /// Always the size of the grid
+    level: Level,
+    /// The level as it is on disk
+    saved_level: Level,
+    undo: Vec<Level>,
+    redo: Vec<Level>,
+    /// The level before the stroke being painted, if there is one
+    stroke_start: Option<Level>,
+    brush: BrickKind,
+    status: String,
+    /// Switching files again throws away unsaved changes. Changing the level asks again.
+    discard_armed: bool,
+}
+
+impl Editor {
+    fn open(file: usize, asset_root: &Path, arena: &Arena) -> Self {
+        let path = level_path(asset_root, file);
+        let (header, level, status) = match fs::read_to_string(&path) {
+            Ok(text) => match Level::parse(&text) {
+                Ok(level) => {
+                    let header = text
+                        .lines()
+                        .take_while(|line| line.starts_with(COMMENT))
+                        .map(|line| format!("{line}\n"))
+                        .collect();
+                    (header, level, String::new())
+                }
+                Err(error) => (String::new(), Level::default(), format!("{} IS BROKEN: {error}", file_name(file))),
+            },
+            Err(error) => (String::new(), Level::default(), format!("COULDN'T OPEN {}: {error}", file_name(file))),
+        };
+
+        // The grid grows to fit levels too big for full sized bricks, shrinking them as in the game
+        let (columns, rows) = BrickGrid::capacity(arena);
+        let file_size = (level.columns(), level.rows.len());
+        let level = level.resized(columns.max(file_size.0), rows.max(file_size.1));
+        Editor {
+            file,
+            header,
+            file_size,
+            saved_level: level.clone(),
+            level,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            stroke_start: None,
+            brush: BrickKind::Plain,
+            status,
+            discard_armed: false,
+        }
+    }
+
+    fn has_unsaved_changes(&self) -> bool {
+        self.level != self.saved_level
+    }
+
+    fn grid(&self, arena: &Arena) -> BrickGrid {
+        BrickGrid::new(arena, self.level.columns(), self.level.rows.len())
+    }
+
+    /// What saving writes to the file. The grid's empty slots beyond the file's size are left out,
+    /// as they'd make the bricks smaller in the game.
+    fn file_text(&self) -> String {
+        let (columns, rows) = self.file_size;
+        format!("{}{}", self.header, self.level.trimmed(columns, rows).to_text())
+    }
+
+    fn save(&mut self, asset_root: &Path) {
+        if !self.level.has_breakable_bricks() {
+            self.status = String::from("NOTHING TO BREAK, NOT SAVED");
+            return;
+        }
+
+        self.status = match fs::write(level_path(asset_root, self.file), self.file_text()) {
+            Ok(()) => {
+                self.saved_level = self.level.clone();
+                format!("SAVED {}", file_name(self.file))
+            }
+            Err(error) => format!("COULDN'T SAVE {}: {error}", file_name(self.file)),
+        };
+    }
+}
+
+#[derive(Component)]
+struct EditorBrick;
+
+#[derive(Component)]
+struct EditorHud;
+
+#[derive(Component, Clone, Copy)]
+enum EditorButton {
+    TestPlay,
+    Save,
+}
+
+/// Where a level file is on disk
+fn level_path(asset_root: &Path, file: usize) -> PathBuf {
+    FileAssetReader::get_base_path().join(ASSET_FOLDER).join(asset_root).join(LEVEL_FILES[file])
+}
+
+fn file_name(file: usize) -> &'static str {
+    LEVEL_FILES[file].rsplit('/').next().unwrap()
+}
+
+fn brush_name(kind: BrickKind) -> &'static str {
+    match kind {
+        BrickKind::Plain => "PLAIN",
+        BrickKind::Tough => "TOUGH",
+        BrickKind::Indestructible => "INDESTRUCTIBLE",
+        BrickKind::Explosive => "EXPLOSIVE",
+    }
+}
+
+fn setup(mut commands: Commands, arena: Res<Arena>, asset_root: Res<AssetRoot>, editor: Option<ResMut<Editor>>) {
+    match editor {
+        // The bricks and HUD go with the editor's state, so coming back needs them drawn again
+        Some(mut editor) => editor.set_changed(),
+        None => commands.insert_resource(Editor::open(0, &asset_root.0, &arena)),
+    }
+    spawn_camera(&mut commands, arena.0, AppState::BreakoutEditor);
+
+    commands.spawn((
+        EditorHud,
+        StateScoped(AppState::BreakoutEditor),
+        TextBundle::from_section("", TextStyle { font_size: HUD_FONT_SIZE, color: TEXT_COLOR, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: HUD_PADDING,
+                left: HUD_PADDING,
+                ..default()
+            }),
+    ));
+
+    commands
+        .spawn((
+            StateScoped(AppState::BreakoutEditor),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: HUD_PADDING,
+                    right: HUD_PADDING,
+                    column_gap: BUTTON_GAP,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|buttons| {
+            spawn_menu_button(buttons, "TEST PLAY", EditorButton::TestPlay);
+            spawn_menu_button(buttons, "SAVE", EditorButton::Save);
+        });
+}
+
+/// The brick slot under the cursor, if there is one
+fn hovered_slot(
+    window_query: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+    editor: &Editor,
+    arena: &Arena,
+) -> Option<(usize, usize)> {
+    let cursor = window_query.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    let point = cursor_to_world(cursor, camera, camera_transform)?;
+
+    editor.grid(arena).slot_at(point, editor.level.columns(), editor.level.rows.len())
+}
+
+fn handle_buttons(
+    mut commands: Commands,
+    button_query: Query<(&Interaction, &EditorButton), Changed<Interaction>>,
+    mut editor: ResMut<Editor>,
+    asset_root: Res<AssetRoot>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            EditorButton::TestPlay => test_play(&mut commands, &mut editor, &mut next_state),
+            EditorButton::Save => editor.save(&asset_root.0),
+        }
+    }
+}
+
+fn choose_brush(keyboard_input: Res<ButtonInput<KeyCode>>, mut editor: ResMut<Editor>) {
+    let keys = [KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4];
+    for (key, kind) in keys.into_iter().zip(BrickKind::ALL) {
+        if keyboard_input.just_pressed(key) {
+            editor.brush = kind;
+        }
+    }
+}
+
+/// Paints with the left button and erases with the right, keeping what the level was like before
+/// each stroke for undo
+fn paint(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut editor: ResMut<Editor>,
+    arena: Res<Arena>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    button_query: Query<&Interaction, With<Button>>,
+) {
+    let buttons = [MouseButton::Left, MouseButton::Right];
+    // Clicking a button isn't painting
+    let over_button = button_query.iter().any(|interaction| *interaction != Interaction::None);
+    if mouse_input.any_just_pressed(buttons) && editor.stroke_start.is_none() && !over_button {
+        editor.stroke_start = Some(editor.level.clone());
+    }
+
+    if editor.stroke_start.is_some() {
+        let slot = if mouse_input.pressed(MouseButton::Left) {
+            Some(Some(editor.brush))
+        } else if mouse_input.pressed(MouseButton::Right) {
+            Some(None)
+        } else {
+            None
+        };
+
+        if let (Some(slot), Some((row, column))) = (slot, hovered_slot(&window_query, &camera_query, &editor, &arena)) {
+            if editor.level.rows[row][column] != slot {
+                editor.level.rows[row][column] = slot;
+                editor.discard_armed = false;
+            }
+        }
+    }
+
+    if !mouse_input.any_pressed(buttons) {
+        if let Some(stroke_start) = editor.stroke_start.take() {
+            if stroke_start != editor.level {
+                editor.undo.push(stroke_start);
+                editor.redo.clear();
+            }
+        }
+    }
+}
+
+fn undo_redo(keyboard_input: Res<ButtonInput<KeyCode>>, mut editor: ResMut<Editor>) {
+    let control = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    // Not in the middle of a stroke, which would be pushed onto the undo stack after the undo
+    if !control || editor.stroke_start.is_some() {
+        return;
+    }
+
+    let editor = editor.as_mut();
+    if keyboard_input.just_pressed(KeyCode::KeyZ) && !shift {
+        if let Some(level) = editor.undo.pop() {
+            editor.redo.push(std::mem::replace(&mut editor.level, level));
+            editor.discard_armed = false;
+        }
+    } else if keyboard_input.just_pressed(KeyCode::KeyY) || (keyboard_input.just_pressed(KeyCode::KeyZ) && shift) {
+        if let Some(level) = editor.redo.pop() {
+            editor.undo.push(std::mem::replace(&mut editor.level, level));
+            editor.discard_armed = false;
+        }
+    }
+}
+
+/// Opens the next or previous file, asking first before throwing away unsaved changes
+fn switch_file(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    asset_root: Res<AssetRoot>,
+    arena: Res<Arena>,
+) {
+    let step = if keyboard_input.just_pressed(KeyCode::PageDown) {
+        1
+    } else if keyboard_input.just_pressed(KeyCode::PageUp) {
+        LEVEL_FILES.len() - 1
+    } else {
+        return;
+    };
+
+    if editor.has_unsaved_changes() && !editor.discard_armed {
+        editor.discard_armed = true;
+        editor.status = String::from("UNSAVED CHANGES, PRESS AGAIN TO THROW THEM AWAY");
+        return;
+    }
+
+    let brush = editor.brush;
+    *editor = Editor::open((editor.file + step) % LEVEL_FILES.len(), &asset_root.0, &arena);
+    editor.brush = brush;
+}
+
+fn save_on_request(keyboard_input: Res<ButtonInput<KeyCode>>, mut editor: ResMut<Editor>, asset_root: Res<AssetRoot>) {
+    let control = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if control && keyboard_input.just_pressed(KeyCode::KeyS) {
+        editor.save(&asset_root.0);
+    }
+}
+
+fn test_play_on_request(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(TEST_PLAY_KEY) {
+        test_play(&mut commands, &mut editor, &mut next_state);
+    }
+}
+
+fn test_play(commands: &mut Commands, editor: &mut Editor, next_state: &mut NextState<AppState>) {
+    if !editor.level.has_breakable_bricks() {
+        editor.status = String::from("NOTHING TO BREAK");
+        return;
+    }
+
+    commands.insert_resource(TestLevel(editor.level.clone()));
+    next_state.set(AppState::Breakout);
+}
+
+fn leave_test_play(keyboard_input: Res<ButtonInput<KeyCode>>, mut next_state: ResMut<NextState<AppState>>) {
+    if keyboard_input.just_pressed(TEST_PLAY_KEY) {
+        next_state.set(AppState::BreakoutEditor);
+    }
+}
+
+/// Shows the level's bricks as they'd look in the game
+fn redraw_bricks(
+    mut commands: Commands,
+    editor: Res<Editor>,
+    arena: Res<Arena>,
+    brick_query: Query<Entity, With<EditorBrick>>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+
+    for entity in brick_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let grid = editor.grid(&arena);
+    for (row, bricks) in editor.level.rows.iter().enumerate() {
+        for (column, kind) in bricks.iter().enumerate() {
+            let Some(kind) = kind else {
+                continue;
+            };
+
+            commands.spawn((
+                EditorBrick,
+                StateScoped(AppState::BreakoutEditor),
+                SpriteBundle {
+                    sprite: Sprite { color: Brick::new(row, *kind).color(kind.hit_points().unwrap_or(0)), ..default() },
+                    transform: Transform {
+                        translation: grid.position(row, column).extend(0.0),
+                        scale: grid.brick_size.extend(1.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+/// Outlines the walls and every brick slot, with the one under the cursor picked out
+fn draw_grid(
+    mut gizmos: Gizmos,
+    editor: Res<Editor>,
+    arena: Res<Arena>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+) {
+    gizmos.rect_2d(arena.0.center(), 0.0, arena.0.size(), WALL_COLOR);
+
+    let grid = editor.grid(&arena);
+    let hovered = hovered_slot(&window_query, &camera_query, &editor, &arena);
+    for row in 0..editor.level.rows.len() {
+        for column in 0..editor.level.columns() {
+            let colour = if hovered == Some((row, column)) { HOVERED_SLOT_COLOR } else { SLOT_COLOR };
+            gizmos.rect_2d(grid.position(row, column), 0.0, grid.brick_size, colour);
+        }
+    }
+}
+
+fn update_hud(editor: Res<Editor>, mut query: Query<&mut Text, With<EditorHud>>) {
+    if !editor.is_changed() {
+        return;
+    }
+
+    let unsaved = if editor.has_unsaved_changes() { " (UNSAVED)" } else { "" };
+    let brushes = BrickKind::ALL
+        .iter()
+        .enumerate()
+        .map(|(index, kind)| {
+            let name = brush_name(*kind);
+            if *kind == editor.brush {
+                format!("[{} {name}]", index + 1)
+            } else {
+                format!("{} {name}", index + 1)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    let contents = format!(
+        "EDITING {}{unsaved}\n{brushes}\nLEFT CLICK PAINTS, RIGHT CLICK ERASES\nCTRL+Z UNDO  CTRL+Y REDO  CTRL+S SAVE\nPAGE UP/DOWN OTHER LEVELS  F5 TEST PLAY\n{}",
+        file_name(editor.file),
+        editor.status
+    );
+    for mut text in query.iter_mut() {
+        text.sections[0].value.clone_from(&contents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::breakout::{BOTTOM_WALL, LEFT_WALL, RIGHT_WALL, TOP_WALL};
+
+    #[test]
+    fn level_files_open_and_save_unchanged() {
+        let arena = Arena(Rect::new(LEFT_WALL, BOTTOM_WALL, RIGHT_WALL, TOP_WALL));
+        for (file, name) in LEVEL_FILES.iter().enumerate() {
+            let editor = Editor::open(file, Path::new(""), &arena);
+            assert!(editor.status.is_empty(), "{}", editor.status);
+            let text = fs::read_to_string(level_path(Path::new(""), file)).unwrap();
+            assert_eq!(editor.file_text(), text, "{name}");
+        }
+    }
+
+    #[test]
+    fn grid_grows_to_fit_wide_levels() {
+        let arena = Arena(Rect::new(LEFT_WALL, BOTTOM_WALL, RIGHT_WALL, TOP_WALL));
+        let (columns, rows) = BrickGrid::capacity(&arena);
+        // 04.level is wider than full sized bricks allow
+        let editor = Editor::open(3, Path::new(""), &arena);
+        assert!(editor.level.columns() > columns);
+        assert_eq!(editor.level.rows.len(), rows);
+    }
+
+    #[test]
+    fn undoing_asks_again_before_throwing_changes_away() {
+        let arena = Arena(Rect::new(LEFT_WALL, BOTTOM_WALL, RIGHT_WALL, TOP_WALL));
+        let mut editor = Editor::open(0, Path::new(""), &arena);
+        let before = editor.level.clone();
+        editor.level.rows[0][0] = if before.rows[0][0].is_some() { None } else { Some(BrickKind::Plain) };
+        editor.undo.push(before);
+        editor.discard_armed = true;
+
+        let mut world = World::new();
+        let mut keyboard_input = ButtonInput::<KeyCode>::default();
+        keyboard_input.press(KeyCode::ControlLeft);
+        keyboard_input.press(KeyCode::KeyZ);
+        world.insert_resource(keyboard_input);
+        world.insert_resource(editor);
+        world.run_system_once(undo_redo);
+        assert!(!world.resource::<Editor>().discard_armed);
+    }
+}
@@ -0,0 +1,426 @@
+//! Brick layouts read from level files, and the order they're played in.
+//!
+//! A level file is plain text with a line per row of bricks, from the top, and a character per
+//! brick:
+//!
+//! ```text
+//! ; Lines starting with a semicolon are comments
+//! TTTTTTTT
+//! ##.##.##
+//! #X*..*X#
+//! ```
+//!
+//! `#` is a plain brick, `T` tough, `X` indestructible, `*` explosive and `.` or a space an empty
+//! slot. Rows can be different lengths, the widest sets how many columns there are, up to
+//! `MAX_COLUMNS` by `MAX_ROWS`. Bricks are sized to fit the level's columns and rows into the
+//! arena. The level editor saves files in the same format.
+//!
+//! The best score on each level is kept in `best_scores.json` in the config folder, by level
+//! file, when `BreakoutConfig::keep_best_scores` is set.
+
+use std::{collections::BTreeMap, fmt, fs};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+
+use super::{Arena, BrickKind, GAP_BETWEEN_BRICKS, WALL_THICKNESS};
+use crate::settings::{config_dir, write_config_file};
+
+/// The levels in the order they're played, relative to the asset root. After the last one the
+/// sequence starts over.
+pub(super) const LEVEL_FILES: [&str; 4] =
+    ["levels/breakout/01.level", "levels/breakout/02.level", "levels/breakout/03.level", "levels/breakout/04.level"];
+
+// The largest a brick can be. Levels with lots of columns or rows get smaller bricks.
+const BRICK_SIZE: Vec2 = Vec2::new(100., 30.);
+// Space above the top row of bricks
+const GAP_BETWEEN_BRICKS_AND_CEILING: f32 = 20.0;
+// Space left at each side
+const GAP_BETWEEN_BRICKS_AND_SIDES: f32 = 20.0;
+// The lowest the bricks can reach, measured up from the bottom of the arena
+const LOWEST_BRICK_HEIGHT: f32 = 330.0;
+/// The most columns and rows a level can have. Any more and the bricks would be too small to
+/// see, or have no size at all once the gaps between them take up the arena.
+pub(super) const MAX_COLUMNS: usize = 32;
+pub(super) const MAX_ROWS: usize = 20;
+
+const BEST_SCORES_FILE: &str = "best_scores.json";
+
+const EMPTY: char = '.';
+pub(super) const COMMENT: char = ';';
+
+/// A layout of bricks, row by row from the top. `None` is a gap.
+#[derive(Asset, TypePath, Clone, Debug, PartialEq, Default)]
+pub(super) struct Level {
+    pub(super) rows: Vec<Vec<Option<BrickKind>>>,
+}
+
+impl Level {
+    pub(super) fn columns(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub(super) fn parse(text: &str) -> Result<Level, LevelError> {
+        let mut rows = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with(COMMENT) {
+                continue;
+            }
+
+            let row = line
+                .chars()
+                .map(|symbol| match symbol {
+                    EMPTY | ' ' => Ok(None),
+                    symbol => BrickKind::from_symbol(symbol)
+                        .map(Some)
+                        .ok_or(LevelError::UnknownBrick { line: line_number + 1, symbol }),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.push(row);
+        }
+
+        let level = Level { rows };
+        if level.columns() > MAX_COLUMNS || level.rows.len() > MAX_ROWS {
+            return Err(LevelError::TooBig { columns: level.columns(), rows: level.rows.len() });
+        }
+        if !level.has_breakable_bricks() {
+            return Err(LevelError::NothingToBreak);
+        }
+        Ok(level)
+    }
+
+    /// The level as the text of a level file, which parses back to the same level
+    pub(super) fn to_text(&self) -> String {
+        let columns = self.columns();
+        let mut text = String::new();
+        for row in &self.rows {
+            text.extend(row.iter().map(|slot| slot.map_or(EMPTY, BrickKind::symbol)));
+            // Pad short rows so that the file shows the level's full width
+            text.extend(std::iter::repeat_n(EMPTY, columns - row.len()));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The level cut down or padded out with gaps to exactly `columns` by `rows`
+    pub(super) fn resized(&self, columns: usize, rows: usize) -> Level {
+        let rows = (0..rows)
+            .map(|row| {
+                (0..columns)
+                    .map(|column| self.rows.get(row).and_then(|bricks| bricks.get(column)).copied().flatten())
+                    .collect()
+            })
+            .collect();
+        Level { rows }
+    }
+
+    /// The level without empty rows along the bottom or empty columns down the right, but still at
+    /// least `columns` by `rows`
+    pub(super) fn trimmed(&self, columns: usize, rows: usize) -> Level {
+        let used_rows = self.rows.iter().rposition(|row| row.iter().any(Option::is_some)).map_or(0, |row| row + 1);
+        let used_columns = self.rows.iter().filter_map(|row| row.iter().rposition(Option::is_some)).max();
+        let used_columns = used_columns.map_or(0, |column| column + 1);
+        self.resized(used_columns.max(columns), used_rows.max(rows))
+    }
+
+    pub(super) fn has_breakable_bricks(&self) -> bool {
+        self.rows.iter().flatten().flatten().any(|kind| *kind != BrickKind::Indestructible)
+    }
+}
+
+impl BrickKind {
+    pub(super) fn symbol(self) -> char {
+        match self {
+            BrickKind::Plain => '#',
+            BrickKind::Tough => 'T',
+            BrickKind::Indestructible => 'X',
+            BrickKind::Explosive => '*',
+        }
+    }
+
+    fn from_symbol(symbol: char) -> Option<BrickKind> {
+        BrickKind::ALL.into_iter().find(|kind| kind.symbol() == symbol)
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum LevelError {
+    Io(std::io::Error),
+    NotUtf8,
+    UnknownBrick {
+        line: usize,
+        symbol: char,
+    },
+    /// More than `MAX_COLUMNS` by `MAX_ROWS`
+    TooBig {
+        columns: usize,
+        rows: usize,
+    },
+    /// Every brick is indestructible, or there are none, so the level could never be cleared
+    NothingToBreak,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(error) => write!(f, "couldn't read the level: {error}"),
+            LevelError::NotUtf8 => write!(f, "the level isn't UTF-8 text"),
+            LevelError::UnknownBrick { line, symbol } => write!(f, "line {line} has an unknown brick '{symbol}'"),
+            LevelError::TooBig { columns, rows } => {
+                write!(f, "the level is {columns} by {rows} bricks, but the most is {MAX_COLUMNS} by {MAX_ROWS}")
+            }
+            LevelError::NothingToBreak => write!(f, "the level has no bricks that can be broken"),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<std::io::Error> for LevelError {
+    fn from(error: std::io::Error) -> Self {
+        LevelError::Io(error)
+    }
+}
+
+#[derive(Default)]
+pub(super) struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    type Asset = Level;
+    type Settings = ();
+    type Error = LevelError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Level, LevelError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let text = String::from_utf8(bytes).map_err(|_| LevelError::NotUtf8)?;
+        Level::parse(&text)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level"]
+    }
+}
+
+/// Where the bricks of a level go in the arena
+#[derive(Clone, Copy, Debug)]
+pub(super) struct BrickGrid {
+    /// Centre of the top left brick
+    origin: Vec2,
+    /// Distance between the centres of neighbouring bricks
+    pitch: Vec2,
+    pub(super) brick_size: Vec2,
+}
+
+/// The part of the arena that bricks can go in
+fn brick_area(arena: &Arena) -> Rect {
+    let arena = arena.0;
+    let area = Rect::new(
+        arena.min.x + WALL_THICKNESS / 2.0 + GAP_BETWEEN_BRICKS_AND_SIDES,
+        arena.min.y + LOWEST_BRICK_HEIGHT,
+        arena.max.x - WALL_THICKNESS / 2.0 - GAP_BETWEEN_BRICKS_AND_SIDES,
+        arena.max.y - WALL_THICKNESS / 2.0 - GAP_BETWEEN_BRICKS_AND_CEILING,
+    );
+    assert!(area.width() > 0.0 && area.height() > 0.0, "The arena is too small for any bricks");
+    area
+}
+
+impl BrickGrid {
+    /// Fits `columns` by `rows` bricks into the top of the arena, centred across it
+    pub(super) fn new(arena: &Arena, columns: usize, rows: usize) -> Self {
+        let area = brick_area(arena);
+
+        // Each brick takes its size plus a gap, except that the last one needs no gap after it
+        let fit = |space: f32, count: usize| (space + GAP_BETWEEN_BRICKS) / count.max(1) as f32 - GAP_BETWEEN_BRICKS;
+        let brick_size =
+            Vec2::new(fit(area.width(), columns).min(BRICK_SIZE.x), fit(area.height(), rows).min(BRICK_SIZE.y));
+        let pitch = brick_size + GAP_BETWEEN_BRICKS;
+
+        let total_width = columns as f32 * pitch.x - GAP_BETWEEN_BRICKS;
+        let origin =
+            Vec2::new(area.center().x - total_width / 2.0 + brick_size.x / 2.0, area.max.y - brick_size.y / 2.0);
+        BrickGrid { origin, pitch, brick_size }
+    }
+
+    /// How many columns and rows of full sized bricks fit in the arena
+    pub(super) fn capacity(arena: &Arena) -> (usize, usize) {
+        let area = brick_area(arena);
+        let count =
+            |space: f32, brick: f32| ((space + GAP_BETWEEN_BRICKS) / (brick + GAP_BETWEEN_BRICKS)).floor() as usize;
+        (count(area.width(), BRICK_SIZE.x), count(area.height(), BRICK_SIZE.y))
+    }
+
+    pub(super) fn position(&self, row: usize, column: usize) -> Vec2 {
+        self.origin + Vec2::new(column as f32 * self.pitch.x, -(row as f32) * self.pitch.y)
+    }
+
+    /// The row and column of the brick slot at a point, gaps included, if it's on the grid
+    pub(super) fn slot_at(&self, point: Vec2, columns: usize, rows: usize) -> Option<(usize, usize)> {
+        let offset = (point - self.origin) * Vec2::new(1.0, -1.0) / self.pitch + 0.5;
+        let (column, row) = (offset.x.floor(), offset.y.floor());
+        let on_grid = column >= 0.0 && row >= 0.0 && (column as usize) < columns && (row as usize) < rows;
+        on_grid.then_some((row as usize, column as usize))
+    }
+}
+
+/// Which level is being played
+#[derive(Resource)]
+pub(super) struct Levels {
+    handles: Vec<Handle<Level>>,
+    /// Playing a level from the editor rather than `LEVEL_FILES`
+    pub(super) testing: bool,
+    /// How many levels have been started this game, so 1 on the first level
+    pub(super) number: usize,
+    /// The score when the current level started
+    pub(super) starting_score: usize,
+}
+
+impl Levels {
+    pub(super) fn load(asset_server: &AssetServer, asset_root: &std::path::Path) -> Self {
+        let handles = LEVEL_FILES.iter().map(|file| asset_server.load(asset_root.join(file))).collect();
+        Levels { handles, testing: false, number: 1, starting_score: 0 }
+    }
+
+    /// Plays just the one level, which is being test-played from the editor
+    pub(super) fn test(level: Handle<Level>) -> Self {
+        Levels { handles: vec![level], testing: true, number: 1, starting_score: 0 }
+    }
+
+    fn index(&self) -> usize {
+        (self.number - 1) % self.handles.len()
+    }
+
+    /// Where the current level is in `LEVEL_FILES`, or `None` for a level being tested
+    pub(super) fn file_index(&self) -> Option<usize> {
+        (!self.testing).then(|| self.index())
+    }
+
+    pub(super) fn current(&self) -> &Handle<Level> {
+        &self.handles[self.index()]
+    }
+}
+
+/// The most points scored on each level in `LEVEL_FILES`
+#[derive(Resource, Default)]
+pub(super) struct BestLevelScores {
+    scores: [Option<usize>; LEVEL_FILES.len()],
+    /// Whether a new best is saved to `BEST_SCORES_FILE`
+    saving: bool,
+}
+
+impl BestLevelScores {
+    /// The scores saved in the config folder, which new bests are saved back to. Scores that
+    /// can't be read are left alone, and the bests are only kept for as long as the app runs.
+    pub(super) fn load() -> Self {
+        let Some(path) = config_dir().map(|dir| dir.join(BEST_SCORES_FILE)) else {
+            return BestLevelScores::default();
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::from("{}"),
+            Err(error) => {
+                warn!("Couldn't read {}: {error}", path.display());
+                return BestLevelScores::default();
+            }
+        };
+        match BestLevelScores::parse(&text) {
+            Ok(scores) => scores,
+            Err(error) => {
+                warn!("Ignoring {}: {error}", path.display());
+                BestLevelScores::default()
+            }
+        }
+    }
+
+    /// Reads a best scores file, which maps level files to their best score. Levels that are no
+    /// longer in `LEVEL_FILES` are dropped.
+    fn parse(text: &str) -> Result<Self, serde_json::Error> {
+        let saved: BTreeMap<String, usize> = serde_json::from_str(text)?;
+        Ok(BestLevelScores { scores: LEVEL_FILES.map(|file| saved.get(file).copied()), saving: true })
+    }
+
+    fn to_text(&self) -> String {
+        let saved: BTreeMap<&str, usize> =
+            LEVEL_FILES.into_iter().zip(self.scores).filter_map(|(file, score)| Some((file, score?))).collect();
+        serde_json::to_string_pretty(&saved).expect("Scores are always serialisable")
+    }
+
+    pub(super) fn get(&self, index: usize) -> Option<usize> {
+        self.scores[index]
+    }
+
+    /// Keeps `score` as the best for level `index` of `LEVEL_FILES` if it beats the last best
+    pub(super) fn record(&mut self, index: usize, score: usize) {
+        if self.scores[index].is_some_and(|best| score <= best) {
+            return;
+        }
+        self.scores[index] = Some(score);
+        if self.saving {
+            write_config_file(BEST_SCORES_FILE, &self.to_text());
+        }
+    }
+}
+
+/// Played when a level file can't be loaded, so that the game can go on
+pub(super) fn fallback_level() -> Level {
+    Level::parse("########\n########\n########\n########\n").unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_files_round_trip_through_text() {
+        for file in LEVEL_FILES {
+            let text = fs::read_to_string(std::path::Path::new("assets").join(file)).unwrap();
+            let level = Level::parse(&text).unwrap();
+            assert_eq!(Level::parse(&level.to_text()).unwrap(), level, "{file}");
+        }
+    }
+
+    #[test]
+    fn short_rows_are_padded_and_comments_dropped() {
+        let level = Level::parse("; A comment\nT*\n\n#X.#\n").unwrap();
+        assert_eq!(level.to_text(), "T*..\n#X.#\n");
+        assert_eq!(Level::parse(&level.to_text()).unwrap(), level.resized(4, 2));
+    }
+
+    #[test]
+    fn oversize_levels_are_rejected() {
+        let row = |columns| "#".repeat(columns) + "\n";
+        assert!(Level::parse(&row(MAX_COLUMNS).repeat(MAX_ROWS)).is_ok());
+        assert!(matches!(
+            Level::parse(&row(MAX_COLUMNS + 1)),
+            Err(LevelError::TooBig { columns, rows: 1 }) if columns == MAX_COLUMNS + 1
+        ));
+        assert!(matches!(Level::parse(&row(1).repeat(MAX_ROWS + 1)), Err(LevelError::TooBig { .. })));
+    }
+
+    #[test]
+    fn largest_level_has_bricks_with_size() {
+        let grid = BrickGrid::new(&Arena(Rect::new(-450., -300., 450., 300.)), MAX_COLUMNS, MAX_ROWS);
+        assert!(grid.brick_size.x > 0. && grid.brick_size.y > 0., "{:?}", grid.brick_size);
+    }
+
+    #[test]
+    fn best_scores_round_trip_by_level_file() {
+        let mut scores = BestLevelScores::default();
+        scores.scores[1] = Some(1200);
+        let parsed = BestLevelScores::parse(&scores.to_text()).unwrap();
+        assert_eq!(parsed.scores, [None, Some(1200), None, None]);
+        assert!(parsed.saving);
+
+        let parsed = BestLevelScores::parse(r#"{ "levels/breakout/04.level": 30, "gone.level": 10 }"#).unwrap();
+        assert_eq!(parsed.scores, [None, None, None, Some(30)]);
+        assert!(BestLevelScores::parse("[1, 2]").is_err());
+    }
+}
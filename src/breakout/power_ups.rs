@@ -0,0 +1,443 @@
+//! Power-up capsules that fall from broken bricks.
+//!
+//! Catching a capsule with the paddle starts its power-up. Wide, laser, catch and slow last
+//! `POWER_UP_SECONDS`, and catching the same one again starts the time over. Multi-ball and
+//! extra life happen at once. Losing the last ball ends every power-up and clears away
+//! whatever is still falling.
+
+use bevy::{math::bounding::Aabb2d, prelude::*};
+use rand::prelude::*;
+
+use super::{
+    paddle_bounce, serve_position, spawn_ball, Arena, Ball, BallAssets, Lives, Paddle, PaddleInput, Rally, Velocity,
+    TEXT_COLOR,
+};
+use crate::{
+    damage::{CollisionLayer, CollisionMask, DamageEvent},
+    first_hit,
+    launcher::AppState,
+};
+
+// Chance that a broken brick drops a capsule
+const CAPSULE_DROP_CHANCE: f64 = 0.15;
+const CAPSULE_SIZE: Vec2 = Vec2::new(40.0, 16.0);
+const CAPSULE_SPEED: f32 = 150.0;
+const CAPSULE_FONT_SIZE: f32 = 14.0;
+const CAPSULE_TEXT_COLOR: Color = Color::srgb(1.0, 1.0, 1.0);
+// Capsules fall in front of the bricks but behind the ball
+const CAPSULE_Z: f32 = 0.5;
+
+const POWER_UP_SECONDS: f32 = 15.0;
+const WIDE_PADDLE_SCALE: f32 = 1.5;
+const SLOW_BALL_SCALE: f32 = 0.6;
+// Angle either side of the first ball that multi-ball sends the new balls off at, in radians
+const MULTI_BALL_SPREAD: f32 = 0.4;
+
+const LASER_SIZE: Vec2 = Vec2::new(4.0, 16.0);
+const LASER_SPEED: f32 = 700.0;
+const LASER_COOLDOWN_SECONDS: f32 = 0.3;
+const LASER_COLOR: Color = Color::srgb(0.9, 0.2, 0.2);
+// Lasers go past the paddle they're fired from
+const LASER_MASK: CollisionMask = CollisionMask::of(&[CollisionLayer::WALL, CollisionLayer::BRICK]);
+const LASER_DAMAGE: u32 = 1;
+
+const HUD_FONT_SIZE: f32 = 30.0;
+const HUD_TEXT_PADDING: Val = Val::Px(5.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum PowerUp {
+    /// Two more balls split off the first one
+    MultiBall,
+    /// A wider paddle
+    Wide,
+    /// The paddle fires lasers that break bricks
+    Laser,
+    /// The paddle holds on to balls it catches until fire is pressed
+    Catch,
+    /// Slower balls
+    Slow,
+    ExtraLife,
+}
+
+impl PowerUp {
+    const ALL: [PowerUp; 6] =
+        [PowerUp::MultiBall, PowerUp::Wide, PowerUp::Laser, PowerUp::Catch, PowerUp::Slow, PowerUp::ExtraLife];
+
+    /// The letter on its capsule
+    fn letter(self) -> &'static str {
+        match self {
+            PowerUp::MultiBall => "M",
+            PowerUp::Wide => "W",
+            PowerUp::Laser => "L",
+            PowerUp::Catch => "C",
+            PowerUp::Slow => "S",
+            PowerUp::ExtraLife => "+",
+        }
+    }
+
+    /// What the HUD calls it while it's on
+    fn name(self) -> &'static str {
+        match self {
+            PowerUp::MultiBall => "MULTI-BALL",
+            PowerUp::Wide => "WIDE",
+            PowerUp::Laser => "LASER",
+            PowerUp::Catch => "CATCH",
+            PowerUp::Slow => "SLOW",
+            PowerUp::ExtraLife => "EXTRA LIFE",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            PowerUp::MultiBall => Color::srgb(0.2, 0.7, 0.9),
+            PowerUp::Wide => Color::srgb(0.3, 0.3, 0.9),
+            PowerUp::Laser => LASER_COLOR,
+            PowerUp::Catch => Color::srgb(0.2, 0.7, 0.2),
+            PowerUp::Slow => Color::srgb(0.9, 0.5, 0.1),
+            PowerUp::ExtraLife => Color::srgb(0.5, 0.5, 0.5),
+        }
+    }
+}
+
+/// A falling capsule, caught by touching the paddle
+#[derive(Component)]
+pub(crate) struct Capsule(PowerUp);
+
+#[derive(Component)]
+pub(crate) struct Laser;
+
+/// A ball held by the catch power-up, this far across from the middle of the paddle
+#[derive(Component)]
+pub(super) struct Caught {
+    offset: f32,
+}
+
+/// The power-ups that last a while, and how long each has left
+#[derive(Resource, Default)]
+pub(super) struct ActivePowerUps {
+    timers: Vec<(PowerUp, Timer)>,
+    /// Seconds until the laser can fire again
+    laser_cooldown: f32,
+}
+
+impl ActivePowerUps {
+    pub(super) fn is_active(&self, power_up: PowerUp) -> bool {
+        self.timers.iter().any(|(active, _)| *active == power_up)
+    }
+
+    fn start(&mut self, power_up: PowerUp) {
+        match self.timers.iter_mut().find(|(active, _)| *active == power_up) {
+            Some((_, timer)) => timer.reset(),
+            None => self.timers.push((power_up, Timer::from_seconds(POWER_UP_SECONDS, TimerMode::Once))),
+        }
+    }
+
+    pub(super) fn paddle_width_scale(&self) -> f32 {
+        if self.is_active(PowerUp::Wide) {
+            WIDE_PADDLE_SCALE
+        } else {
+            1.0
+        }
+    }
+
+    pub(super) fn ball_speed_scale(&self) -> f32 {
+        if self.is_active(PowerUp::Slow) {
+            SLOW_BALL_SCALE
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Decides what broken bricks drop
+#[derive(Resource)]
+pub(super) struct CapsuleRng(pub(super) StdRng);
+
+#[derive(Component)]
+pub(super) struct PowerUpHud;
+
+/// Maybe drops a capsule from a brick that just broke
+pub(super) fn drop_capsule(commands: &mut Commands, rng: &mut CapsuleRng, position: Vec2) {
+    if !rng.0.gen_bool(CAPSULE_DROP_CHANCE) {
+        return;
+    }
+
+    let power_up = *PowerUp::ALL.choose(&mut rng.0).unwrap();
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite { color: power_up.color(), custom_size: Some(CAPSULE_SIZE), ..default() },
+                transform: Transform::from_translation(position.extend(CAPSULE_Z)),
+                ..default()
+            },
+            Capsule(power_up),
+            Velocity(Vec2::NEG_Y * CAPSULE_SPEED),
+            StateScoped(AppState::Breakout),
+        ))
+        .with_children(|capsule| {
+            capsule.spawn(Text2dBundle {
+                text: Text::from_section(
+                    power_up.letter(),
+                    TextStyle { font_size: CAPSULE_FONT_SIZE, color: CAPSULE_TEXT_COLOR, ..default() },
+                ),
+                transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                ..default()
+            });
+        });
+}
+
+/// Starts the power-up of any capsule that met the paddle this tick, and despawns capsules that
+/// fell past it
+pub(super) fn catch_capsules(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    time: Res<Time>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    mut lives: ResMut<Lives>,
+    ball_assets: Res<BallAssets>,
+    capsule_query: Query<(Entity, &Transform, &Velocity, &Capsule)>,
+    paddle_query: Query<(&Transform, &Paddle)>,
+    ball_query: Query<(&Transform, &Velocity), With<Ball>>,
+) {
+    let (paddle_transform, paddle) = paddle_query.single();
+    let paddle_box = Aabb2d::new(paddle_transform.translation.truncate(), paddle_transform.scale.truncate() / 2.0);
+    let paddle_movement = Vec2::X * paddle.velocity * time.delta_seconds();
+
+    for (capsule_entity, capsule_transform, velocity, Capsule(power_up)) in capsule_query.iter() {
+        let capsule_centre = capsule_transform.translation.truncate();
+        if capsule_centre.y < arena.0.min.y - CAPSULE_SIZE.y {
+            commands.entity(capsule_entity).despawn_recursive();
+            continue;
+        }
+
+        // Both have already moved this tick, so the capsule is swept back over the way it came
+        let movement = **velocity * time.delta_seconds();
+        let capsule_box = Aabb2d::new(capsule_centre - movement + paddle_movement, CAPSULE_SIZE / 2.0);
+        if first_hit(capsule_box, movement, [((), paddle_box, paddle_movement)].into_iter()).is_none() {
+            continue;
+        }
+
+        commands.entity(capsule_entity).despawn_recursive();
+        match power_up {
+            PowerUp::MultiBall => split_ball(&mut commands, &ball_assets, &ball_query),
+            PowerUp::ExtraLife => **lives += 1,
+            PowerUp::Wide | PowerUp::Laser | PowerUp::Catch | PowerUp::Slow => power_ups.start(*power_up),
+        }
+    }
+}
+
+/// Sends two new balls off from the first ball in play, angled either side of it
+fn split_ball(
+    commands: &mut Commands,
+    ball_assets: &BallAssets,
+    ball_query: &Query<(&Transform, &Velocity), With<Ball>>,
+) {
+    let Some((ball_transform, velocity)) = ball_query.iter().find(|(_, velocity)| ***velocity != Vec2::ZERO) else {
+        return;
+    };
+
+    for angle in [-MULTI_BALL_SPREAD, MULTI_BALL_SPREAD] {
+        let ball = spawn_ball(commands, ball_assets, ball_transform.translation);
+        commands.entity(ball).insert(Velocity(Vec2::from_angle(angle).rotate(**velocity)));
+    }
+}
+
+/// Counts down the power-ups and ends the ones that have run out
+pub(super) fn tick_power_ups(mut power_ups: ResMut<ActivePowerUps>, time: Res<Time>) {
+    power_ups.laser_cooldown = (power_ups.laser_cooldown - time.delta_seconds()).max(0.0);
+    for (_, timer) in power_ups.timers.iter_mut() {
+        timer.tick(time.delta());
+    }
+    power_ups.timers.retain(|(_, timer)| !timer.finished());
+}
+
+/// Ends every power-up and clears away capsules and lasers, for when the last ball is lost
+pub(super) fn clear_power_ups(
+    commands: &mut Commands,
+    power_ups: &mut ActivePowerUps,
+    leftovers: &Query<Entity, Or<(With<Capsule>, With<Laser>)>>,
+) {
+    *power_ups = ActivePowerUps::default();
+    for entity in leftovers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Fires a laser from each end of the paddle while the laser power-up is on and fire is held
+pub(super) fn fire_lasers(
+    mut commands: Commands,
+    input: Res<PaddleInput>,
+    mut power_ups: ResMut<ActivePowerUps>,
+    paddle_query: Query<&Transform, With<Paddle>>,
+) {
+    if !input.fire || !power_ups.is_active(PowerUp::Laser) || power_ups.laser_cooldown > 0.0 {
+        return;
+    }
+
+    power_ups.laser_cooldown = LASER_COOLDOWN_SECONDS;
+    let paddle_transform = paddle_query.single();
+    let half_width = paddle_transform.scale.x / 2.0;
+    for side in [-1.0, 1.0] {
+        let position = paddle_transform.translation.truncate()
+            + Vec2::new(side * (half_width - LASER_SIZE.x), (paddle_transform.scale.y + LASER_SIZE.y) / 2.0);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite { color: LASER_COLOR, ..default() },
+                transform: Transform { translation: position.extend(0.0), scale: LASER_SIZE.extend(1.0), ..default() },
+                ..default()
+            },
+            Laser,
+            LASER_MASK,
+            Velocity(Vec2::Y * LASER_SPEED),
+            StateScoped(AppState::Breakout),
+        ));
+    }
+}
+
+/// Lasers stop at the first thing they hit on their way this tick, damaging it if it can be damaged
+pub(super) fn check_laser_hits(
+    mut commands: Commands,
+    time: Res<Time>,
+    laser_query: Query<(Entity, &Transform, &Velocity, &CollisionMask), With<Laser>>,
+    collider_query: Query<(Entity, &Transform, &CollisionLayer)>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for (laser_entity, laser_transform, velocity, laser_mask) in laser_query.iter() {
+        // Lasers have already moved this tick, so each is swept back over the way it came
+        let movement = **velocity * time.delta_seconds();
+        let laser_box = Aabb2d::new(laser_transform.translation.truncate() - movement, LASER_SIZE / 2.0);
+        let colliders = collider_query.iter().filter(|(_, _, layer)| laser_mask.hits(**layer)).map(
+            |(collider_entity, collider_transform, _)| {
+                let collider_box =
+                    Aabb2d::new(collider_transform.translation.truncate(), collider_transform.scale.truncate() / 2.0);
+                (collider_entity, collider_box, Vec2::ZERO)
+            },
+        );
+
+        if let Some(collider_entity) = first_hit(laser_box, movement, colliders) {
+            damage_events.send(DamageEvent { target: collider_entity, source: laser_entity, amount: LASER_DAMAGE });
+            commands.entity(laser_entity).despawn();
+        }
+    }
+}
+
+/// Where a caught ball sits on the paddle. Kept on it even if the paddle has shrunk since.
+fn caught_position(paddle_transform: &Transform, caught: &Caught) -> Vec3 {
+    let half_width = paddle_transform.scale.x / 2.0;
+    serve_position(paddle_transform.translation) + Vec3::X * caught.offset.clamp(-half_width, half_width)
+}
+
+/// Stops a ball that landed on the paddle while the catch power-up is on
+pub(super) fn catch_ball(commands: &mut Commands, ball: Entity, ball_x: f32, paddle_transform: &Transform) {
+    let offset = ball_x - paddle_transform.translation.x;
+    commands.entity(ball).insert((Caught { offset }, Velocity(Vec2::ZERO)));
+}
+
+/// Carries caught balls along with the paddle, and lets them go when fire is pressed. Holding
+/// fire, say for the lasers, doesn't let go of balls as they're caught.
+pub(super) fn hold_caught_balls(
+    mut commands: Commands,
+    input: Res<PaddleInput>,
+    rally: Res<Rally>,
+    power_ups: Res<ActivePowerUps>,
+    paddle_query: Query<(&Transform, &Paddle)>,
+    mut ball_query: Query<(Entity, &mut Transform, &mut Velocity, &Caught), (With<Ball>, Without<Paddle>)>,
+) {
+    let (paddle_transform, paddle) = paddle_query.single();
+    for (ball_entity, mut ball_transform, mut velocity, caught) in ball_query.iter_mut() {
+        ball_transform.translation = caught_position(paddle_transform, caught);
+
+        if input.fire_pressed {
+            let speed = rally.ball_speed() * power_ups.ball_speed_scale();
+            **velocity = paddle_bounce(ball_transform.translation.x, paddle_transform, paddle, speed);
+            commands.entity(ball_entity).remove::<Caught>();
+        }
+    }
+}
+
+pub(super) fn spawn_power_up_hud(mut commands: Commands) {
+    commands.spawn((
+        PowerUpHud,
+        StateScoped(AppState::Breakout),
+        TextBundle::from_section("", TextStyle { font_size: HUD_FONT_SIZE, color: TEXT_COLOR, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: HUD_TEXT_PADDING,
+                right: HUD_TEXT_PADDING,
+                ..default()
+            }),
+    ));
+}
+
+/// Lists the power-ups that are on, with the seconds each has left
+pub(super) fn update_power_up_hud(power_ups: Res<ActivePowerUps>, mut query: Query<&mut Text, With<PowerUpHud>>) {
+    let contents = power_ups
+        .timers
+        .iter()
+        .map(|(power_up, timer)| format!("{} {:.0}", power_up.name(), timer.remaining_secs().ceil()))
+        .collect::<Vec<_>>()
+        .join("   ");
+
+    for mut text in query.iter_mut() {
+        text.sections[0].value.clone_from(&contents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    const TICK: Duration = Duration::from_micros(15_625);
+
+    fn world_after_one_tick() -> World {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(TICK);
+        world.insert_resource(time);
+        world.init_resource::<Events<DamageEvent>>();
+        world
+    }
+
+    fn spawn_brick(world: &mut World, centre: Vec2, size: Vec2) -> Entity {
+        let transform = Transform::from_translation(centre.extend(0.0)).with_scale(size.extend(1.0));
+        world.spawn((transform, CollisionLayer::BRICK)).id()
+    }
+
+    #[test]
+    fn fast_laser_hits_the_nearest_brick_it_passed() {
+        let mut world = world_after_one_tick();
+        // Both thinner than the laser and passed over this tick, with the further one spawned first
+        spawn_brick(&mut world, Vec2::new(0.0, 60.0), Vec2::new(100.0, 2.0));
+        let near = spawn_brick(&mut world, Vec2::new(0.0, 30.0), Vec2::new(100.0, 2.0));
+        let velocity = Vec2::Y * 100.0 / TICK.as_secs_f32();
+        let laser = world
+            .spawn((Laser, LASER_MASK, Velocity(velocity), Transform::from_translation(Vec3::new(0.0, 100.0, 0.0))))
+            .id();
+
+        world.run_system_once(check_laser_hits);
+        let damage: Vec<_> = world.resource::<Events<DamageEvent>>().iter_current_update_events().collect();
+        assert_eq!(damage.len(), 1);
+        assert_eq!((damage[0].target, damage[0].source), (near, laser));
+        assert!(world.get_entity(laser).is_none());
+    }
+
+    #[test]
+    fn capsule_falling_past_the_paddle_in_one_tick_is_caught() {
+        let mut world = world_after_one_tick();
+        world.insert_resource(Arena(Rect::new(-500.0, -300.0, 500.0, 300.0)));
+        world.init_resource::<ActivePowerUps>();
+        world.insert_resource(Lives(3));
+        world.insert_resource(BallAssets { mesh: Handle::default(), material: Handle::default() });
+        world.spawn((Paddle::default(), Transform::from_scale(Vec3::new(120.0, 20.0, 1.0))));
+        // Above the paddle at the start of the tick and below it at the end
+        let velocity = Vec2::NEG_Y * 100.0 / TICK.as_secs_f32();
+        world.spawn((Capsule(PowerUp::Slow), Velocity(velocity), Transform::from_xyz(0.0, -50.0, 0.0)));
+
+        world.run_system_once(catch_capsules);
+        assert!(world.resource::<ActivePowerUps>().is_active(PowerUp::Slow));
+    }
+}
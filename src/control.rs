@@ -0,0 +1,448 @@
+//! Lets an external program play through a JSON-lines protocol.
+//!
+//! `--control stdio` talks over stdin and stdout, `--control <address>` listens for one TCP client
+//! at a time on that address (e.g. `--control 127.0.0.1:7100`). The controller plays player 1 of
+//! Space Invaders, or the player given by `--control-player <1|2>`, or with `--game breakout` the
+//! paddle in Breakout.
+//!
+//! Before every fixed tick the game sends one line describing the controlled player's board:
+//!
+//! ```text
+//! {"tick":12,"turret_x":-2.5,"bullets":[[-2.5,-280.0]],"invader_bullets":[],"invaders":[{"type":"A","x":-305.0,"y":270.0}],"score":0,"lives":3,"wave":1,"game_over":false}
+//! ```
+//!
+//! or in Breakout the paddle, the balls and the bricks, whose type is `plain`, `tough`,
+//! `indestructible` or `explosive`:
+//!
+//! ```text
+//! {"tick":12,"paddle_x":-2.5,"paddle_width":120.0,"balls":[[-2.5,-214.0]],"bricks":[{"type":"plain","x":-315.0,"y":255.0}],"score":0,"lives":3,"level":1,"game_over":false}
+//! ```
+//!
+//! Positions are in pixels from the centre of the window, with y up. The controller replies with
+//! lines like `{"left":true,"fire":true}`, where missing fields count as false. In Breakout fire
+//! serves the ball or lets go of a caught one when it's sent after an action without it, and
+//! fires lasers for as long as it's sent. In real time (the default) the game uses the latest
+//! action it has received; with `--lockstep` it waits for an action before every tick, and
+//! doesn't start until a controller connects. After the game ends a final state with
+//! `"game_over":true` is sent.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arg_value,
+    breakout::{self, Ball, Brick, BrickKind, Paddle, PaddleInput},
+    input::{LocalInputs, PlayerInput},
+    launcher::AppState,
+    players::Players,
+    BoardId, Bullet, GameState, Invader, InvaderBullet, InvaderType, PlayerId, Turret,
+};
+
+// How often a lockstep game waiting for an action checks whether the controller has gone
+const LOCKSTEP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct ControlConfig {
+    transport: Transport,
+    player: usize,
+    lockstep: bool,
+}
+
+enum Transport {
+    Stdio,
+    Tcp(SocketAddr),
+}
+
+impl ControlConfig {
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let transport = match arg_value(args, "--control")? {
+            "stdio" => Transport::Stdio,
+            address => {
+                Transport::Tcp(address.parse().expect("--control expects stdio or an address like 127.0.0.1:7100"))
+            }
+        };
+
+        Some(ControlConfig {
+            transport,
+            player: Self::player_from_args(args),
+            lockstep: args.iter().any(|arg| arg == "--lockstep"),
+        })
+    }
+
+    /// The player an external controller plays, as an index into `Players::states`
+    pub fn player_from_args(args: &[String]) -> usize {
+        match arg_value(args, "--control-player") {
+            Some("2") => 1,
+            _ => 0,
+        }
+    }
+
+    /// Whether the controller talks over stdout, which then can't be used for logging
+    pub fn uses_stdio(args: &[String]) -> bool {
+        arg_value(args, "--control") == Some("stdio")
+    }
+}
+
+#[derive(Serialize)]
+struct StateMessage {
+    tick: u64,
+    /// Missing while the player has no turret on the field
+    turret_x: Option<f32>,
+    bullets: Vec<[f32; 2]>,
+    invader_bullets: Vec<[f32; 2]>,
+    invaders: Vec<InvaderMessage>,
+    score: u32,
+    lives: u32,
+    wave: u32,
+    game_over: bool,
+}
+
+#[derive(Serialize)]
+struct InvaderMessage {
+    #[serde(rename = "type")]
+    invader_type: &'static str,
+    x: f32,
+    y: f32,
+}
+
+#[derive(Serialize)]
+struct PaddleStateMessage {
+    tick: u64,
+    paddle_x: f32,
+    paddle_width: f32,
+    balls: Vec<[f32; 2]>,
+    bricks: Vec<BrickMessage>,
+    score: usize,
+    lives: u32,
+    level: usize,
+    game_over: bool,
+}
+
+#[derive(Serialize)]
+struct BrickMessage {
+    #[serde(rename = "type")]
+    brick_type: &'static str,
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ActionMessage {
+    left: bool,
+    right: bool,
+    fire: bool,
+}
+
+type SharedWriter = Arc<Mutex<Option<Box<dyn Write + Send>>>>;
+
+#[derive(Resource)]
+pub struct ExternalControl {
+    player: usize,
+    lockstep: bool,
+    actions: Mutex<Receiver<PlayerInput>>,
+    /// Where state lines go, `None` while no controller is connected
+    output: SharedWriter,
+    tick: u64,
+    game_over_sent: bool,
+}
+
+impl ExternalControl {
+    fn connected(&self) -> bool {
+        self.output.lock().is_ok_and(|output| output.is_some())
+    }
+
+    /// Whether to send the state this tick. Once the game is over it's only sent once.
+    fn state_due(&mut self, game_over: bool) -> bool {
+        if !game_over {
+            self.game_over_sent = false;
+        }
+        !self.game_over_sent
+    }
+
+    /// Sends the controller `message` and returns what to play this tick, which is `input` if
+    /// the controller hasn't said otherwise
+    fn exchange(&mut self, message: &impl Serialize, game_over: bool, mut input: PlayerInput) -> PlayerInput {
+        let line = serde_json::to_string(message).expect("State messages are always serialisable");
+        let sent = match self.output.lock().unwrap().as_mut() {
+            Some(output) => writeln!(output, "{line}").and_then(|_| output.flush()).is_ok(),
+            None => false,
+        };
+
+        if game_over {
+            self.game_over_sent = sent;
+            return input;
+        }
+        if !sent {
+            return input;
+        }
+        self.tick += 1;
+
+        let actions = self.actions.lock().unwrap();
+        if self.lockstep {
+            // Wait for the controller to answer. If it disconnects the previous action stays in place.
+            loop {
+                match actions.recv_timeout(LOCKSTEP_POLL_INTERVAL) {
+                    Ok(action) => {
+                        input = action;
+                        break;
+                    }
+                    Err(RecvTimeoutError::Timeout) if self.connected() => continue,
+                    Err(_) => break,
+                }
+            }
+        } else {
+            // Use the latest action, keeping any fire press from the ones it replaced
+            let mut fired = false;
+            while let Ok(action) = actions.try_recv() {
+                fired |= action.fire;
+                input = action;
+            }
+            input.fire |= fired;
+        }
+        input
+    }
+}
+
+pub fn add_control(app: &mut App, config: ControlConfig) {
+    let (sender, receiver) = mpsc::channel();
+    let output: SharedWriter = Arc::new(Mutex::new(None));
+
+    match config.transport {
+        Transport::Stdio => {
+            *output.lock().unwrap() = Some(Box::new(std::io::stdout()));
+            thread::spawn(move || read_actions(std::io::stdin().lock(), &sender));
+        }
+        Transport::Tcp(address) => {
+            let listener = TcpListener::bind(address)
+                .unwrap_or_else(|error| panic!("Couldn't listen for a controller on {address}: {error}"));
+            let output = output.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let Ok(writer) = stream.try_clone() else {
+                        continue;
+                    };
+                    info!("Controller connected from {:?}", stream.peer_addr());
+                    *output.lock().unwrap() = Some(Box::new(writer));
+                    read_actions(BufReader::new(stream), &sender);
+                    *output.lock().unwrap() = None;
+                    info!("Controller disconnected");
+                }
+            });
+        }
+    }
+
+    app.insert_resource(ExternalControl {
+        player: config.player,
+        lockstep: config.lockstep,
+        actions: Mutex::new(receiver),
+        output,
+        tick: 0,
+        game_over_sent: false,
+    })
+    .configure_sets(FixedUpdate, breakout::GameplaySet.run_if(simulation_running))
+    .add_systems(
+        FixedUpdate,
+        exchange_with_paddle_controller.in_set(breakout::InputSet).run_if(in_state(AppState::Breakout)),
+    );
+}
+
+/// Forwards each action line to the game until the controller goes away
+fn read_actions(reader: impl BufRead, sender: &Sender<PlayerInput>) {
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_action(&line) {
+            Ok(input) => {
+                if sender.send(input).is_err() {
+                    return;
+                }
+            }
+            Err(error) => warn!("Ignoring malformed controller action {line:?}: {error}"),
+        }
+    }
+}
+
+/// Reads an action line like `{"left":true,"fire":true}`
+pub(crate) fn parse_action(line: &str) -> serde_json::Result<PlayerInput> {
+    let action = serde_json::from_str::<ActionMessage>(line)?;
+    Ok(PlayerInput { left: action.left, right: action.right, fire: action.fire })
+}
+
+/// Run condition for the gameplay systems, which in lockstep wait for a controller to connect
+pub fn simulation_running(control: Option<Res<ExternalControl>>) -> bool {
+    control.is_none_or(|control| !control.lockstep || control.connected())
+}
+
+/// Sends the controller the state about to be simulated and applies its action to the player
+pub fn exchange_with_controller(
+    mut control: ResMut<ExternalControl>,
+    mut local_inputs: ResMut<LocalInputs>,
+    players: Res<Players>,
+    state: Res<State<GameState>>,
+    turret_query: Query<(&Transform, &PlayerId, &BoardId), With<Turret>>,
+    invader_query: Query<(&Transform, &BoardId, &Invader)>,
+    bullet_query: Query<(&Transform, &BoardId), With<Bullet>>,
+    invader_bullet_query: Query<(&Transform, &BoardId), With<InvaderBullet>>,
+) {
+    let game_over = *state.get() == GameState::GameOver;
+    if !control.state_due(game_over) {
+        return;
+    }
+
+    let player = control.player;
+    let turret = turret_query.iter().find(|(_, player_id, _)| player_id.0 == player);
+    let board_id = turret.map_or(players.board_of(player), |(_, _, board_id)| *board_id);
+
+    let mut bullets = positions_on_board(bullet_query.iter(), board_id);
+    bullets.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    let mut invader_bullets = positions_on_board(invader_bullet_query.iter(), board_id);
+    invader_bullets.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+
+    let mut invaders: Vec<InvaderMessage> = invader_query
+        .iter()
+        .filter(|(_, invader_board, _)| **invader_board == board_id)
+        .map(|(transform, _, invader)| InvaderMessage {
+            invader_type: match invader.invader_type {
+                InvaderType::A => "A",
+                InvaderType::B => "B",
+                InvaderType::C => "C",
+            },
+            x: transform.translation.x,
+            y: transform.translation.y,
+        })
+        .collect();
+    invaders.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+
+    let player_state = &players.states[player];
+    let message = StateMessage {
+        tick: control.tick,
+        turret_x: turret.map(|(transform, _, _)| transform.translation.x),
+        bullets,
+        invader_bullets,
+        invaders,
+        score: player_state.score,
+        lives: player_state.lives,
+        wave: player_state.wave,
+        game_over,
+    };
+
+    local_inputs.0[player] = control.exchange(&message, game_over, local_inputs.0[player]);
+}
+
+/// Sends the controller the Breakout game about to be simulated and applies its action to the
+/// paddle
+fn exchange_with_paddle_controller(world: &mut World) {
+    let Some(status) = breakout::Status::of(world) else {
+        return;
+    };
+    let game_over = status.lives == 0;
+    if !world.resource_mut::<ExternalControl>().state_due(game_over) {
+        return;
+    }
+
+    let (paddle_x, paddle_width) = world
+        .query_filtered::<&Transform, With<Paddle>>()
+        .iter(world)
+        .next()
+        .map_or((0.0, 0.0), |transform| (transform.translation.x, transform.scale.x));
+
+    let mut balls: Vec<[f32; 2]> = world
+        .query_filtered::<&Transform, With<Ball>>()
+        .iter(world)
+        .map(|transform| [transform.translation.x, transform.translation.y])
+        .collect();
+    balls.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+
+    let mut bricks: Vec<BrickMessage> = world
+        .query::<(&Transform, &Brick)>()
+        .iter(world)
+        .map(|(transform, brick)| BrickMessage {
+            brick_type: match brick.kind {
+                BrickKind::Plain => "plain",
+                BrickKind::Tough => "tough",
+                BrickKind::Indestructible => "indestructible",
+                BrickKind::Explosive => "explosive",
+            },
+            x: transform.translation.x,
+            y: transform.translation.y,
+        })
+        .collect();
+    bricks.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+
+    let input = world.resource::<PaddleInput>().to_player_input();
+    let mut control = world.resource_mut::<ExternalControl>();
+    let message = PaddleStateMessage {
+        tick: control.tick,
+        paddle_x,
+        paddle_width,
+        balls,
+        bricks,
+        score: status.score,
+        lives: status.lives,
+        level: status.level,
+        game_over,
+    };
+    let input = control.exchange(&message, game_over, input);
+    world.resource_mut::<PaddleInput>().set(input);
+}
+
+fn positions_on_board<'a>(
+    positions: impl Iterator<Item = (&'a Transform, &'a BoardId)>,
+    board_id: BoardId,
+) -> Vec<[f32; 2]> {
+    positions
+        .filter(|(_, entity_board)| **entity_board == board_id)
+        .map(|(transform, _)| [transform.translation.x, transform.translation.y])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_are_false() {
+        let input = parse_action(r#"{"left":true}"#).unwrap();
+        assert_eq!(input, PlayerInput { left: true, right: false, fire: false });
+        assert_eq!(parse_action("{}").unwrap(), PlayerInput::default());
+    }
+
+    #[test]
+    fn malformed_actions_are_errors() {
+        for line in [r#"{"left":tru"#, "left", "true", r#"{"fire":1}"#, "null", r#"{"left":true}{"#] {
+            assert!(parse_action(line).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped_and_the_rest_forwarded() {
+        let lines = "{\"left\":true}\nnot json\n\n{\"fire\":\n{\"right\":true,\"fire\":true}\n\u{fffd}\n";
+        let (sender, receiver) = mpsc::channel();
+        read_actions(std::io::Cursor::new(lines), &sender);
+
+        let received: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            received,
+            [
+                PlayerInput { left: true, right: false, fire: false },
+                PlayerInput { left: false, right: true, fire: true }
+            ]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Collision layers and damage, shared by both games.
+//!
+//! Anything that can be hit is on a `CollisionLayer`, and anything that hits things carries a
+//! `CollisionMask` of the layers it hits. A hit sends a `DamageEvent`, `apply_damage` takes it
+//! off the target's `Health`, and a `DeathEvent` goes out when the health runs out. What dying
+//! means is up to each game: invaders are scored, turrets lose a life and bricks break. Things
+//! with no `Health`, like walls and shots, take hits without ever dying.
+
+use bevy::prelude::*;
+
+/// What kind of thing a collider is, as a bit so that a `CollisionMask` can pick out several
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionLayer(u32);
+
+impl CollisionLayer {
+    // Space Invaders
+    pub const TURRET: CollisionLayer = CollisionLayer(1 << 0);
+    pub const INVADER: CollisionLayer = CollisionLayer(1 << 1);
+    pub const PLAYER_SHOT: CollisionLayer = CollisionLayer(1 << 2);
+    pub const INVADER_SHOT: CollisionLayer = CollisionLayer(1 << 3);
+
+    // Breakout
+    pub const WALL: CollisionLayer = CollisionLayer(1 << 4);
+    pub const PADDLE: CollisionLayer = CollisionLayer(1 << 5);
+    pub const BRICK: CollisionLayer = CollisionLayer(1 << 6);
+}
+
+/// The layers something hits. Anything on another layer it passes straight through.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollisionMask(u32);
+
+impl CollisionMask {
+    pub const fn of(layers: &[CollisionLayer]) -> Self {
+        let mut bits = 0;
+        let mut index = 0;
+        while index < layers.len() {
+            bits |= layers[index].0;
+            index += 1;
+        }
+        CollisionMask(bits)
+    }
+
+    pub fn hits(self, layer: CollisionLayer) -> bool {
+        self.0 & layer.0 != 0
+    }
+}
+
+/// Hits left before dying
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Health { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+
+    /// Takes `amount` off, returning whether that finished it off. Something already dead
+    /// takes no more damage.
+    pub fn take(&mut self, amount: u32) -> bool {
+        if self.is_dead() {
+            return false;
+        }
+        self.current = self.current.saturating_sub(amount);
+        self.is_dead()
+    }
+
+    /// Back to full health, for something that comes back after dying
+    pub fn restore(&mut self) {
+        self.current = self.max;
+    }
+}
+
+/// Asks `apply_damage` to take `amount` off the target's health
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    /// What did the damage, such as the shot or ball that hit
+    pub source: Entity,
+    pub amount: u32,
+}
+
+/// Sent once when something's health runs out
+#[derive(Event, Clone, Copy, Debug)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    /// The source of the damage that finished it off
+    pub source: Entity,
+}
+
+/// Takes damage off health, sending a `DeathEvent` for anything it finishes off. Damage to
+/// something already dead is ignored, so however many hits land in a tick it only dies once.
+pub fn apply_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut health_query: Query<&mut Health>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    for damage in damage_events.read() {
+        let Ok(mut health) = health_query.get_mut(damage.target) else {
+            continue;
+        };
+
+        if health.take(damage.amount) {
+            death_events.send(DeathEvent { entity: damage.target, source: damage.source });
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Debug overlay for seeing what the collision code sees.
+//!
+//! F3 toggles drawing every collider exactly as the collision code measures it. Space Invaders
+//! boxes come from `Sprite::custom_size`, falling back to 1×1 like `check_for_collisions` does, so
+//! a missing size shows up as a dot. Breakout boxes come from the transform's scale, and balls
+//! are drawn as the circle `move_balls` sweeps. Boxes are coloured by the collider's
+//! `CollisionLayer`. Bullets also get an arrow for
+//! their velocity, and each board gets the formation's bounding box and the edge limits at which
+//! `move_invaders` turns it around.
+//!
+//! While the overlay is on, a panel lists the collider under the cursor and its components.
+
+use bevy::{prelude::*, utils::get_short_name, window::PrimaryWindow};
+
+use crate::{
+    breakout::{Ball, BALL_DIAMETER},
+    damage::CollisionLayer,
+    difficulty::ActiveDifficulty,
+    display::cursor_to_world,
+    formation_edge_limits, Board, BoardId, Bullet, Invader, InvaderBullet,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+const TURRET_OUTLINE_COLOUR: Color = Color::srgb(0.2, 1.0, 0.2);
+const INVADER_OUTLINE_COLOUR: Color = Color::srgb(1.0, 0.2, 1.0);
+const BULLET_OUTLINE_COLOUR: Color = Color::srgb(0.2, 0.8, 1.0);
+const INVADER_BULLET_OUTLINE_COLOUR: Color = Color::srgb(1.0, 0.3, 0.2);
+const WALL_OUTLINE_COLOUR: Color = Color::srgb(0.2, 0.2, 1.0);
+const PADDLE_OUTLINE_COLOUR: Color = Color::srgb(0.2, 1.0, 0.2);
+const BRICK_OUTLINE_COLOUR: Color = Color::srgb(1.0, 0.2, 1.0);
+const BALL_OUTLINE_COLOUR: Color = Color::srgb(1.0, 0.6, 0.0);
+const OTHER_OUTLINE_COLOUR: Color = Color::srgb(1.0, 1.0, 1.0);
+const VELOCITY_COLOUR: Color = Color::srgb(1.0, 1.0, 0.2);
+const FORMATION_COLOUR: Color = Color::srgb(1.0, 0.6, 0.0);
+const EDGE_LIMIT_COLOUR: Color = Color::srgb(1.0, 0.1, 0.1);
+// Velocity arrows show how far a bullet travels in this many seconds
+const VELOCITY_ARROW_SECONDS: f32 = 0.1;
+const PANEL_FONT_SIZE: f32 = 16.0;
+const PANEL_PADDING: Val = Val::Px(8.0);
+const PANEL_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.7);
+const PANEL_TEXT_COLOUR: Color = Color::srgb(1.0, 1.0, 1.0);
+
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugOverlay>()
+            .add_systems(Startup, spawn_inspector_panel)
+            .add_systems(Update, toggle_overlay)
+            .add_systems(
+                Update,
+                (draw_colliders, draw_bullet_velocities, draw_formation_bounds, update_inspector_panel)
+                    .after(toggle_overlay)
+                    .run_if(|overlay: Res<DebugOverlay>| overlay.enabled),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+struct DebugOverlay {
+    enabled: bool,
+}
+
+#[derive(Component)]
+struct InspectorPanel;
+
+fn toggle_overlay(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+    mut panel_query: Query<&mut Visibility, With<InspectorPanel>>,
+) {
+    if !keyboard_input.just_pressed(TOGGLE_KEY) {
+        return;
+    }
+
+    overlay.enabled = !overlay.enabled;
+    for mut visibility in panel_query.iter_mut() {
+        *visibility = if overlay.enabled { Visibility::Visible } else { Visibility::Hidden };
+    }
+}
+
+/// The box the collision code uses for a collider. Only Space Invaders colliders have a board,
+/// and Breakout sizes its colliders by scaling them.
+fn collider_rect(transform: &Transform, sprite: &Sprite, on_board: bool) -> Rect {
+    let size = if on_board { sprite.custom_size.unwrap_or(Vec2::new(1.0, 1.0)) } else { transform.scale.truncate() };
+    Rect::from_center_size(transform.translation.truncate(), size)
+}
+
+fn draw_colliders(
+    mut gizmos: Gizmos,
+    collider_query: Query<(&Transform, &Sprite, &CollisionLayer, Has<BoardId>)>,
+    ball_query: Query<&Transform, With<Ball>>,
+) {
+    for (transform, sprite, layer, on_board) in collider_query.iter() {
+        let colour = match *layer {
+            CollisionLayer::TURRET => TURRET_OUTLINE_COLOUR,
+            CollisionLayer::INVADER => INVADER_OUTLINE_COLOUR,
+            CollisionLayer::PLAYER_SHOT => BULLET_OUTLINE_COLOUR,
+            CollisionLayer::INVADER_SHOT => INVADER_BULLET_OUTLINE_COLOUR,
+            CollisionLayer::WALL => WALL_OUTLINE_COLOUR,
+            CollisionLayer::PADDLE => PADDLE_OUTLINE_COLOUR,
+            CollisionLayer::BRICK => BRICK_OUTLINE_COLOUR,
+            _ => OTHER_OUTLINE_COLOUR,
+        };
+
+        let rect = collider_rect(transform, sprite, on_board);
+        gizmos.rect_2d(rect.center(), 0.0, rect.size(), colour);
+    }
+
+    for transform in ball_query.iter() {
+        gizmos.circle_2d(transform.translation.truncate(), BALL_DIAMETER / 2., BALL_OUTLINE_COLOUR);
+    }
+}
+
+fn draw_bullet_velocities(
+    mut gizmos: Gizmos,
+    bullet_query: Query<&Transform, With<Bullet>>,
+    invader_bullet_query: Query<&Transform, With<InvaderBullet>>,
+    difficulty: Option<Res<ActiveDifficulty>>,
+) {
+    // Player bullets fly up and invader bullets down, at the speeds the difficulty sets
+    let Some(difficulty) = difficulty else {
+        return;
+    };
+    let tuning = &difficulty.tuning;
+    let velocities = bullet_query
+        .iter()
+        .map(|transform| (transform, Vec2::Y * tuning.bullet_speed))
+        .chain(invader_bullet_query.iter().map(|transform| (transform, Vec2::NEG_Y * tuning.invader_bullet_speed)));
+
+    for (transform, velocity) in velocities {
+        let start = transform.translation.truncate();
+        gizmos.arrow_2d(start, start + velocity * VELOCITY_ARROW_SECONDS, VELOCITY_COLOUR);
+    }
+}
+
+fn draw_formation_bounds(
+    mut gizmos: Gizmos,
+    board_query: Query<&Board>,
+    invader_query: Query<(&Transform, &Sprite, &BoardId), With<Invader>>,
+) {
+    for board in board_query.iter() {
+        // `move_invaders` compares invader centres against the limits, so draw those
+        let (left_limit, right_limit) = formation_edge_limits(board);
+        for x in [left_limit, right_limit] {
+            gizmos.line_2d(Vec2::new(x, board.area.min.y), Vec2::new(x, board.area.max.y), EDGE_LIMIT_COLOUR);
+        }
+
+        let bounds = invader_query
+            .iter()
+            .filter(|(_, _, board_id)| **board_id == board.id)
+            .map(|(transform, sprite, _)| collider_rect(transform, sprite, true))
+            .reduce(|bounds, rect| bounds.union(rect));
+
+        if let Some(bounds) = bounds {
+            gizmos.rect_2d(bounds.center(), 0.0, bounds.size(), FORMATION_COLOUR);
+        }
+    }
+}
+
+fn spawn_inspector_panel(mut commands: Commands) {
+    commands.spawn((
+        InspectorPanel,
+        TextBundle::from_section("", TextStyle { font_size: PANEL_FONT_SIZE, color: PANEL_TEXT_COLOUR, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: PANEL_PADDING,
+                left: PANEL_PADDING,
+                padding: UiRect::all(PANEL_PADDING),
+                ..default()
+            })
+            .with_background_color(PANEL_BACKGROUND),
+        Visibility::Hidden,
+    ));
+}
+
+/// Fills the panel with the collider under the cursor. Exclusive so that it can list every
+/// component on the entity, whatever they are.
+fn update_inspector_panel(world: &mut World) {
+    let cursor =
+        world.query_filtered::<&Window, With<PrimaryWindow>>().get_single(world).ok().and_then(Window::cursor_position);
+
+    let cursor_world = cursor.and_then(|cursor| {
+        world
+            .query::<(&Camera, &GlobalTransform)>()
+            .iter(world)
+            .find_map(|(camera, camera_transform)| cursor_to_world(cursor, camera, camera_transform))
+    });
+
+    // Bullets and balls are drawn above everything else, so prefer whatever is nearest the viewer
+    let hovered = cursor_world.and_then(|point| {
+        let mut under_cursor: Vec<(Entity, Vec3)> = world
+            .query_filtered::<(Entity, &Transform, &Sprite, Has<BoardId>), With<CollisionLayer>>()
+            .iter(world)
+            .filter(|(_, transform, sprite, on_board)| collider_rect(transform, sprite, *on_board).contains(point))
+            .map(|(entity, transform, ..)| (entity, transform.translation))
+            .collect();
+        under_cursor.extend(
+            world
+                .query_filtered::<(Entity, &Transform), With<Ball>>()
+                .iter(world)
+                .filter(|(_, transform)| transform.translation.truncate().distance(point) <= BALL_DIAMETER / 2.)
+                .map(|(entity, transform)| (entity, transform.translation)),
+        );
+
+        under_cursor.into_iter().max_by(|(a_entity, a), (b_entity, b)| a.z.total_cmp(&b.z).then(a_entity.cmp(b_entity)))
+    });
+
+    let contents = match hovered {
+        Some((entity, translation)) => {
+            let mut components: Vec<String> =
+                world.inspect_entity(entity).into_iter().map(|info| get_short_name(info.name())).collect();
+            components.sort();
+            format!(
+                "{entity}\nposition ({:.1}, {:.1}, {:.1})\n{}",
+                translation.x,
+                translation.y,
+                translation.z,
+                components.join("\n")
+            )
+        }
+        None => match cursor_world {
+            Some(point) => format!("cursor ({:.1}, {:.1})\nnothing here", point.x, point.y),
+            None => String::from("cursor outside the window"),
+        },
+    };
+
+    for mut text in world.query_filtered::<&mut Text, With<InspectorPanel>>().iter_mut(world) {
+        text.sections[0].value.clone_from(&contents);
+    }
+}
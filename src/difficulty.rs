@@ -0,0 +1,340 @@
+//! Difficulty presets for Space Invaders.
+//!
+//! A `Difficulty` is one of the presets, Easy, Normal, Hard or Arcade, with any of its values
+//! overridden on the tuning screen. It works out to a `Tuning`: the lives each player starts
+//! with, the score that earns a bonus life, how often the invaders fire, how fast they march,
+//! how fast shots fly and how far down each wave starts. Normal is the game as it has always
+//! played. Arcade follows the cabinet, with a bonus life at 1500, slow bombs, a last invader that
+//! races across the screen and each wave starting lower than the one before.
+//!
+//! The difficulty is part of the `Settings`, or `--difficulty` picks a preset. A game keeps the
+//! difficulty it started at until it's started over, as `ActiveDifficulty`, and reports it in
+//! `GameOver` with the scores.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arg_value, BULLET_SPEED, INVADER_MIN_MOVE_INTERVAL, INVADER_MOVE_INTERVAL, INVADER_SHOOT_INTERVAL, STARTING_LIVES,
+};
+
+/// The furthest down a wave starts, in rows
+pub const MAX_WAVE_DROP: u32 = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Arcade,
+}
+
+impl Preset {
+    const ALL: [Preset; 4] = [Preset::Easy, Preset::Normal, Preset::Hard, Preset::Arcade];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Preset::Easy),
+            "normal" => Some(Preset::Normal),
+            "hard" => Some(Preset::Hard),
+            "arcade" => Some(Preset::Arcade),
+            _ => None,
+        }
+    }
+
+    /// The preset named by `--difficulty`, if there is one
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        arg_value(args, "--difficulty").map(|name| {
+            Preset::from_name(name).unwrap_or_else(|| panic!("--difficulty expects easy, normal, hard or arcade"))
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Easy => "EASY",
+            Preset::Normal => "NORMAL",
+            Preset::Hard => "HARD",
+            Preset::Arcade => "ARCADE",
+        }
+    }
+
+    pub fn next(self) -> Preset {
+        let index = Preset::ALL.iter().position(|preset| *preset == self).unwrap_or(0);
+        Preset::ALL[(index + 1) % Preset::ALL.len()]
+    }
+
+    pub fn tuning(self) -> Tuning {
+        match self {
+            Preset::Easy => Tuning {
+                starting_lives: 5,
+                bonus_life_score: 1000,
+                invader_shoot_interval: 3.0,
+                march_initial_interval: 1.2,
+                march_minimum_interval: 0.2,
+                bullet_speed: 450.,
+                invader_bullet_speed: 300.,
+                wave_drop: 0,
+            },
+            Preset::Normal => Tuning {
+                starting_lives: STARTING_LIVES,
+                bonus_life_score: 0,
+                invader_shoot_interval: INVADER_SHOOT_INTERVAL,
+                march_initial_interval: INVADER_MOVE_INTERVAL,
+                march_minimum_interval: INVADER_MIN_MOVE_INTERVAL,
+                bullet_speed: BULLET_SPEED,
+                invader_bullet_speed: BULLET_SPEED,
+                wave_drop: 0,
+            },
+            Preset::Hard => Tuning {
+                starting_lives: 3,
+                bonus_life_score: 0,
+                invader_shoot_interval: 1.2,
+                march_initial_interval: 0.8,
+                march_minimum_interval: 0.06,
+                bullet_speed: 400.,
+                invader_bullet_speed: 500.,
+                wave_drop: 1,
+            },
+            Preset::Arcade => Tuning {
+                starting_lives: 3,
+                bonus_life_score: 1500,
+                invader_shoot_interval: 1.5,
+                march_initial_interval: 1.0,
+                march_minimum_interval: 0.02,
+                bullet_speed: 500.,
+                invader_bullet_speed: 250.,
+                wave_drop: 1,
+            },
+        }
+    }
+}
+
+/// What a difficulty sets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    pub starting_lives: u32,
+    /// The score that earns each player one extra life, or 0 for none
+    pub bonus_life_score: u32,
+    /// Seconds between shots from each board's formation
+    pub invader_shoot_interval: f32,
+    /// Seconds between the formation's steps while it's whole. The steps come quicker as its
+    /// invaders are shot, down to `march_minimum_interval` apart.
+    pub march_initial_interval: f32,
+    pub march_minimum_interval: f32,
+    pub bullet_speed: f32,
+    pub invader_bullet_speed: f32,
+    /// How many rows further down each wave starts than the one before, up to `MAX_WAVE_DROP`
+    pub wave_drop: u32,
+}
+
+impl Tuning {
+    pub fn get(&self, value: TuningValue) -> f32 {
+        match value {
+            TuningValue::StartingLives => self.starting_lives as f32,
+            TuningValue::BonusLifeScore => self.bonus_life_score as f32,
+            TuningValue::InvaderShootInterval => self.invader_shoot_interval,
+            TuningValue::MarchInitialInterval => self.march_initial_interval,
+            TuningValue::MarchMinimumInterval => self.march_minimum_interval,
+            TuningValue::BulletSpeed => self.bullet_speed,
+            TuningValue::InvaderBulletSpeed => self.invader_bullet_speed,
+            TuningValue::WaveDrop => self.wave_drop as f32,
+        }
+    }
+
+    fn set(&mut self, value: TuningValue, amount: f32) {
+        let amount = value.clamp(amount);
+        match value {
+            TuningValue::StartingLives => self.starting_lives = amount as u32,
+            TuningValue::BonusLifeScore => self.bonus_life_score = amount as u32,
+            TuningValue::InvaderShootInterval => self.invader_shoot_interval = amount,
+            TuningValue::MarchInitialInterval => self.march_initial_interval = amount,
+            TuningValue::MarchMinimumInterval => self.march_minimum_interval = amount,
+            TuningValue::BulletSpeed => self.bullet_speed = amount,
+            TuningValue::InvaderBulletSpeed => self.invader_bullet_speed = amount,
+            TuningValue::WaveDrop => self.wave_drop = amount as u32,
+        }
+    }
+
+    /// How many rows down wave number `wave` starts
+    pub fn wave_start_drop(&self, wave: u32) -> u32 {
+        wave.saturating_sub(1).saturating_mul(self.wave_drop).min(MAX_WAVE_DROP)
+    }
+}
+
+/// One of the values in a `Tuning`, which can be overridden
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum TuningValue {
+    StartingLives,
+    BonusLifeScore,
+    InvaderShootInterval,
+    MarchInitialInterval,
+    MarchMinimumInterval,
+    BulletSpeed,
+    InvaderBulletSpeed,
+    WaveDrop,
+}
+
+impl TuningValue {
+    pub const ALL: [TuningValue; 8] = [
+        TuningValue::StartingLives,
+        TuningValue::BonusLifeScore,
+        TuningValue::InvaderShootInterval,
+        TuningValue::MarchInitialInterval,
+        TuningValue::MarchMinimumInterval,
+        TuningValue::BulletSpeed,
+        TuningValue::InvaderBulletSpeed,
+        TuningValue::WaveDrop,
+    ];
+
+    /// How the value is named in the settings file and by `--set`
+    pub fn key(self) -> &'static str {
+        match self {
+            TuningValue::StartingLives => "starting_lives",
+            TuningValue::BonusLifeScore => "bonus_life_score",
+            TuningValue::InvaderShootInterval => "invader_shoot_interval",
+            TuningValue::MarchInitialInterval => "march_initial_interval",
+            TuningValue::MarchMinimumInterval => "march_minimum_interval",
+            TuningValue::BulletSpeed => "bullet_speed",
+            TuningValue::InvaderBulletSpeed => "invader_bullet_speed",
+            TuningValue::WaveDrop => "wave_drop",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        TuningValue::ALL.into_iter().find(|value| value.key() == key)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TuningValue::StartingLives => "LIVES",
+            TuningValue::BonusLifeScore => "BONUS LIFE",
+            TuningValue::InvaderShootInterval => "FIRE EVERY",
+            TuningValue::MarchInitialInterval => "MARCH START",
+            TuningValue::MarchMinimumInterval => "MARCH FASTEST",
+            TuningValue::BulletSpeed => "SHOT SPEED",
+            TuningValue::InvaderBulletSpeed => "BOMB SPEED",
+            TuningValue::WaveDrop => "WAVE DROP",
+        }
+    }
+
+    /// How much one press of a tuning button changes the value
+    pub fn step(self) -> f32 {
+        match self {
+            TuningValue::StartingLives | TuningValue::WaveDrop => 1.,
+            TuningValue::BonusLifeScore => 500.,
+            TuningValue::InvaderShootInterval | TuningValue::MarchInitialInterval => 0.1,
+            TuningValue::MarchMinimumInterval => 0.02,
+            TuningValue::BulletSpeed | TuningValue::InvaderBulletSpeed => 50.,
+        }
+    }
+
+    /// The least and most the value can be
+    fn range(self) -> (f32, f32) {
+        match self {
+            TuningValue::StartingLives => (1., 9.),
+            TuningValue::BonusLifeScore => (0., 10000.),
+            TuningValue::InvaderShootInterval => (0.2, 5.),
+            TuningValue::MarchInitialInterval => (0.2, 2.),
+            TuningValue::MarchMinimumInterval => (0.02, 0.5),
+            TuningValue::BulletSpeed | TuningValue::InvaderBulletSpeed => (100., 1000.),
+            TuningValue::WaveDrop => (0., MAX_WAVE_DROP as f32),
+        }
+    }
+
+    fn clamp(self, amount: f32) -> f32 {
+        let (min, max) = self.range();
+        let amount = amount.clamp(min, max);
+        match self {
+            TuningValue::StartingLives | TuningValue::BonusLifeScore | TuningValue::WaveDrop => amount.round(),
+            _ => amount,
+        }
+    }
+
+    pub fn format(self, amount: f32) -> String {
+        match self {
+            TuningValue::BonusLifeScore if amount == 0. => "OFF".to_string(),
+            TuningValue::InvaderShootInterval | TuningValue::MarchInitialInterval => format!("{amount:.1}S"),
+            TuningValue::MarchMinimumInterval => format!("{amount:.2}S"),
+            _ => format!("{amount:.0}"),
+        }
+    }
+}
+
+/// A preset, with any of its values overridden
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Difficulty {
+    pub preset: Preset,
+    pub overrides: BTreeMap<TuningValue, f32>,
+}
+
+impl From<Preset> for Difficulty {
+    fn from(preset: Preset) -> Self {
+        Difficulty { preset, overrides: BTreeMap::new() }
+    }
+}
+
+impl Difficulty {
+    pub fn tuning(&self) -> Tuning {
+        let mut tuning = self.preset.tuning();
+        for (value, amount) in &self.overrides {
+            tuning.set(*value, *amount);
+        }
+        tuning
+    }
+
+    /// The preset's name, marked if any of its values are overridden
+    pub fn name(&self) -> String {
+        if self.overrides.is_empty() {
+            self.preset.name().to_string()
+        } else {
+            format!("{} (CUSTOM)", self.preset.name())
+        }
+    }
+
+    /// Moves `value` up or down by `steps` of its step. Moving it back to the preset's value
+    /// drops the override.
+    pub fn adjust(&mut self, value: TuningValue, steps: i32) {
+        let mut tuning = self.tuning();
+        let stepped = ((tuning.get(value) + steps as f32 * value.step()) / value.step()).round() * value.step();
+        tuning.set(value, stepped);
+
+        let amount = tuning.get(value);
+        if amount == self.preset.tuning().get(value) {
+            self.overrides.remove(&value);
+        } else {
+            self.overrides.insert(value, amount);
+        }
+    }
+
+    /// Overrides `value` with `amount`, brought into range
+    pub fn set(&mut self, value: TuningValue, amount: f32) {
+        self.overrides.insert(value, value.clamp(amount));
+    }
+
+    /// The difficulty with any overrides out of range brought into range
+    pub fn clamped(self) -> Self {
+        Difficulty {
+            overrides: self.overrides.into_iter().map(|(value, amount)| (value, value.clamp(amount))).collect(),
+            ..self
+        }
+    }
+}
+
+/// The difficulty the current game started at, and what it works out to
+#[derive(Resource, Debug, Clone)]
+pub struct ActiveDifficulty {
+    pub difficulty: Difficulty,
+    pub tuning: Tuning,
+}
+
+impl ActiveDifficulty {
+    pub fn new(difficulty: Difficulty) -> Self {
+        ActiveDifficulty { tuning: difficulty.tuning(), difficulty }
+    }
+}
@@ -0,0 +1,209 @@
+//! Fitting the playfield to the window.
+//!
+//! Each game draws into a fixed area of world space, its playfield, through a camera marked
+//! `PlayfieldCamera`. The window can be any size: the camera's viewport is the largest centred
+//! rectangle the playfield fits in, with black bars filling the rest. By default the playfield is
+//! drawn at a whole number of screen pixels per world unit so that sprites stay crisp
+//! (`--scaling fit` fills as much of the window as it can instead), falling back to shrinking it
+//! when the window is too small for even one. The UI is scaled by the same amount, so the HUD
+//! keeps its place on the playfield.
+//!
+//! F11 switches between a window and fullscreen. Both are saved in the `Settings`, which
+//! `--fullscreen`, `--windowed` and `--scaling` override. Space
+//! Invaders is played on a square board, or the arcade's portrait screen with `--layout portrait`.
+
+use bevy::{
+    prelude::*,
+    render::camera::{ScalingMode, Viewport},
+    window::{PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{arg_value, launcher::AppState, ARCADE_RESOLUTION, INVADER_SCALE, RESOLUTION};
+
+const LETTERBOX_COLOUR: Color = Color::BLACK;
+// Drawn behind everything else in the playfield
+const BACKGROUND_Z: f32 = -100.0;
+
+/// The shape of a Space Invaders board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlayfieldLayout {
+    #[default]
+    Square,
+    /// The arcade's 224×256 screen, at the size the sprites are drawn at
+    Portrait,
+}
+
+impl PlayfieldLayout {
+    /// The layout named by `--layout`, or the square one if there isn't one
+    pub fn from_args(args: &[String]) -> Self {
+        match arg_value(args, "--layout") {
+            None | Some("square") => PlayfieldLayout::Square,
+            Some("portrait") => PlayfieldLayout::Portrait,
+            Some(_) => panic!("--layout expects square or portrait"),
+        }
+    }
+
+    /// The size of one board in world units
+    pub fn board_size(self) -> Vec2 {
+        match self {
+            PlayfieldLayout::Square => RESOLUTION,
+            PlayfieldLayout::Portrait => ARCADE_RESOLUTION * INVADER_SCALE,
+        }
+    }
+}
+
+/// How the playfield is scaled up to fill the window
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Scaling {
+    /// A whole number of screen pixels per world unit, so every pixel of a sprite is the same size
+    #[default]
+    Integer,
+    /// As large as fits, however many pixels that makes
+    Fit,
+}
+
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub scaling: Scaling,
+    pub fullscreen: bool,
+}
+
+impl DisplaySettings {
+    /// Overrides the settings with `--scaling integer` or `--scaling fit`, and `--fullscreen` or
+    /// `--windowed`
+    pub fn apply_args(&mut self, args: &[String]) {
+        match arg_value(args, "--scaling") {
+            None => {}
+            Some("integer") => self.scaling = Scaling::Integer,
+            Some("fit") => self.scaling = Scaling::Fit,
+            Some(_) => panic!("--scaling expects integer or fit"),
+        }
+        if args.iter().any(|arg| arg == "--fullscreen") {
+            self.fullscreen = true;
+        } else if args.iter().any(|arg| arg == "--windowed") {
+            self.fullscreen = false;
+        }
+    }
+}
+
+/// Keeps every `PlayfieldCamera` fitted to the window, and switches to and from fullscreen
+pub struct DisplayPlugin {
+    pub settings: DisplaySettings,
+}
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings)
+            .add_systems(Update, (toggle_fullscreen, apply_window_mode, fit_playfield_cameras).chain());
+    }
+}
+
+/// A camera showing exactly `area` of world space, letterboxed to fit the window
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PlayfieldCamera {
+    pub area: Rect,
+}
+
+/// The backdrop behind a playfield
+#[derive(Component)]
+pub struct PlayfieldBackground;
+
+/// Spawns a camera on `area`, and a `background` coloured backdrop to tell the playfield from
+/// the letterbox around it, for as long as `AppState` is `state`. Without a `DisplayPlugin` the
+/// camera still zooms to fit the area in the window, just not to whole pixels.
+pub fn spawn_playfield_camera(commands: &mut Commands, area: Rect, background: Color, state: AppState) {
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera { clear_color: ClearColorConfig::Custom(LETTERBOX_COLOUR), ..default() },
+            projection: OrthographicProjection {
+                scaling_mode: ScalingMode::AutoMin { min_width: area.width(), min_height: area.height() },
+                ..Camera2dBundle::default().projection
+            },
+            transform: Transform::from_translation(area.center().extend(0.0)),
+            ..default()
+        },
+        PlayfieldCamera { area },
+        StateScoped(state),
+    ));
+
+    commands.spawn((
+        PlayfieldBackground,
+        SpriteBundle {
+            sprite: Sprite { color: background, custom_size: Some(area.size()), ..default() },
+            transform: Transform::from_translation(area.center().extend(BACKGROUND_Z)),
+            ..default()
+        },
+        StateScoped(state),
+    ));
+}
+
+/// Where a cursor at `cursor` in the window is in the world, as seen by `camera`.
+/// `Camera::viewport_to_world_2d` wants positions relative to the camera's viewport instead.
+pub fn cursor_to_world(cursor: Vec2, camera: &Camera, camera_transform: &GlobalTransform) -> Option<Vec2> {
+    let viewport_origin = camera.logical_viewport_rect()?.min;
+    camera.viewport_to_world_2d(camera_transform, cursor - viewport_origin)
+}
+
+fn toggle_fullscreen(keyboard_input: Res<ButtonInput<KeyCode>>, mut settings: ResMut<DisplaySettings>) {
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        settings.fullscreen = !settings.fullscreen;
+    }
+}
+
+fn apply_window_mode(settings: Res<DisplaySettings>, mut window_query: Query<&mut Window, With<PrimaryWindow>>) {
+    let Ok(mut window) = window_query.get_single_mut() else {
+        return;
+    };
+    let mode = if settings.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed };
+    if window.mode != mode {
+        window.mode = mode;
+    }
+}
+
+/// Sizes each playfield camera's viewport to the window, and the UI along with it. The menu has
+/// no playfield, so its UI is left at its natural size.
+fn fit_playfield_cameras(
+    settings: Res<DisplaySettings>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<(&mut Camera, &PlayfieldCamera)>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let window_size = UVec2::new(window.physical_width(), window.physical_height());
+    // A minimised window has no size to fit anything to
+    if window_size.cmpeq(UVec2::ZERO).any() {
+        return;
+    }
+
+    let mut zoom = 1.0;
+    for (mut camera, playfield) in camera_query.iter_mut() {
+        let area_size = playfield.area.size();
+        let largest_fit = (window_size.as_vec2() / area_size).min_element();
+        let scale = match settings.scaling {
+            Scaling::Integer if largest_fit >= 1.0 => largest_fit.floor(),
+            _ => largest_fit,
+        };
+
+        let viewport_size = (area_size * scale).round().as_uvec2().clamp(UVec2::ONE, window_size);
+        let viewport_position = (window_size - viewport_size) / 2;
+        let fitted = camera.viewport.as_ref().is_some_and(|viewport| {
+            viewport.physical_position == viewport_position && viewport.physical_size == viewport_size
+        });
+        if !fitted {
+            camera.viewport =
+                Some(Viewport { physical_position: viewport_position, physical_size: viewport_size, ..default() });
+        }
+
+        // UI sizes are in logical pixels, which the window's scale factor already multiplies
+        zoom = scale / window.scale_factor();
+    }
+
+    if ui_scale.0 != zoom {
+        ui_scale.0 = zoom;
+    }
+}
@@ -0,0 +1,82 @@
+//! Explosions, shared by both games.
+//!
+//! Gameplay sends an `ExplosionEvent` when something blows up, and a copy of it is left behind
+//! for a moment after it's gone. It flashes on and off, or just fades out when the
+//! `Accessibility` options ask for less flashing. Explosions are only for show, so they're
+//! spawned outside the fixed timestep and left out of snapshots.
+
+use bevy::prelude::*;
+
+use crate::{accessibility::Accessibility, launcher::AppState};
+
+const EXPLOSION_DURATION: f32 = 0.3;
+const FLASH_INTERVAL: f32 = 0.05;
+// In front of whatever blew up
+const EXPLOSION_Z_OFFSET: f32 = 5.0;
+
+/// Turns `ExplosionEvent`s into explosions. Both games add it, so it only needs adding once.
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Accessibility>()
+            .add_event::<ExplosionEvent>()
+            .add_systems(Update, (spawn_explosions, animate_explosions).chain());
+    }
+}
+
+/// Something blew up, looking like `sprite` drawn with `texture` at `transform`
+#[derive(Event, Clone)]
+pub struct ExplosionEvent {
+    pub sprite: Sprite,
+    pub texture: Handle<Image>,
+    pub transform: Transform,
+}
+
+#[derive(Component)]
+struct Explosion {
+    timer: Timer,
+    colour: Color,
+}
+
+/// Leaves an explosion behind for everything that blew up, for as long as the current game lasts
+fn spawn_explosions(
+    mut commands: Commands,
+    mut explosion_events: EventReader<ExplosionEvent>,
+    state: Res<State<AppState>>,
+) {
+    for event in explosion_events.read() {
+        let mut transform = event.transform;
+        transform.translation.z += EXPLOSION_Z_OFFSET;
+
+        commands.spawn((
+            SpriteBundle { sprite: event.sprite.clone(), texture: event.texture.clone(), transform, ..default() },
+            Explosion { timer: Timer::from_seconds(EXPLOSION_DURATION, TimerMode::Once), colour: event.sprite.color },
+            StateScoped(*state.get()),
+        ));
+    }
+}
+
+fn animate_explosions(
+    mut commands: Commands,
+    mut explosion_query: Query<(Entity, &mut Explosion, &mut Sprite, &mut Visibility)>,
+    accessibility: Res<Accessibility>,
+    time: Res<Time>,
+) {
+    for (entity, mut explosion, mut sprite, mut visibility) in explosion_query.iter_mut() {
+        explosion.timer.tick(time.delta());
+        if explosion.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        if accessibility.reduce_flashing {
+            *visibility = Visibility::Inherited;
+            sprite.color = explosion.colour.with_alpha(explosion.timer.fraction_remaining());
+        } else {
+            let lit = ((explosion.timer.elapsed_secs() / FLASH_INTERVAL) as u32).is_multiple_of(2);
+            *visibility = if lit { Visibility::Inherited } else { Visibility::Hidden };
+            sprite.color = explosion.colour;
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! Reinforcement-learning environment.
+//!
+//! `SpaceInvadersEnv` runs a one-player game headless, without a window or renderer, and advances
+//! it exactly one fixed tick per `step`. The reward for a step is the score gained during it, minus
+//! `EnvConfig::life_loss_penalty` for every life lost.
+//!
+//! `BreakoutEnv` does the same for Breakout, with the same actions: left and right move the paddle
+//! and fire serves the ball, lets go of caught balls and fires lasers. Its playfield is the one in
+//! `BreakoutEnvConfig`, and each level starts with the level number showing for a couple of
+//! seconds, during which nothing moves.
+//!
+//! ```no_run
+//! use bevy_experiment::env::{Action, EnvConfig, SpaceInvadersEnv};
+//!
+//! let mut env = SpaceInvadersEnv::new(EnvConfig::default());
+//! let _observation = env.reset(42);
+//! loop {
+//!     let (_observation, _reward, done, _info) = env.step(Action::Fire);
+//!     if done {
+//!         break;
+//!     }
+//! }
+//! ```
+
+use bevy::prelude::*;
+
+use crate::{
+    breakout::{self, Ball, BreakoutConfig, Brick, BrickKind, Capsule, Laser, Paddle, PaddleInput},
+    headless::{headless_app, headless_breakout_app, start_headless},
+    input::{PlayerInput, PlayerInputs},
+    players::{GameMode, Players},
+    Bullet, Difficulty, Invader, InvaderBullet, InvaderType, Turret, RESOLUTION,
+};
+
+/// The Space Invaders playfield's width and height in pixels. Observed positions are relative to
+/// its centre, with y up.
+pub const PLAYFIELD_SIZE: Vec2 = RESOLUTION;
+
+/// The discrete actions available each tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Noop,
+    Left,
+    Right,
+    Fire,
+    LeftFire,
+    RightFire,
+}
+
+impl Action {
+    pub const COUNT: usize = 6;
+
+    /// Maps `0..Action::COUNT` onto the actions, for agents that output an index
+    pub fn from_index(index: usize) -> Option<Self> {
+        [Action::Noop, Action::Left, Action::Right, Action::Fire, Action::LeftFire, Action::RightFire]
+            .get(index)
+            .copied()
+    }
+
+    fn to_input(self) -> PlayerInput {
+        PlayerInput {
+            left: matches!(self, Action::Left | Action::LeftFire),
+            right: matches!(self, Action::Right | Action::RightFire),
+            fire: matches!(self, Action::Fire | Action::LeftFire | Action::RightFire),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObservationKind {
+    /// Every entity's kind, position and size
+    Entities,
+    /// The playfield downsampled to a grid, each cell holding the kind of entity covering it
+    Grid { columns: usize, rows: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvConfig {
+    pub observation: ObservationKind,
+    /// Subtracted from the reward whenever a life is lost
+    pub life_loss_penalty: f32,
+    /// Ends the episode after this many ticks, if set
+    pub max_ticks: Option<u32>,
+    pub difficulty: Difficulty,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            observation: ObservationKind::Entities,
+            life_loss_penalty: 100.,
+            max_ticks: None,
+            difficulty: Difficulty::default(),
+        }
+    }
+}
+
+/// What an observed entity is. As a `u8`, this is also the value of grid cells it covers, with 0 meaning empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum EntityKind {
+    Turret = 1,
+    InvaderA = 2,
+    InvaderB = 3,
+    InvaderC = 4,
+    Bullet = 5,
+    InvaderBullet = 6,
+    Paddle = 7,
+    Ball = 8,
+    /// A brick that can be broken, however many hits it has left
+    Brick = 9,
+    IndestructibleBrick = 10,
+    Capsule = 11,
+    Laser = 12,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObservedEntity {
+    pub kind: EntityKind,
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Observation {
+    /// Sorted by kind, then position, so the order doesn't depend on the ECS
+    Entities(Vec<ObservedEntity>),
+    /// Row-major from the top-left of the playfield
+    Grid { columns: usize, rows: usize, cells: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepInfo {
+    pub score: u32,
+    pub lives: u32,
+    /// The wave, or in Breakout the level, counting from 1
+    pub wave: u32,
+    pub tick: u32,
+    /// The episode was cut short by `EnvConfig::max_ticks` rather than lost
+    pub truncated: bool,
+}
+
+pub struct SpaceInvadersEnv {
+    config: EnvConfig,
+    app: App,
+    tick: u32,
+    score: u32,
+    lives: u32,
+}
+
+impl SpaceInvadersEnv {
+    pub fn new(config: EnvConfig) -> Self {
+        SpaceInvadersEnv { app: new_app(&config.difficulty, 0), config, tick: 0, score: 0, lives: 0 }
+    }
+
+    /// Starts a new game whose random choices all follow from `seed`
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.app = new_app(&self.config.difficulty, seed);
+        self.tick = 0;
+
+        let player = &self.app.world().resource::<Players>().states[0];
+        self.score = player.score;
+        self.lives = player.lives;
+
+        self.observe()
+    }
+
+    /// Plays `action` for one fixed tick and returns `(observation, reward, done, info)`
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, StepInfo) {
+        self.app.world_mut().resource_mut::<PlayerInputs>().0[0] = action.to_input();
+        self.app.update();
+        self.tick += 1;
+
+        let players = self.app.world().resource::<Players>();
+        let player = &players.states[0];
+        let lives_lost = self.lives.saturating_sub(player.lives);
+        let reward = player.score.saturating_sub(self.score) as f32 - lives_lost as f32 * self.config.life_loss_penalty;
+
+        let game_over = players.is_game_over();
+        let truncated = !game_over && self.config.max_ticks.is_some_and(|max_ticks| self.tick >= max_ticks);
+        let info = StepInfo { score: player.score, lives: player.lives, wave: player.wave, tick: self.tick, truncated };
+
+        self.score = player.score;
+        self.lives = player.lives;
+
+        (self.observe(), reward, game_over || truncated, info)
+    }
+
+    pub fn observe(&mut self) -> Observation {
+        let entities = self.entities();
+        observation(self.config.observation, entities, Rect::from_center_size(Vec2::ZERO, PLAYFIELD_SIZE))
+    }
+
+    fn entities(&mut self) -> Vec<ObservedEntity> {
+        let world = self.app.world_mut();
+        let mut query =
+            world.query::<(&Transform, &Sprite, Has<Turret>, Option<&Invader>, Has<Bullet>, Has<InvaderBullet>)>();
+
+        let mut entities: Vec<ObservedEntity> = query
+            .iter(world)
+            .filter_map(|(transform, sprite, is_turret, invader, is_bullet, is_invader_bullet)| {
+                let kind = match invader {
+                    Some(invader) => match invader.invader_type {
+                        InvaderType::A => EntityKind::InvaderA,
+                        InvaderType::B => EntityKind::InvaderB,
+                        InvaderType::C => EntityKind::InvaderC,
+                    },
+                    None if is_turret => EntityKind::Turret,
+                    None if is_bullet => EntityKind::Bullet,
+                    None if is_invader_bullet => EntityKind::InvaderBullet,
+                    None => return None,
+                };
+
+                Some(ObservedEntity {
+                    kind,
+                    position: transform.translation.truncate(),
+                    size: sprite.custom_size.unwrap_or(Vec2::ONE),
+                })
+            })
+            .collect();
+
+        sort_entities(&mut entities);
+        entities
+    }
+}
+
+fn new_app(difficulty: &Difficulty, seed: u64) -> App {
+    let mut app = headless_app(GameMode::OnePlayer, difficulty.clone(), seed);
+    start_headless(&mut app);
+    app
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakoutEnvConfig {
+    pub observation: ObservationKind,
+    /// Subtracted from the reward whenever a life is lost
+    pub life_loss_penalty: f32,
+    /// Ends the episode after this many ticks, if set
+    pub max_ticks: Option<u32>,
+    /// Where the game is played, in world space, measured between the middles of the walls.
+    /// Observed positions are in world space too, and the grid covers this.
+    pub playfield: Rect,
+}
+
+impl Default for BreakoutEnvConfig {
+    fn default() -> Self {
+        BreakoutEnvConfig {
+            observation: ObservationKind::Entities,
+            life_loss_penalty: 10.,
+            max_ticks: None,
+            playfield: BreakoutConfig::default().playfield,
+        }
+    }
+}
+
+pub struct BreakoutEnv {
+    config: BreakoutEnvConfig,
+    app: App,
+    tick: u32,
+    score: u32,
+    lives: u32,
+}
+
+impl BreakoutEnv {
+    pub fn new(config: BreakoutEnvConfig) -> Self {
+        BreakoutEnv { app: new_breakout_app(config.playfield, 0), config, tick: 0, score: 0, lives: 0 }
+    }
+
+    /// Starts a new game whose random choices all follow from `seed`
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.app = new_breakout_app(self.config.playfield, seed);
+        self.tick = 0;
+
+        let status = self.status();
+        self.score = status.score as u32;
+        self.lives = status.lives;
+
+        self.observe()
+    }
+
+    /// Plays `action` for one fixed tick and returns `(observation, reward, done, info)`
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, StepInfo) {
+        self.app.world_mut().resource_mut::<PaddleInput>().set(action.to_input());
+        self.app.update();
+        self.tick += 1;
+
+        let status = self.status();
+        let score = status.score as u32;
+        let lives_lost = self.lives.saturating_sub(status.lives);
+        let reward = score.saturating_sub(self.score) as f32 - lives_lost as f32 * self.config.life_loss_penalty;
+
+        let game_over = status.lives == 0;
+        let truncated = !game_over && self.config.max_ticks.is_some_and(|max_ticks| self.tick >= max_ticks);
+        let info = StepInfo { score, lives: status.lives, wave: status.level as u32, tick: self.tick, truncated };
+
+        self.score = score;
+        self.lives = status.lives;
+
+        (self.observe(), reward, game_over || truncated, info)
+    }
+
+    pub fn observe(&mut self) -> Observation {
+        let entities = self.entities();
+        observation(self.config.observation, entities, self.config.playfield)
+    }
+
+    fn status(&self) -> breakout::Status {
+        breakout::Status::of(self.app.world()).expect("Breakout is always being played")
+    }
+
+    fn entities(&mut self) -> Vec<ObservedEntity> {
+        let world = self.app.world_mut();
+        let mut query = world.query::<(
+            &Transform,
+            Option<&Sprite>,
+            Has<Paddle>,
+            Has<Ball>,
+            Option<&Brick>,
+            Has<Capsule>,
+            Has<Laser>,
+        )>();
+
+        let mut entities: Vec<ObservedEntity> = query
+            .iter(world)
+            .filter_map(|(transform, sprite, is_paddle, is_ball, brick, is_capsule, is_laser)| {
+                let kind = match brick {
+                    Some(brick) if brick.kind == BrickKind::Indestructible => EntityKind::IndestructibleBrick,
+                    Some(_) => EntityKind::Brick,
+                    None if is_paddle => EntityKind::Paddle,
+                    None if is_ball => EntityKind::Ball,
+                    None if is_capsule => EntityKind::Capsule,
+                    None if is_laser => EntityKind::Laser,
+                    None => return None,
+                };
+
+                // Breakout sizes most things by scaling them, capsules by their sprite
+                let size = sprite.and_then(|sprite| sprite.custom_size).unwrap_or(Vec2::ONE);
+                Some(ObservedEntity {
+                    kind,
+                    position: transform.translation.truncate(),
+                    size: size * transform.scale.truncate(),
+                })
+            })
+            .collect();
+
+        sort_entities(&mut entities);
+        entities
+    }
+}
+
+fn new_breakout_app(playfield: Rect, seed: u64) -> App {
+    let mut app = headless_breakout_app(BreakoutConfig { playfield, seed: Some(seed), ..default() });
+    start_headless(&mut app);
+    app
+}
+
+/// Sorts by kind, then position, so the order doesn't depend on the ECS
+fn sort_entities(entities: &mut [ObservedEntity]) {
+    entities.sort_by(|a, b| {
+        a.kind.cmp(&b.kind).then(a.position.x.total_cmp(&b.position.x)).then(a.position.y.total_cmp(&b.position.y))
+    });
+}
+
+/// Observes `entities` in the way `kind` asks for, with the grid covering `playfield`
+fn observation(kind: ObservationKind, entities: Vec<ObservedEntity>, playfield: Rect) -> Observation {
+    let ObservationKind::Grid { columns, rows } = kind else {
+        return Observation::Entities(entities);
+    };
+
+    let cell_size = playfield.size() / Vec2::new(columns as f32, rows as f32);
+    let mut cells = vec![0; columns * rows];
+
+    for entity in &entities {
+        // Measure down from the top left so that row 0 is the top of the playfield
+        let top_left = Vec2::new(entity.position.x - playfield.min.x, playfield.max.y - entity.position.y);
+        let min = top_left - entity.size / 2.;
+        let max = min + entity.size;
+        let first = (min / cell_size).floor().max(Vec2::ZERO);
+        let last = (max / cell_size).ceil().min(Vec2::new(columns as f32, rows as f32));
+
+        for row in first.y as usize..last.y as usize {
+            for column in first.x as usize..last.x as usize {
+                cells[row * columns + column] = entity.kind as u8;
+            }
+        }
+    }
+
+    Observation::Grid { columns, rows, cells }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixed mix of actions, so that the turret or paddle moves around and fires
+    fn actions() -> impl Iterator<Item = Action> {
+        (0..300).map(|tick| Action::from_index(tick * 7 / 5 % Action::COUNT).unwrap())
+    }
+
+    fn play(env: &mut SpaceInvadersEnv, seed: u64) -> Vec<(Observation, f32, bool, StepInfo)> {
+        env.reset(seed);
+        actions().map(|action| env.step(action)).collect()
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_episode() {
+        let mut env = SpaceInvadersEnv::new(EnvConfig::default());
+        let first = play(&mut env, 3);
+        // Reset on the same env, and on a new one, so neither leftovers nor a fresh app matter
+        assert_eq!(play(&mut env, 3), first);
+        assert_eq!(play(&mut SpaceInvadersEnv::new(EnvConfig::default()), 3), first);
+        assert_ne!(play(&mut env, 4), first);
+    }
+
+    #[test]
+    fn reset_gives_the_same_first_observation() {
+        let config = EnvConfig { observation: ObservationKind::Grid { columns: 32, rows: 24 }, ..default() };
+        let mut env = SpaceInvadersEnv::new(config);
+        let first = env.reset(9);
+        env.step(Action::LeftFire);
+        assert_eq!(env.reset(9), first);
+        let Observation::Grid { cells, .. } = first else {
+            panic!("asked for a grid");
+        };
+        assert_eq!(cells.len(), 32 * 24);
+    }
+
+    #[test]
+    fn breakout_same_seed_and_actions_give_the_same_episode() {
+        let play = |env: &mut BreakoutEnv| {
+            env.reset(5);
+            actions().map(|action| env.step(action)).collect::<Vec<_>>()
+        };
+        let mut env = BreakoutEnv::new(BreakoutEnvConfig::default());
+        let first = play(&mut env);
+        // The level's bricks appear once its intro is over
+        let has_bricks = |(observation, ..): &(Observation, f32, bool, StepInfo)| matches!(observation, Observation::Entities(entities) if entities.iter().any(|entity| entity.kind == EntityKind::Brick));
+        assert!(first.iter().any(has_bricks));
+        assert_eq!(play(&mut env), first);
+    }
+}
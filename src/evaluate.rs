@@ -0,0 +1,464 @@
+//! Batch evaluation: plays many seeded one-player games headless with the autopilot, or a
+//! replay, and reports how they went.
+//!
+//! ```text
+//! cargo run --release -- --evaluate --games 500 --first-seed 0 --bot hard --output stats.csv
+//! ```
+//!
+//! `--games` (default 100) games are played on consecutive seeds from `--first-seed` (default 0),
+//! at the `--difficulty` preset (default normal), spread over `--threads` threads (default one
+//! per core). Each game is cut off after `--max-ticks` fixed ticks (default ten minutes of play).
+//! A summary is always printed; with `--output`, statistics are also written as JSON, or as CSV
+//! if the path ends in `.csv`.
+//!
+//! `--set <key>=<value>` overrides one of the preset's tuning values, as the tuning screen does,
+//! and can be given more than once, e.g. `--set bullet_speed=800 --set invader_shoot_interval=0.5`.
+//! The keys are `starting_lives`, `bonus_life_score`, `invader_shoot_interval`,
+//! `march_initial_interval`, `march_minimum_interval`, `bullet_speed`, `invader_bullet_speed` and
+//! `wave_drop`. Values out of range are brought into range.
+//!
+//! `--replay <file>` plays every game with the inputs in the file instead of the autopilot. It
+//! has a line per fixed tick from the start of the game, in the format external controllers send
+//! actions in, e.g. `{"left":true,"fire":true}`, with a blank line for nothing pressed. After the
+//! last line nothing is pressed.
+//!
+//! Every life lost is put down to a shot or an invasion, and each game's ending is the cause of
+//! its last death, or the tick limit.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    arg_value,
+    autopilot::{drive_autopilots, AutopilotSkill},
+    control::parse_action,
+    difficulty::TuningValue,
+    headless::{headless_app, start_headless},
+    input::{apply_local_inputs, InputSource, InputSources, LocalInputs, PlayerInput, PlayerInputs},
+    players::{DeathCause, GameMode, Players},
+    Difficulty, GameState, InputSet, Preset,
+};
+
+const DEFAULT_GAMES: u64 = 100;
+const DEFAULT_MAX_TICKS: u32 = 64 * 60 * 10;
+const PERCENTILES: [usize; 5] = [10, 25, 50, 75, 90];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Ending {
+    Shot,
+    Invasion,
+    /// Still alive when the tick limit was reached
+    TimeLimit,
+}
+
+/// Who plays the games
+enum Player {
+    Bot(AutopilotSkill),
+    /// The same inputs every game, one per tick
+    Replay {
+        path: String,
+        inputs: Vec<PlayerInput>,
+    },
+}
+
+impl Player {
+    fn from_args(args: &[String]) -> Self {
+        if let Some(path) = arg_value(args, "--replay") {
+            let contents = fs::read_to_string(path).unwrap_or_else(|error| panic!("Couldn't read {path}: {error}"));
+            let inputs = contents
+                .lines()
+                .enumerate()
+                .map(|(index, line)| match line.trim() {
+                    "" => PlayerInput::default(),
+                    line => parse_action(line)
+                        .unwrap_or_else(|error| panic!("Line {} of {path} isn't an action: {error}", index + 1)),
+                })
+                .collect();
+            return Player::Replay { path: path.to_string(), inputs };
+        }
+
+        Player::Bot(arg_value(args, "--bot").map_or(AutopilotSkill::Normal, |name| {
+            AutopilotSkill::from_name(name).unwrap_or_else(|| panic!("--bot expects easy, normal or hard"))
+        }))
+    }
+}
+
+#[derive(Serialize)]
+struct GameResult {
+    seed: u64,
+    score: u32,
+    wave: u32,
+    survival_ticks: u32,
+    deaths_shot: u32,
+    deaths_invasion: u32,
+    ending: Ending,
+}
+
+#[derive(Serialize)]
+struct Distribution {
+    mean: f64,
+    min: u32,
+    p10: u32,
+    p25: u32,
+    p50: u32,
+    p75: u32,
+    p90: u32,
+    max: u32,
+}
+
+impl Distribution {
+    fn of(mut values: Vec<u32>) -> Self {
+        values.sort_unstable();
+        let mean = values.iter().map(|value| *value as f64).sum::<f64>() / values.len().max(1) as f64;
+        // Nearest-rank percentiles
+        let percentile = |percent: usize| {
+            let rank = (percent * values.len()).div_ceil(100).max(1);
+            values.get(rank - 1).copied().unwrap_or(0)
+        };
+        let [p10, p25, p50, p75, p90] = PERCENTILES.map(percentile);
+
+        Distribution {
+            mean,
+            min: values.first().copied().unwrap_or(0),
+            p10,
+            p25,
+            p50,
+            p75,
+            p90,
+            max: values.last().copied().unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    /// The autopilot's skill, or `replay`
+    bot: String,
+    /// The replay file, when the games were replayed
+    replay: Option<String>,
+    difficulty: String,
+    /// Tuning values set with `--set`
+    overrides: BTreeMap<TuningValue, f32>,
+    games: usize,
+    first_seed: u64,
+    max_ticks: u32,
+    score: Distribution,
+    wave: Distribution,
+    survival_ticks: Distribution,
+    /// Lives lost to invader shots, over every game
+    deaths_shot: u32,
+    /// Lives lost to the invaders reaching the turret, over every game
+    deaths_invasion: u32,
+    ended_shot: usize,
+    ended_invasion: usize,
+    reached_time_limit: usize,
+    results: Vec<GameResult>,
+}
+
+impl Report {
+    fn new(evaluation: &Evaluation, mut results: Vec<GameResult>) -> Self {
+        results.sort_by_key(|result| result.seed);
+        let count = |ending: Ending| results.iter().filter(|result| result.ending == ending).count();
+
+        let (bot, replay) = match &evaluation.player {
+            Player::Bot(skill) => (format!("{skill:?}").to_lowercase(), None),
+            Player::Replay { path, .. } => ("replay".to_string(), Some(path.clone())),
+        };
+        Report {
+            bot,
+            replay,
+            difficulty: evaluation.difficulty.preset.name().to_lowercase(),
+            overrides: evaluation.difficulty.overrides.clone(),
+            games: results.len(),
+            first_seed: evaluation.first_seed,
+            max_ticks: evaluation.max_ticks,
+            score: Distribution::of(results.iter().map(|result| result.score).collect()),
+            wave: Distribution::of(results.iter().map(|result| result.wave).collect()),
+            survival_ticks: Distribution::of(results.iter().map(|result| result.survival_ticks).collect()),
+            deaths_shot: results.iter().map(|result| result.deaths_shot).sum(),
+            deaths_invasion: results.iter().map(|result| result.deaths_invasion).sum(),
+            ended_shot: count(Ending::Shot),
+            ended_invasion: count(Ending::Invasion),
+            reached_time_limit: count(Ending::TimeLimit),
+            results,
+        }
+    }
+
+    /// One `statistic,value` row per figure, with the per-game results left to the JSON output
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("statistic,value\n");
+        let mut row = |statistic: &str, value: String| {
+            let _ = writeln!(csv, "{statistic},{value}");
+        };
+
+        row("bot", self.bot.clone());
+        if let Some(replay) = &self.replay {
+            row("replay", replay.clone());
+        }
+        row("difficulty", self.difficulty.clone());
+        for (value, amount) in &self.overrides {
+            row(&format!("set_{}", value.key()), amount.to_string());
+        }
+        row("games", self.games.to_string());
+        row("first_seed", self.first_seed.to_string());
+        row("max_ticks", self.max_ticks.to_string());
+        for (name, distribution) in
+            [("score", &self.score), ("wave", &self.wave), ("survival_ticks", &self.survival_ticks)]
+        {
+            row(&format!("{name}_mean"), format!("{:.2}", distribution.mean));
+            row(&format!("{name}_min"), distribution.min.to_string());
+            for (percent, value) in PERCENTILES.iter().zip([
+                distribution.p10,
+                distribution.p25,
+                distribution.p50,
+                distribution.p75,
+                distribution.p90,
+            ]) {
+                row(&format!("{name}_p{percent}"), value.to_string());
+            }
+            row(&format!("{name}_max"), distribution.max.to_string());
+        }
+        row("deaths_shot", self.deaths_shot.to_string());
+        row("deaths_invasion", self.deaths_invasion.to_string());
+        row("ended_shot", self.ended_shot.to_string());
+        row("ended_invasion", self.ended_invasion.to_string());
+        row("reached_time_limit", self.reached_time_limit.to_string());
+        csv
+    }
+
+    fn summary(&self) -> String {
+        let player = match &self.replay {
+            Some(replay) => format!("replaying {replay}"),
+            None => format!("with the {} autopilot", self.bot),
+        };
+        let overrides: Vec<String> =
+            self.overrides.iter().map(|(value, amount)| format!("{}={amount}", value.key())).collect();
+        let difficulty = if overrides.is_empty() {
+            self.difficulty.clone()
+        } else {
+            format!("{} with {}", self.difficulty, overrides.join(", "))
+        };
+        format!(
+            "{} games {} on {} from seed {}\n\
+             score:          mean {:.1}, median {}, p90 {}, max {}\n\
+             wave:           mean {:.2}, median {}, max {}\n\
+             survival ticks: mean {:.0}, median {}, max {}\n\
+             lives lost:     {} shot, {} invaded\n\
+             endings:        {} shot, {} invaded, {} reached the {}-tick limit",
+            self.games,
+            player,
+            difficulty,
+            self.first_seed,
+            self.score.mean,
+            self.score.p50,
+            self.score.p90,
+            self.score.max,
+            self.wave.mean,
+            self.wave.p50,
+            self.wave.max,
+            self.survival_ticks.mean,
+            self.survival_ticks.p50,
+            self.survival_ticks.max,
+            self.deaths_shot,
+            self.deaths_invasion,
+            self.ended_shot,
+            self.ended_invasion,
+            self.reached_time_limit,
+            self.max_ticks,
+        )
+    }
+}
+
+/// The games to play and how
+struct Evaluation {
+    games: u64,
+    first_seed: u64,
+    max_ticks: u32,
+    player: Player,
+    difficulty: Difficulty,
+    threads: u64,
+}
+
+impl Evaluation {
+    fn from_args(args: &[String]) -> Self {
+        let parse = |name: &str, default: u64| {
+            arg_value(args, name)
+                .map_or(default, |value| value.parse().unwrap_or_else(|_| panic!("{name} expects a number")))
+        };
+
+        let games = parse("--games", DEFAULT_GAMES);
+        let cores = thread::available_parallelism().map_or(1, |cores| cores.get() as u64);
+        Evaluation {
+            games,
+            first_seed: parse("--first-seed", 0),
+            max_ticks: parse("--max-ticks", DEFAULT_MAX_TICKS as u64) as u32,
+            player: Player::from_args(args),
+            difficulty: difficulty_from_args(args),
+            threads: parse("--threads", cores).clamp(1, games.max(1)),
+        }
+    }
+
+    fn run(&self) -> Report {
+        let next_game = AtomicU64::new(0);
+        let results = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    let game = next_game.fetch_add(1, Ordering::Relaxed);
+                    if game >= self.games {
+                        return;
+                    }
+                    let result = play_game(self.first_seed + game, &self.player, &self.difficulty, self.max_ticks);
+                    results.lock().unwrap().push(result);
+                });
+            }
+        });
+
+        Report::new(self, results.into_inner().unwrap())
+    }
+}
+
+/// The `--difficulty` preset with each `--set key=value` applied on top
+fn difficulty_from_args(args: &[String]) -> Difficulty {
+    let mut difficulty = Difficulty::from(Preset::from_args(args).unwrap_or_default());
+    for (index, _) in args.iter().enumerate().filter(|(_, arg)| *arg == "--set") {
+        let setting = args.get(index + 1).unwrap_or_else(|| panic!("--set expects key=value"));
+        let (key, amount) = setting.split_once('=').unwrap_or_else(|| panic!("--set expects key=value, not {setting}"));
+        let value = TuningValue::from_key(key).unwrap_or_else(|| {
+            let keys: Vec<_> = TuningValue::ALL.iter().map(|value| value.key()).collect();
+            panic!("--set doesn't know {key}, it expects one of {}", keys.join(", "))
+        });
+        let amount = amount.parse().unwrap_or_else(|_| panic!("--set {key} expects a number"));
+        difficulty.set(value, amount);
+    }
+    difficulty
+}
+
+/// Runs the evaluation described by the command line
+pub fn run(args: &[String]) {
+    let report = Evaluation::from_args(args).run();
+    println!("{}", report.summary());
+
+    if let Some(path) = arg_value(args, "--output") {
+        let contents = if path.ends_with(".csv") {
+            report.to_csv()
+        } else {
+            serde_json::to_string_pretty(&report).expect("Reports are always serialisable")
+        };
+        fs::write(path, contents).unwrap_or_else(|error| panic!("Couldn't write {path}: {error}"));
+    }
+}
+
+fn play_game(seed: u64, player: &Player, difficulty: &Difficulty, max_ticks: u32) -> GameResult {
+    let mut app = headless_app(GameMode::OnePlayer, difficulty.clone(), seed);
+    if let Player::Bot(skill) = player {
+        app.insert_resource(InputSources(vec![InputSource::Autopilot(*skill)]))
+            .insert_resource(LocalInputs::new(1))
+            .add_systems(
+                FixedPreUpdate,
+                (
+                    drive_autopilots.run_if(in_state(GameState::Playing)).in_set(InputSet),
+                    apply_local_inputs.after(InputSet),
+                ),
+            );
+    }
+    start_headless(&mut app);
+
+    let mut ticks = 0;
+    let mut lives = app.world().resource::<Players>().states[0].lives;
+    let (mut deaths_shot, mut deaths_invasion) = (0, 0);
+    while ticks < max_ticks && !app.world().resource::<Players>().is_game_over() {
+        if let Player::Replay { inputs, .. } = player {
+            app.world_mut().resource_mut::<PlayerInputs>().0[0] =
+                inputs.get(ticks as usize).copied().unwrap_or_default();
+        }
+        app.update();
+        ticks += 1;
+
+        // One cause for each time a life is lost, even when an invasion takes every life left
+        let state = &app.world().resource::<Players>().states[0];
+        if state.lives < lives {
+            match state.death_cause {
+                Some(DeathCause::Invasion) => deaths_invasion += 1,
+                Some(DeathCause::Shot) | None => deaths_shot += 1,
+            }
+        }
+        lives = state.lives;
+    }
+
+    let player = &app.world().resource::<Players>().states[0];
+    let ending = match player.death_cause {
+        _ if player.lives > 0 => Ending::TimeLimit,
+        Some(DeathCause::Invasion) => Ending::Invasion,
+        Some(DeathCause::Shot) | None => Ending::Shot,
+    };
+
+    GameResult {
+        seed,
+        score: player.score,
+        wave: player.wave,
+        survival_ticks: ticks,
+        deaths_shot,
+        deaths_invasion,
+        ending,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn same_seeds_give_the_same_report() {
+        let evaluation = |threads| Evaluation {
+            games: 3,
+            first_seed: 20,
+            max_ticks: 1500,
+            player: Player::Bot(AutopilotSkill::Hard),
+            difficulty: Difficulty::default(),
+            threads,
+        };
+        let first = evaluation(1).run();
+        // Spreading the games over threads finishes them in any order
+        let second = evaluation(3).run();
+        assert_eq!(second.summary(), first.summary());
+        assert_eq!(second.to_csv(), first.to_csv());
+        assert_eq!(serde_json::to_string(&second.results).unwrap(), serde_json::to_string(&first.results).unwrap());
+    }
+
+    #[test]
+    fn set_overrides_the_presets_tuning() {
+        let difficulty = difficulty_from_args(&args("--difficulty hard --set bullet_speed=800 --set wave_drop=99"));
+        assert_eq!(difficulty.preset, Preset::Hard);
+        let tuning = difficulty.tuning();
+        assert_eq!(tuning.bullet_speed, 800.);
+        // Brought into range
+        assert_eq!(tuning.wave_drop, crate::difficulty::MAX_WAVE_DROP);
+        assert_eq!(tuning.invader_shoot_interval, Preset::Hard.tuning().invader_shoot_interval);
+    }
+
+    #[test]
+    fn tuning_keys_match_the_settings_file() {
+        for value in TuningValue::ALL {
+            assert_eq!(serde_json::to_value(value).unwrap(), value.key());
+            assert_eq!(TuningValue::from_key(value.key()), Some(value));
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Events the games send for the app hosting them, e.g. to show scores or move on after a game.
+//! Players are numbered from 0.
+
+use bevy::prelude::*;
+
+use crate::difficulty::Difficulty;
+
+/// A player's score changed, including back to 0 when a game starts over
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ScoreChanged {
+    pub player: usize,
+    pub score: u32,
+}
+
+/// A player lost a life
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerDied {
+    pub player: usize,
+    /// Lives the player has left
+    pub lives: u32,
+}
+
+/// A game ended
+#[derive(Event, Debug, Clone)]
+pub struct GameOver {
+    /// Each player's final score
+    pub scores: Vec<u32>,
+    /// What the scores were played at, for Space Invaders. Breakout has no difficulty settings.
+    pub difficulty: Option<Difficulty>,
+}
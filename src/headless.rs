@@ -0,0 +1,181 @@
+//! Running the game without a window or renderer, for training, evaluation and testing.
+//!
+//! Headless games never touch the disk for sprites or sounds: every asset path reads as empty and
+//! every image or sound "loads" as a blank placeholder. Besides being faster, this sidesteps loads
+//! failing after the last handle to them was dropped, which Bevy doesn't cope with. Breakout's
+//! level files are the exception, being part of the game rather than its looks.
+
+use std::path::Path;
+
+use bevy::{
+    asset::{
+        io::{
+            file::FileAssetReader, AssetReader, AssetReaderError, AssetSource, AssetSourceId, PathStream, Reader,
+            VecReader,
+        },
+        AssetLoader, LoadContext,
+    },
+    ecs::schedule::ExecutorKind,
+    prelude::*,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
+
+use crate::{
+    add_gameplay,
+    breakout::{BreakoutConfig, BreakoutPlugin, PaddleSource},
+    difficulty::Difficulty,
+    display::PlayfieldLayout,
+    launcher::AppState,
+    players::{GameMode, Players},
+    standard_playfield, GameRng, Playfield,
+};
+
+/// A game without window, renderer, HUD or input sources, fed through `PlayerInputs`.
+/// Once started, every update runs exactly one fixed tick.
+pub fn headless_app(mode: GameMode, difficulty: Difficulty, seed: u64) -> App {
+    let mut app = App::new();
+    app.register_asset_source(
+        AssetSourceId::Default,
+        AssetSource::build().with_reader(|| Box::new(PlaceholderAssetReader)),
+    )
+    .add_plugins((MinimalPlugins, AssetPlugin::default(), StatesPlugin))
+    .init_asset::<Image>()
+    .register_asset_loader(PlaceholderImageLoader)
+    .insert_state(AppState::SpaceInvaders);
+
+    let players = Players::new(mode, difficulty.tuning().starting_lives);
+    let playfield = Playfield(standard_playfield(players.board_count(), PlayfieldLayout::Square));
+    add_gameplay(&mut app, players, GameRng::from_seed(seed), playfield, difficulty);
+
+    fixed_ticks_only(&mut app);
+    app
+}
+
+/// A game of Breakout without window, renderer, HUD or input sources, with the paddle moved by
+/// writing `PaddleInput`. Once started, every update runs exactly one fixed tick.
+pub fn headless_breakout_app(config: BreakoutConfig) -> App {
+    let mut app = App::new();
+    app.register_asset_source(
+        AssetSourceId::Default,
+        AssetSource::build().with_reader(|| Box::new(LevelFileReader(FileAssetReader::new("assets")))),
+    )
+    .add_plugins((MinimalPlugins, AssetPlugin::default(), StatesPlugin))
+    .init_asset::<Image>()
+    .init_asset::<Mesh>()
+    .init_asset::<ColorMaterial>()
+    .init_asset::<AudioSource>()
+    .register_asset_loader(PlaceholderImageLoader)
+    .register_asset_loader(PlaceholderSoundLoader)
+    // Only Enter on the game over screen reads the keyboard, which never gets pressed
+    .init_resource::<ButtonInput<KeyCode>>()
+    .insert_state(AppState::Breakout)
+    .add_plugins(BreakoutPlugin { config: BreakoutConfig { input: PaddleSource::External, ..config } });
+
+    fixed_ticks_only(&mut app);
+    app
+}
+
+/// Makes every update advance time by exactly one fixed timestep
+fn fixed_ticks_only(app: &mut App) {
+    let timestep = app.world().resource::<Time<Fixed>>().timestep();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(timestep));
+}
+
+/// Runs startup on a headless app, once all its systems have been added
+pub fn start_headless(app: &mut App) {
+    // Headless games are often run many at a time, where spreading each one across threads only gets in the way
+    for (_, schedule) in app.world_mut().resource_mut::<Schedules>().iter_mut() {
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+    }
+
+    // The first update only runs startup, leaving the game ready for its first tick
+    app.update();
+}
+
+struct PlaceholderAssetReader;
+
+impl AssetReader for PlaceholderAssetReader {
+    async fn read<'a>(&'a self, _path: &'a Path) -> Result<Box<Reader<'a>>, AssetReaderError> {
+        Ok(Box::new(VecReader::new(Vec::new())))
+    }
+
+    async fn read_meta<'a>(&'a self, path: &'a Path) -> Result<Box<Reader<'a>>, AssetReaderError> {
+        Err(AssetReaderError::NotFound(path.to_path_buf()))
+    }
+
+    async fn read_directory<'a>(&'a self, path: &'a Path) -> Result<Box<PathStream>, AssetReaderError> {
+        Err(AssetReaderError::NotFound(path.to_path_buf()))
+    }
+
+    async fn is_directory<'a>(&'a self, _path: &'a Path) -> Result<bool, AssetReaderError> {
+        Ok(false)
+    }
+}
+
+/// Reads level files from the asset folder, and everything else as a placeholder
+struct LevelFileReader(FileAssetReader);
+
+impl AssetReader for LevelFileReader {
+    async fn read<'a>(&'a self, path: &'a Path) -> Result<Box<Reader<'a>>, AssetReaderError> {
+        if path.extension().is_some_and(|extension| extension == "level") {
+            self.0.read(path).await
+        } else {
+            Ok(Box::new(VecReader::new(Vec::new())))
+        }
+    }
+
+    async fn read_meta<'a>(&'a self, path: &'a Path) -> Result<Box<Reader<'a>>, AssetReaderError> {
+        Err(AssetReaderError::NotFound(path.to_path_buf()))
+    }
+
+    async fn read_directory<'a>(&'a self, path: &'a Path) -> Result<Box<PathStream>, AssetReaderError> {
+        Err(AssetReaderError::NotFound(path.to_path_buf()))
+    }
+
+    async fn is_directory<'a>(&'a self, _path: &'a Path) -> Result<bool, AssetReaderError> {
+        Ok(false)
+    }
+}
+
+struct PlaceholderImageLoader;
+
+impl AssetLoader for PlaceholderImageLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        _reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Image, Self::Error> {
+        Ok(Image::default())
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["png"]
+    }
+}
+
+struct PlaceholderSoundLoader;
+
+impl AssetLoader for PlaceholderSoundLoader {
+    type Asset = AudioSource;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load<'a>(
+        &'a self,
+        _reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<AudioSource, Self::Error> {
+        Ok(AudioSource { bytes: Vec::new().into() })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ogg"]
+    }
+}
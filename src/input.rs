@@ -0,0 +1,264 @@
+//! Player input, decoupled from the keyboard.
+//!
+//! Gameplay systems only ever read `PlayerInputs`, which holds what each player is doing on the
+//! current fixed tick. Locally each player's `InputSource`, the keyboard, the autopilot or an
+//! external program, fills
+//! it in; in netplay the session fills it in from the delayed local input and the (possibly
+//! predicted) remote input.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{accessibility::Accessibility, arg_value, autopilot::AutopilotSkill, control::ControlConfig};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlayerInput {
+    pub left: bool,
+    pub right: bool,
+    pub fire: bool,
+}
+
+impl PlayerInput {
+    pub fn to_bits(self) -> u8 {
+        self.left as u8 | (self.right as u8) << 1 | (self.fire as u8) << 2
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        PlayerInput { left: bits & 1 != 0, right: bits & 2 != 0, fire: bits & 4 != 0 }
+    }
+}
+
+/// Input for the current fixed tick, indexed by `PlayerId`
+#[derive(Resource)]
+pub struct PlayerInputs(pub Vec<PlayerInput>);
+
+impl PlayerInputs {
+    pub fn new(player_count: usize) -> Self {
+        PlayerInputs(vec![PlayerInput::default(); player_count])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSource {
+    Keyboard,
+    Autopilot(AutopilotSkill),
+    /// A program connected through `control`, a host app writing `LocalInputs` during `InputSet`,
+    /// or the opponent's machine in netplay
+    External,
+}
+
+/// Who controls each player, indexed by `PlayerId`
+#[derive(Resource, Clone)]
+pub struct InputSources(pub Vec<InputSource>);
+
+impl InputSources {
+    /// Every player uses the keyboard unless `--autopilot [easy|normal|hard]` hands player 1 to
+    /// the autopilot, `--autopilot2` does the same for player 2, or `--control` hands a player
+    /// to an external program
+    pub fn from_args(args: &[String], player_count: usize) -> Self {
+        let mut sources = vec![InputSource::Keyboard; player_count];
+
+        for (player, flag) in ["--autopilot", "--autopilot2"].into_iter().enumerate() {
+            if let Some(source) = autopilot_from_args(args, flag).filter(|_| player < player_count) {
+                sources[player] = source;
+            }
+        }
+
+        let controlled_player = ControlConfig::player_from_args(args);
+        if arg_value(args, "--control").is_some() && controlled_player < player_count {
+            sources[controlled_player] = InputSource::External;
+        }
+
+        InputSources(sources)
+    }
+}
+
+/// The autopilot `flag` asks for, at the skill following it or normal skill if none does
+pub fn autopilot_from_args(args: &[String], flag: &str) -> Option<InputSource> {
+    args.iter().any(|arg| arg == flag).then(|| {
+        InputSource::Autopilot(
+            arg_value(args, flag).and_then(AutopilotSkill::from_name).unwrap_or(AutopilotSkill::Normal),
+        )
+    })
+}
+
+/// The keys that move something left and right and fire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Controls {
+    #[serde(with = "key_name")]
+    pub left: KeyCode,
+    #[serde(with = "key_name")]
+    pub right: KeyCode,
+    #[serde(with = "key_name")]
+    pub fire: KeyCode,
+}
+
+impl Controls {
+    fn key(&self, action: Action) -> KeyCode {
+        match action {
+            Action::Left => self.left,
+            Action::Right => self.right,
+            Action::Fire => self.fire,
+        }
+    }
+
+    fn key_mut(&mut self, action: Action) -> &mut KeyCode {
+        match action {
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::Fire => &mut self.fire,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Left,
+    Right,
+    Fire,
+}
+
+impl Action {
+    pub const ALL: [Action; 3] = [Action::Left, Action::Right, Action::Fire];
+}
+
+/// One key in `KeyBindings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    /// A Space Invaders player's, by `PlayerId`
+    Invaders(usize, Action),
+    Breakout(Action),
+}
+
+/// The keys each game is played with
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    /// Indexed by `PlayerId`
+    pub invaders: [Controls; 2],
+    pub breakout: Controls,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            invaders: [
+                Controls { left: KeyCode::KeyA, right: KeyCode::KeyD, fire: KeyCode::Space },
+                Controls { left: KeyCode::ArrowLeft, right: KeyCode::ArrowRight, fire: KeyCode::ArrowUp },
+            ],
+            breakout: Controls { left: KeyCode::ArrowLeft, right: KeyCode::ArrowRight, fire: KeyCode::Space },
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, binding: Binding) -> KeyCode {
+        match binding {
+            Binding::Invaders(player, action) => self.invaders[player].key(action),
+            Binding::Breakout(action) => self.breakout.key(action),
+        }
+    }
+
+    /// Binds `key` to `binding`. A key does one thing per game, so if it was already bound to
+    /// something else in the same game, that takes `binding`'s old key in exchange.
+    pub fn bind(&mut self, binding: Binding, key: KeyCode) {
+        let old_key = self.key(binding);
+        let game: &mut [Controls] = match binding {
+            Binding::Invaders(..) => &mut self.invaders,
+            Binding::Breakout(_) => std::slice::from_mut(&mut self.breakout),
+        };
+        for controls in game.iter_mut() {
+            for action in Action::ALL {
+                let bound = controls.key_mut(action);
+                if *bound == key {
+                    *bound = old_key;
+                }
+            }
+        }
+        *self.key_mut(binding) = key;
+    }
+
+    fn key_mut(&mut self, binding: Binding) -> &mut KeyCode {
+        match binding {
+            Binding::Invaders(player, action) => self.invaders[player].key_mut(action),
+            Binding::Breakout(action) => self.breakout.key_mut(action),
+        }
+    }
+}
+
+/// Keys are saved by name, such as `KeyA` or `ArrowLeft`. Bevy only derives serde for `KeyCode`
+/// behind a feature, but reflection knows the names too.
+mod key_name {
+    use bevy::{
+        prelude::*,
+        reflect::{DynamicEnum, DynamicVariant, Enum, TypeInfo, Typed, VariantInfo},
+    };
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(key: &KeyCode, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(key.variant_name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyCode, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        // `from_reflect` panics on variants that don't exist, rather than returning `None`
+        let TypeInfo::Enum(info) = KeyCode::type_info() else {
+            unreachable!("KeyCode is an enum");
+        };
+        let is_key = matches!(info.variant(&name), Some(VariantInfo::Unit(_)));
+        is_key
+            .then(|| KeyCode::from_reflect(&DynamicEnum::new(name.as_str(), DynamicVariant::Unit)))
+            .flatten()
+            .ok_or_else(|| D::Error::custom(format!("{name} isn't a key")))
+    }
+}
+
+/// Input gathered on this machine from the keyboard and the autopilot, indexed by `PlayerId`.
+/// Fire presses are latched until a fixed tick picks them up, since a frame can run without a tick.
+#[derive(Resource)]
+pub struct LocalInputs(pub Vec<PlayerInput>);
+
+impl LocalInputs {
+    pub fn new(player_count: usize) -> Self {
+        LocalInputs(vec![PlayerInput::default(); player_count])
+    }
+
+    /// Returns the input for `player` and clears its latched fire press
+    pub fn take(&mut self, player: usize) -> PlayerInput {
+        let input = self.0[player];
+        self.0[player].fire = false;
+        input
+    }
+}
+
+/// With auto-fire on, holding fire counts as pressing it again whenever the turret can fire
+pub fn read_keyboard(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    sources: Res<InputSources>,
+    bindings: Res<KeyBindings>,
+    accessibility: Res<Accessibility>,
+    mut local_inputs: ResMut<LocalInputs>,
+) {
+    for ((input, controls), source) in local_inputs.0.iter_mut().zip(bindings.invaders.iter()).zip(sources.0.iter()) {
+        if *source != InputSource::Keyboard {
+            continue;
+        }
+
+        input.left = keyboard_input.pressed(controls.left);
+        input.right = keyboard_input.pressed(controls.right);
+        input.fire |= keyboard_input.just_pressed(controls.fire)
+            || (accessibility.auto_fire && keyboard_input.pressed(controls.fire));
+    }
+}
+
+/// Forgets whatever was pressed while the game was paused, so that a fire press latched then
+/// doesn't go off once it's running again
+pub fn clear_local_inputs(mut local_inputs: ResMut<LocalInputs>) {
+    local_inputs.0.fill(PlayerInput::default());
+}
+
+pub fn apply_local_inputs(mut local_inputs: ResMut<LocalInputs>, mut inputs: ResMut<PlayerInputs>) {
+    for player in 0..inputs.0.len() {
+        inputs.0[player] = local_inputs.take(player);
+    }
+}
@@ -0,0 +1,117 @@
+//! The launcher menu for picking a game.
+//!
+//! Each game runs while `AppState` is in its state, and everything it spawns is scoped to that
+//! state, so going back to the menu clears the game away entirely. `--game invaders` or
+//! `--game breakout` skips the menu and starts that game straight away, and `--game editor` opens
+//! the Breakout level editor.
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    arg_value,
+    pause::{highlight_buttons, spawn_menu_button},
+    settings,
+};
+
+const TITLE_FONT_SIZE: f32 = 48.0;
+const HINT_FONT_SIZE: f32 = 16.0;
+const MENU_TEXT_COLOUR: Color = Color::srgb(1.0, 1.0, 1.0);
+const BUTTON_GAP: Val = Val::Px(10.0);
+
+/// Which game, if any, is running
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AppState {
+    #[default]
+    Menu,
+    SpaceInvaders,
+    Breakout,
+    BreakoutEditor,
+}
+
+impl AppState {
+    /// The game named by `--game`, or the menu if there isn't one
+    pub fn from_args(args: &[String]) -> Self {
+        match arg_value(args, "--game") {
+            None => AppState::Menu,
+            Some("invaders") => AppState::SpaceInvaders,
+            Some("breakout") => AppState::Breakout,
+            Some("editor") => AppState::BreakoutEditor,
+            Some(_) => panic!("--game expects invaders, breakout or editor"),
+        }
+    }
+}
+
+pub struct LauncherPlugin {
+    pub first_state: AppState,
+}
+
+impl Plugin for LauncherPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_state(self.first_state)
+            .enable_state_scoped_entities::<AppState>()
+            .add_systems(OnEnter(AppState::Menu), spawn_menu)
+            .add_systems(Update, (handle_menu_buttons, highlight_buttons).run_if(in_state(AppState::Menu)));
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum LauncherButton {
+    Play(AppState),
+    Settings,
+    Quit,
+}
+
+fn spawn_menu(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), StateScoped(AppState::Menu)));
+
+    let text_style = |font_size| TextStyle { font_size, color: MENU_TEXT_COLOUR, ..default() };
+
+    commands
+        .spawn((
+            StateScoped(AppState::Menu),
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: BUTTON_GAP,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|menu| {
+            menu.spawn(TextBundle::from_section("ARCADE", text_style(TITLE_FONT_SIZE)));
+
+            spawn_menu_button(menu, "SPACE INVADERS", LauncherButton::Play(AppState::SpaceInvaders));
+            spawn_menu_button(menu, "BREAKOUT", LauncherButton::Play(AppState::Breakout));
+            spawn_menu_button(menu, "LEVEL EDITOR", LauncherButton::Play(AppState::BreakoutEditor));
+            spawn_menu_button(menu, "SETTINGS", LauncherButton::Settings);
+            spawn_menu_button(menu, "QUIT", LauncherButton::Quit);
+
+            menu.spawn(TextBundle::from_section("ESCAPE IN GAME TO RETURN HERE", text_style(HINT_FONT_SIZE)));
+        });
+}
+
+fn handle_menu_buttons(
+    mut commands: Commands,
+    button_query: Query<(&Interaction, &LauncherButton), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            LauncherButton::Play(game) => next_state.set(*game),
+            LauncherButton::Settings => settings::open_menu(&mut commands),
+            LauncherButton::Quit => {
+                app_exit_events.send(AppExit::Success);
+            }
+        }
+    }
+}
@@ -0,0 +1,1269 @@
+//! Space Invaders and Breakout as Bevy plugins.
+//!
+//! `run` is the standalone arcade with its launcher menu. To embed a game in another Bevy app,
+//! add `SpaceInvadersPlugin` or `breakout::BreakoutPlugin` with a config describing where to
+//! play, where the assets live and who's playing, then listen for `ScoreChanged`, `PlayerDied`
+//! and `GameOver`. Each game runs while `AppState` is in its state; without the launcher the
+//! first game added starts straight away.
+
+// Bevy systems routinely take many parameters and nested query filters
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+use bevy::{
+    ecs::system::SystemParam,
+    log::LogPlugin,
+    math::bounding::{Aabb2d, BoundingVolume},
+    prelude::*,
+    window::{ExitCondition, Window, WindowResolution},
+};
+use rand::prelude::*;
+use std::{path::PathBuf, time::Duration};
+
+mod accessibility;
+mod autopilot;
+pub mod breakout;
+mod control;
+mod damage;
+mod debug_overlay;
+mod difficulty;
+mod display;
+mod effects;
+pub mod env;
+mod evaluate;
+mod events;
+mod headless;
+mod input;
+mod launcher;
+mod netplay;
+mod pause;
+mod players;
+mod settings;
+mod snapshot;
+mod theme;
+
+use accessibility::AccessibilityPlugin;
+use damage::{CollisionLayer, CollisionMask, DamageEvent, DeathEvent, Health};
+use difficulty::{ActiveDifficulty, Tuning};
+use display::{DisplayPlugin, PlayfieldLayout};
+use effects::{EffectsPlugin, ExplosionEvent};
+use input::{InputSources, PlayerInputs};
+use launcher::LauncherPlugin;
+use settings::{Settings, SettingsPlugin};
+use theme::ActiveTheme;
+
+pub use autopilot::AutopilotSkill;
+pub use difficulty::{Difficulty, Preset};
+pub use events::{GameOver, PlayerDied, ScoreChanged};
+pub use input::{InputSource, LocalInputs, PlayerInput};
+pub use launcher::AppState;
+pub use players::GameMode;
+use players::{DeathCause, Players, TurretHitEvent};
+
+const RESOLUTION: Vec2 = Vec2::new(720., 720.);
+// Sprites other than the invaders', relative to the theme's folder
+const TURRET_SPRITE: &str = "sprites\\turret.png";
+const BULLET_SPRITE: &str = "sprites\\turret_bullet.png";
+const INVADER_BULLET_SPRITE: &str = "sprites\\invader_bullet.png";
+// The arcade cabinet's screen, in its own pixels
+const ARCADE_RESOLUTION: Vec2 = Vec2::new(224., 256.);
+const TURRET_BASE_SIZE: Vec2 = Vec2::new(26., 16.);
+const TURRET_SCALE: f32 = 2.;
+const INVADER_SCALE: f32 = 2.;
+const BULLET_SCALE: f32 = 2.;
+const INVADER_SCREEN_PERCENTAGE: f32 = 0.7;
+const TURRET_SIZE: Vec2 = Vec2::new(TURRET_BASE_SIZE.x * TURRET_SCALE, TURRET_BASE_SIZE.y * TURRET_SCALE);
+const BULLET_BASE_SIZE: Vec2 = Vec2::new(2., 8.);
+const BULLET_SIZE: Vec2 = Vec2::new(BULLET_BASE_SIZE.x * BULLET_SCALE, BULLET_BASE_SIZE.y * BULLET_SCALE);
+const BULLET_SPEED: f32 = 400.;
+const TURRET_SPEED: f32 = 500.0;
+const TURRET_PADDING: f32 = 10.;
+const SHOOT_COOLDOWN: f32 = 0.5;
+const INVADER_A_BASE_SIZE: Vec2 = Vec2::new(16., 16.);
+const INVADER_B_BASE_SIZE: Vec2 = Vec2::new(22., 16.);
+const INVADER_C_BASE_SIZE: Vec2 = Vec2::new(24., 16.);
+const INVADER_A_SIZE: Vec2 = Vec2::new(INVADER_A_BASE_SIZE.x * INVADER_SCALE, INVADER_A_BASE_SIZE.y * TURRET_SCALE);
+const INVADER_B_SIZE: Vec2 = Vec2::new(INVADER_B_BASE_SIZE.x * INVADER_SCALE, INVADER_B_BASE_SIZE.y * TURRET_SCALE);
+const INVADER_C_SIZE: Vec2 = Vec2::new(INVADER_C_BASE_SIZE.x * INVADER_SCALE, INVADER_C_BASE_SIZE.y * TURRET_SCALE);
+
+const GAP_BETWEEN_INVADERS: f32 = 10.;
+const INVADER_STEP_SIZE: f32 = 26.0;
+const INVADER_VERTICAL_STEP: f32 = 26.0;
+// The Normal difficulty's march and fire rate. Each `Preset` sets its own.
+const INVADER_MOVE_INTERVAL: f32 = 1.;
+const INVADER_MIN_MOVE_INTERVAL: f32 = 0.1;
+const INVADER_SHOOT_INTERVAL: f32 = 2.0;
+const INVADER_AIMED_SHOT_CHANCE: f64 = 0.5;
+const INVADER_BULLET_SIZE: Vec2 = Vec2::new(4.0, 10.0);
+// Player shots stop at invaders and at invader shots, which they don't destroy. Invader shots
+// only stop at the turrets.
+const PLAYER_SHOT_MASK: CollisionMask = CollisionMask::of(&[CollisionLayer::INVADER, CollisionLayer::INVADER_SHOT]);
+const INVADER_SHOT_MASK: CollisionMask = CollisionMask::of(&[CollisionLayer::TURRET]);
+const SHOT_DAMAGE: u32 = 1;
+// Space kept free above the formation for the scoreboard
+const HUD_HEIGHT: f32 = 50.;
+const STARTING_LIVES: u32 = 3;
+
+/// Runs the game in a window, or a batch evaluation with `--evaluate`, configured from the command line
+pub fn run() {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.iter().any(|arg| arg == "--evaluate") {
+        evaluate::run(&args);
+        return;
+    }
+
+    let mut config = SpaceInvadersConfig::new(GameMode::from_args(&args));
+    let netplay_config = netplay::NetplayConfig::from_args(&args);
+    config.inputs = match &netplay_config {
+        Some(netplay) => netplay.input_sources(&args),
+        None => InputSources::from_args(&args, config.inputs.len()).0,
+    };
+
+    let mut settings = Settings::load();
+    if let Some(theme) = arg_value(&args, "--theme") {
+        settings.theme = theme.to_string();
+    }
+    settings.display.apply_args(&args);
+    config.theme = settings.theme.clone();
+    config.playfield = standard_playfield(config.mode.board_count(), PlayfieldLayout::from_args(&args));
+
+    // Netplay peers have to play by the same rules, which their own settings can't promise, so
+    // they play at the preset on the command line
+    let difficulty = Preset::from_args(&args).map(Difficulty::from);
+    if netplay_config.is_some() {
+        config.difficulty = difficulty.unwrap_or_default();
+    } else {
+        if let Some(difficulty) = difficulty {
+            settings.difficulty = difficulty;
+        }
+        config.difficulty = settings.difficulty.clone();
+    }
+
+    // Netplay peers must share a seed so both simulations make the same random choices
+    config.seed = match arg_value(&args, "--seed").and_then(|seed| seed.parse().ok()) {
+        Some(seed) => Some(seed),
+        None if netplay_config.is_some() => Some(0),
+        None => None,
+    };
+
+    let mut app = App::new();
+    let mut plugins = DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: Some(Window {
+                resolution: WindowResolution::new(config.playfield.width(), config.playfield.height()),
+                ..default()
+            }),
+            exit_condition: ExitCondition::OnPrimaryClosed,
+            close_when_requested: false,
+        })
+        .set(ImagePlugin::default_nearest());
+
+    // An external controller on stdio needs stdout to itself
+    if control::ControlConfig::uses_stdio(&args) {
+        plugins = plugins.disable::<LogPlugin>();
+    }
+
+    // Netplay is for Space Invaders, and external controllers play Space Invaders unless
+    // `--game breakout` picks Breakout, so they skip the launcher
+    let control_config = control::ControlConfig::from_args(&args);
+    let first_state = match AppState::from_args(&args) {
+        _ if netplay_config.is_some() => AppState::SpaceInvaders,
+        AppState::Breakout if control_config.is_some() => AppState::Breakout,
+        _ if control_config.is_some() => AppState::SpaceInvaders,
+        state => state,
+    };
+    let breakout_config = breakout::BreakoutConfig {
+        input: if control_config.is_some() {
+            breakout::PaddleSource::External
+        } else {
+            breakout::PaddleSource::Keyboard
+        },
+        keep_best_scores: true,
+        ..default()
+    };
+
+    app.add_plugins((
+        plugins,
+        LauncherPlugin { first_state },
+        DisplayPlugin { settings: settings.display },
+        AccessibilityPlugin,
+        SettingsPlugin { settings },
+        pause::PausePlugin,
+        debug_overlay::DebugOverlayPlugin,
+        SpaceInvadersPlugin { config },
+        breakout::BreakoutPlugin { config: breakout_config },
+    ));
+
+    if let Some(config) = control_config {
+        control::add_control(&mut app, config);
+    }
+
+    if let Some(config) = netplay_config {
+        netplay::add_netplay(&mut app, config);
+    }
+
+    app.run();
+}
+
+/// How a game of Space Invaders is set up
+#[derive(Clone)]
+pub struct SpaceInvadersConfig {
+    pub mode: GameMode,
+    /// Where the game is played, in world space. Versus boards split it into equal columns.
+    pub playfield: Rect,
+    /// Folder the sprites are loaded from, relative to the app's asset folder
+    pub asset_root: PathBuf,
+    /// Who controls each player, one per player in `mode`
+    pub inputs: Vec<InputSource>,
+    /// Seeds all gameplay randomness, or `None` for a different game every time
+    pub seed: Option<u64>,
+    /// Name of the theme in `themes.json` under the asset root to draw the game in
+    pub theme: String,
+    /// The difficulty games start at, until the settings change it
+    pub difficulty: Difficulty,
+}
+
+impl SpaceInvadersConfig {
+    /// The standalone game's layout for `mode`, centred on the origin and played from the keyboard
+    pub fn new(mode: GameMode) -> Self {
+        SpaceInvadersConfig {
+            mode,
+            playfield: standard_playfield(mode.board_count(), PlayfieldLayout::Square),
+            asset_root: PathBuf::new(),
+            inputs: vec![InputSource::Keyboard; mode.player_count()],
+            seed: None,
+            theme: theme::DEFAULT_THEME.to_string(),
+            difficulty: Difficulty::default(),
+        }
+    }
+}
+
+impl Default for SpaceInvadersConfig {
+    fn default() -> Self {
+        SpaceInvadersConfig::new(GameMode::OnePlayer)
+    }
+}
+
+/// Space Invaders, played while `AppState` is `AppState::SpaceInvaders`
+#[derive(Default)]
+pub struct SpaceInvadersPlugin {
+    pub config: SpaceInvadersConfig,
+}
+
+impl Plugin for SpaceInvadersPlugin {
+    fn build(&self, app: &mut App) {
+        let config = &self.config;
+        let players = Players::new(config.mode, config.difficulty.tuning().starting_lives);
+        let player_count = players.states.len();
+        assert_eq!(config.inputs.len(), player_count, "Space Invaders needs one input source per player");
+
+        let rng = match config.seed {
+            Some(seed) => GameRng::from_seed(seed),
+            None => GameRng(StdRng::from_entropy()),
+        };
+
+        // Without a launcher to pick the game, play it straight away
+        if !app.world().contains_resource::<State<AppState>>() {
+            app.insert_state(AppState::SpaceInvaders).enable_state_scoped_entities::<AppState>();
+        }
+
+        add_gameplay(app, players, rng, Playfield(config.playfield), config.difficulty.clone());
+
+        if !app.is_plugin_added::<EffectsPlugin>() {
+            app.add_plugins(EffectsPlugin);
+        }
+        theme::add_themes(app, &config.theme);
+
+        app.configure_sets(FixedPreUpdate, InputSet.run_if(in_state(AppState::SpaceInvaders)))
+            .add_systems(OnEnter(AppState::SpaceInvaders), (spawn_camera, players::spawn_hud))
+            .add_systems(Update, input::read_keyboard.run_if(in_state(AppState::SpaceInvaders)))
+            .add_systems(OnExit(pause::PauseState::Paused), input::clear_local_inputs)
+            .add_systems(
+                FixedPreUpdate,
+                (
+                    (
+                        autopilot::drive_autopilots.run_if(in_state(GameState::Playing)),
+                        control::exchange_with_controller.run_if(resource_exists::<control::ExternalControl>),
+                    )
+                        .chain()
+                        .in_set(InputSet),
+                    input::apply_local_inputs
+                        .after(InputSet)
+                        .run_if(in_state(AppState::SpaceInvaders))
+                        .run_if(not(resource_exists::<netplay::NetplaySession>)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (players::update_hud, players::restart_game.run_if(not(resource_exists::<netplay::NetplaySession>)))
+                    .run_if(in_state(AppState::SpaceInvaders)),
+            )
+            .add_systems(Update, players::send_player_events.run_if(in_state(AppState::SpaceInvaders)))
+            .add_systems(OnEnter(GameState::GameOver), players::send_game_over)
+            .insert_resource(LocalInputs::new(player_count))
+            .insert_resource(InputSources(config.inputs.clone()))
+            .insert_resource(AssetRoot(config.asset_root.clone()))
+            .add_event::<pause::RestartEvent>()
+            .add_event::<ScoreChanged>()
+            .add_event::<PlayerDied>()
+            .add_event::<GameOver>();
+    }
+}
+
+/// The standalone game's playfield, boards shaped by `layout` side by side, centred on the origin
+fn standard_playfield(board_count: usize, layout: PlayfieldLayout) -> Rect {
+    let board_size = layout.board_size();
+    Rect::from_center_size(Vec2::ZERO, Vec2::new(board_size.x * board_count as f32, board_size.y))
+}
+
+/// Adds the game simulation itself, everything but the window, HUD and input sources.
+/// Gameplay reads its input from `PlayerInputs`.
+fn add_gameplay(app: &mut App, players: Players, rng: GameRng, playfield: Playfield, difficulty: Difficulty) {
+    let player_count = players.states.len();
+
+    app.add_sub_state::<GameState>()
+        .init_resource::<AssetRoot>()
+        .init_resource::<ActiveTheme>()
+        .add_systems(OnEnter(AppState::SpaceInvaders), setup)
+        .configure_sets(
+            FixedUpdate,
+            GameplaySet
+                .run_if(in_state(GameState::Playing))
+                .run_if(netplay::simulation_running)
+                .run_if(control::simulation_running),
+        )
+        // Everything that affects the game runs in one fixed order so that the simulation is
+        // deterministic given the same seed and inputs, which netplay rollback relies on
+        .add_systems(
+            FixedUpdate,
+            (
+                move_turret,
+                shoot_bullet,
+                invader_shoot,
+                check_for_collisions,
+                damage::apply_damage,
+                handle_deaths,
+                players::award_bonus_lives,
+                players::check_invasion,
+                players::handle_turret_hit,
+                players::check_game_over.run_if(not(resource_exists::<netplay::NetplaySession>)),
+                players::send_extra_invaders,
+                move_bullet,
+                move_invader_bullet,
+                move_invaders,
+                animate_invaders,
+                players::advance_wave,
+                despawn_marked,
+            )
+                .chain()
+                .in_set(GameplaySet),
+        )
+        .insert_resource(PlayerInputs::new(player_count))
+        .insert_resource(players)
+        .insert_resource(rng)
+        .insert_resource(playfield)
+        .insert_resource(ActiveDifficulty::new(difficulty.clone()))
+        .insert_resource(difficulty)
+        .add_event::<CollisionEvent>()
+        .add_event::<DamageEvent>()
+        .add_event::<DeathEvent>()
+        .add_event::<TurretHitEvent>()
+        .add_event::<InvaderKilledEvent>()
+        .add_event::<ExplosionEvent>();
+}
+
+/// Returns the value following `name` on the command line, e.g. `--seed 42`
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == name).and_then(|index| args.get(index + 1)).map(String::as_str)
+}
+
+/// Whether a game of Space Invaders is underway or over. Only exists while it's being played.
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(AppState = AppState::SpaceInvaders)]
+enum GameState {
+    #[default]
+    Playing,
+    GameOver,
+}
+
+/// The systems that advance Space Invaders by one tick, in `FixedUpdate`
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameplaySet;
+
+/// The systems that fill in `LocalInputs` before each tick, in `FixedPreUpdate`. A host driving
+/// a player with `InputSource::External` writes that player's input from a system in this set.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputSet;
+
+/// Where Space Invaders is played, in world space
+#[derive(Resource, Clone, Copy)]
+struct Playfield(Rect);
+
+impl Playfield {
+    /// The part of the playfield given to a board. Boards split it into equal columns.
+    fn board_area(&self, board_id: BoardId, board_count: usize) -> Rect {
+        let width = self.0.width() / board_count as f32;
+        let min_x = self.0.min.x + width * board_id.0 as f32;
+        Rect::new(min_x, self.0.min.y, min_x + width, self.0.max.y)
+    }
+
+    /// Height of the turrets' centres
+    fn turret_y(&self) -> f32 {
+        self.0.min.y + TURRET_SIZE.y / 2. + TURRET_PADDING
+    }
+}
+
+/// Folder the sprites are loaded from, relative to the app's asset folder
+#[derive(Resource, Default)]
+struct AssetRoot(PathBuf);
+
+/// Loads sprites from under the `AssetRoot`
+#[derive(SystemParam)]
+struct Sprites<'w> {
+    asset_server: Res<'w, AssetServer>,
+    root: Res<'w, AssetRoot>,
+    theme: Res<'w, ActiveTheme>,
+}
+
+impl Sprites<'_> {
+    fn load(&self, path: &str) -> Handle<Image> {
+        self.asset_server.load(self.root.0.join(&self.theme.0.sprites).join(path))
+    }
+}
+
+/// Source of all gameplay randomness, so that a game can be replayed from its seed
+#[derive(Resource, Clone)]
+struct GameRng(StdRng);
+
+impl GameRng {
+    fn from_seed(seed: u64) -> Self {
+        GameRng(StdRng::seed_from_u64(seed))
+    }
+}
+
+/// Per-turret cooldown between player shots
+#[derive(Component, Clone)]
+struct ShootCooldown(Timer);
+
+#[derive(Component)]
+struct InvaderBullet;
+
+/// One player's field of play. Versus mode has one per player, the other modes share one.
+/// The board entity carries the formation state (`InvaderDirection`, `InvaderMoveTimer`,
+/// `InvaderShootTimer` and `InvaderCount`) for the invaders on it.
+#[derive(Component, Clone, Copy)]
+struct Board {
+    id: BoardId,
+    /// The board's part of the playfield, in world space
+    area: Rect,
+}
+
+/// Which board an entity belongs to, as an index into `Boards`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BoardId(usize);
+
+/// Board entities, indexed by `BoardId`
+#[derive(Resource)]
+struct Boards(Vec<Entity>);
+
+#[derive(Component, Clone)]
+struct InvaderShootTimer(Timer);
+
+impl InvaderShootTimer {
+    fn new(tuning: &Tuning) -> Self {
+        InvaderShootTimer(Timer::from_seconds(tuning.invader_shoot_interval, TimerMode::Repeating))
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum InvaderDirection {
+    Left,
+    Right,
+}
+
+#[derive(Component, Clone)]
+struct InvaderMoveTimer {
+    timer: Timer,
+    initial_interval: f32,
+    minimum_interval: f32,
+}
+
+impl InvaderMoveTimer {
+    fn new(tuning: &Tuning) -> Self {
+        InvaderMoveTimer {
+            timer: Timer::from_seconds(tuning.march_initial_interval, TimerMode::Repeating),
+            initial_interval: tuning.march_initial_interval,
+            minimum_interval: tuning.march_minimum_interval,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Turret;
+
+/// Which player a turret or bullet belongs to, as an index into `Players::states`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+struct PlayerId(usize);
+
+#[derive(Component)]
+struct Bullet;
+
+/// Marks an entity to be despawned at the end of the tick by `despawn_marked`
+#[derive(Component)]
+struct Despawning;
+
+#[derive(Component)]
+struct Invader {
+    invader_type: InvaderType,
+    animation_frame: usize,
+}
+
+#[derive(Component, Clone, Copy)]
+struct InvaderCount {
+    total: usize,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum InvaderType {
+    A,
+    B,
+    C,
+}
+
+impl InvaderType {
+    /// Points awarded for shooting this invader, as on the arcade cabinet
+    fn points(&self) -> u32 {
+        match self {
+            InvaderType::A => 30,
+            InvaderType::B => 20,
+            InvaderType::C => 10,
+        }
+    }
+
+    /// Shots it takes to destroy
+    fn hit_points(&self) -> u32 {
+        1
+    }
+
+    fn size(&self) -> Vec2 {
+        match self {
+            InvaderType::A => INVADER_A_SIZE,
+            InvaderType::B => INVADER_B_SIZE,
+            InvaderType::C => INVADER_C_SIZE,
+        }
+    }
+}
+
+#[derive(Event, Default)]
+struct CollisionEvent;
+
+/// Sent when a player's bullet destroys an invader
+#[derive(Event)]
+struct InvaderKilledEvent {
+    player: PlayerId,
+}
+
+/// Frames the playfield, scaled to fit the window
+fn spawn_camera(mut commands: Commands, playfield: Res<Playfield>, theme: Res<ActiveTheme>) {
+    display::spawn_playfield_camera(&mut commands, playfield.0, theme.0.background.0, AppState::SpaceInvaders);
+}
+
+fn setup(
+    mut commands: Commands,
+    sprites: Sprites,
+    mut players: ResMut<Players>,
+    mut inputs: ResMut<PlayerInputs>,
+    playfield: Res<Playfield>,
+    difficulty: Res<Difficulty>,
+    mut active_difficulty: ResMut<ActiveDifficulty>,
+) {
+    // Every visit from the launcher starts a fresh game, at the difficulty the settings are at now
+    *active_difficulty = ActiveDifficulty::new(difficulty.clone());
+    let tuning = &active_difficulty.tuning;
+    *players = Players::new(players.mode, tuning.starting_lives);
+    *inputs = PlayerInputs::new(players.states.len());
+
+    let board_count = players.board_count();
+    let boards = (0..board_count)
+        .map(|index| {
+            let board = Board { id: BoardId(index), area: playfield.board_area(BoardId(index), board_count) };
+            let board_entity = commands.spawn((board, StateScoped(AppState::SpaceInvaders))).id();
+            spawn_wave(&mut commands, &sprites, board_entity, board, tuning, players.wave_on(board.id));
+            board_entity
+        })
+        .collect();
+    commands.insert_resource(Boards(boards));
+
+    players::spawn_turrets(&mut commands, &sprites, &players, &playfield);
+}
+
+/// Number of columns and rows in a freshly spawned invader formation on a board of `area`
+fn formation_size(area: Rect) -> (usize, usize) {
+    let n_columns = (((area.width() - 2. * TURRET_PADDING) / (INVADER_C_SIZE.x + GAP_BETWEEN_INVADERS))
+        * INVADER_SCREEN_PERCENTAGE)
+        .floor() as usize;
+    (n_columns, 5)
+}
+
+/// Spawns a full formation on `board` and resets the board's formation state. Later waves can
+/// start lower down, going by the difficulty.
+fn spawn_wave(
+    commands: &mut Commands,
+    sprites: &Sprites,
+    board_entity: Entity,
+    board: Board,
+    tuning: &Tuning,
+    wave: u32,
+) {
+    let (n_columns, n_rows) = formation_size(board.area);
+    let total_invaders = n_columns * n_rows;
+    let drop = tuning.wave_start_drop(wave) as f32 * INVADER_VERTICAL_STEP;
+
+    commands.entity(board_entity).insert((
+        InvaderCount { total: total_invaders },
+        InvaderDirection::Right,
+        InvaderMoveTimer::new(tuning),
+        InvaderShootTimer::new(tuning),
+    ));
+
+    for row in 0..n_rows {
+        for column in 0..n_columns {
+            let invader_type = match row {
+                0..=1 => InvaderType::A,
+                2..=3 => InvaderType::B,
+                _ => InvaderType::C,
+            };
+
+            let invader_position = Vec2::new(
+                board.area.min.x + TURRET_PADDING + (column as f32 + 0.5) * (INVADER_C_SIZE.x + GAP_BETWEEN_INVADERS),
+                board.area.max.y - HUD_HEIGHT - (INVADER_A_SIZE.y + GAP_BETWEEN_INVADERS) * (row as f32 + 1.) - drop,
+            );
+
+            spawn_invader(commands, sprites, board.id, invader_type, invader_position, 1);
+        }
+    }
+}
+
+fn spawn_turret(
+    commands: &mut Commands,
+    sprites: &Sprites,
+    board_id: BoardId,
+    player_id: PlayerId,
+    position: Vec2,
+) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                texture: sprites.load(TURRET_SPRITE),
+                sprite: Sprite { custom_size: Some(TURRET_SIZE), ..default() },
+                transform: Transform { translation: position.extend(0.), ..default() },
+                ..default()
+            },
+            Turret,
+            board_id,
+            player_id,
+            ShootCooldown(Timer::from_seconds(SHOOT_COOLDOWN, TimerMode::Once)),
+            CollisionLayer::TURRET,
+            // Each hit costs a life, and `players::handle_turret_hit` brings it back for the next one
+            Health::new(1),
+            StateScoped(AppState::SpaceInvaders),
+        ))
+        .id()
+}
+
+fn spawn_invader(
+    commands: &mut Commands,
+    sprites: &Sprites,
+    board_id: BoardId,
+    invader_type: InvaderType,
+    position: Vec2,
+    animation_frame: usize,
+) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                texture: sprites.load(&get_invader_sprite_path(&invader_type, animation_frame)),
+                sprite: Sprite { custom_size: Some(invader_type.size()), ..default() },
+                transform: Transform { translation: position.extend(0.0), ..default() },
+                ..default()
+            },
+            Invader { invader_type, animation_frame },
+            board_id,
+            CollisionLayer::INVADER,
+            Health::new(invader_type.hit_points()),
+            StateScoped(AppState::SpaceInvaders),
+        ))
+        .id()
+}
+
+fn spawn_bullet(
+    commands: &mut Commands,
+    sprites: &Sprites,
+    board_id: BoardId,
+    player_id: PlayerId,
+    position: Vec2,
+) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                texture: sprites.load(BULLET_SPRITE),
+                sprite: Sprite { custom_size: Some(BULLET_SIZE), ..default() },
+                transform: Transform { translation: position.extend(1.), ..default() },
+                ..default()
+            },
+            CollisionLayer::PLAYER_SHOT,
+            PLAYER_SHOT_MASK,
+            Bullet,
+            board_id,
+            player_id,
+            StateScoped(AppState::SpaceInvaders),
+        ))
+        .id()
+}
+
+fn spawn_invader_bullet(commands: &mut Commands, sprites: &Sprites, board_id: BoardId, position: Vec2) -> Entity {
+    commands
+        .spawn((
+            SpriteBundle {
+                texture: sprites.load(INVADER_BULLET_SPRITE),
+                sprite: Sprite { custom_size: Some(INVADER_BULLET_SIZE), ..default() },
+                transform: Transform { translation: position.extend(1.0), ..default() },
+                ..default()
+            },
+            CollisionLayer::INVADER_SHOT,
+            INVADER_SHOT_MASK,
+            InvaderBullet,
+            board_id,
+            StateScoped(AppState::SpaceInvaders),
+        ))
+        .id()
+}
+
+fn invader_shoot(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rng: ResMut<GameRng>,
+    mut board_query: Query<(&Board, &mut InvaderShootTimer)>,
+    invader_query: Query<(&Transform, &BoardId), With<Invader>>,
+    turret_query: Query<(&Transform, &BoardId, &PlayerId), With<Turret>>,
+    sprites: Sprites,
+) {
+    for (board, mut shoot_timer) in board_query.iter_mut() {
+        shoot_timer.0.tick(time.delta());
+
+        if !shoot_timer.0.finished() {
+            continue;
+        }
+
+        // Randomly pick which player to aim at. Sorting keeps the choice independent of query order.
+        let mut turrets: Vec<_> = turret_query.iter().filter(|(_, board_id, _)| **board_id == board.id).collect();
+        turrets.sort_by_key(|(_, _, player_id)| player_id.0);
+        let Some((target, _, _)) = turrets.choose(&mut rng.0) else {
+            continue;
+        };
+
+        let invaders: Vec<Vec3> = invader_query
+            .iter()
+            .filter(|(_, board_id)| **board_id == board.id)
+            .map(|(transform, _)| transform.translation)
+            .collect();
+
+        // Some shots are aimed from the column closest to the target, the rest come from a random column
+        let column_x = if rng.0.gen_bool(INVADER_AIMED_SHOT_CHANCE) {
+            target.translation.x
+        } else {
+            let mut columns: Vec<f32> = invaders.iter().map(|translation| translation.x).collect();
+            columns.sort_by(|a, b| a.total_cmp(b));
+            columns.dedup();
+            let Some(column_x) = columns.choose(&mut rng.0) else {
+                continue;
+            };
+            *column_x
+        };
+
+        // The lowest invader in that column takes the shot
+        let shooter = invaders.iter().min_by(|a, b| {
+            let a_distance = (a.x - column_x).abs();
+            let b_distance = (b.x - column_x).abs();
+            a_distance.total_cmp(&b_distance).then(a.y.total_cmp(&b.y)).then(a.x.total_cmp(&b.x))
+        });
+
+        if let Some(invader_translation) = shooter {
+            spawn_invader_bullet(
+                &mut commands,
+                &sprites,
+                board.id,
+                Vec2::new(invader_translation.x, invader_translation.y - INVADER_BULLET_SIZE.y / 2.0),
+            );
+        }
+    }
+}
+
+fn move_invader_bullet(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform), (With<InvaderBullet>, Without<Despawning>)>,
+    playfield: Res<Playfield>,
+    difficulty: Res<ActiveDifficulty>,
+    time: Res<Time>,
+) {
+    for (entity, mut bullet_transform) in query.iter_mut() {
+        bullet_transform.translation.y -= bullet_travel(difficulty.tuning.invader_bullet_speed, &time);
+
+        if bullet_transform.translation.y < playfield.0.min.y {
+            commands.entity(entity).insert(Despawning);
+        }
+    }
+}
+
+fn move_turret(
+    inputs: Res<PlayerInputs>,
+    mut query: Query<(&mut Transform, &PlayerId, &BoardId), With<Turret>>,
+    boards: Res<Boards>,
+    board_query: Query<&Board>,
+    time: Res<Time>,
+) {
+    for (mut turret_transform, player_id, board_id) in query.iter_mut() {
+        let input = inputs.0[player_id.0];
+        let mut direction = 0.0;
+
+        if input.left {
+            direction -= 1.0;
+        }
+
+        if input.right {
+            direction += 1.0;
+        }
+
+        let new_turret_position = turret_transform.translation.x + direction * TURRET_SPEED * time.delta_seconds();
+
+        let Ok(board) = board_query.get(boards.0[board_id.0]) else {
+            continue;
+        };
+        let left_bound = board.area.min.x + TURRET_SIZE.x / 2. + TURRET_PADDING;
+        let right_bound = board.area.max.x - TURRET_SIZE.x / 2. - TURRET_PADDING;
+
+        turret_transform.translation.x = new_turret_position.clamp(left_bound, right_bound);
+    }
+}
+
+fn shoot_bullet(
+    inputs: Res<PlayerInputs>,
+    mut commands: Commands,
+    mut query: Query<(&Transform, &PlayerId, &BoardId, &mut ShootCooldown), With<Turret>>,
+    time: Res<Time>,
+    sprites: Sprites,
+) {
+    for (turret_transform, player_id, board_id, mut cooldown) in query.iter_mut() {
+        cooldown.0.tick(time.delta());
+
+        if inputs.0[player_id.0].fire && cooldown.0.finished() {
+            spawn_bullet(
+                &mut commands,
+                &sprites,
+                *board_id,
+                *player_id,
+                Vec2::new(turret_transform.translation.x, turret_transform.translation.y + TURRET_SIZE.y / 2.),
+            );
+            cooldown.0.reset();
+        }
+    }
+}
+
+fn move_bullet(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform), (With<Bullet>, Without<Despawning>)>,
+    playfield: Res<Playfield>,
+    difficulty: Res<ActiveDifficulty>,
+    time: Res<Time>,
+) {
+    for (entity, mut bullet_transform) in query.iter_mut() {
+        bullet_transform.translation.y += bullet_travel(difficulty.tuning.bullet_speed, &time);
+
+        if bullet_transform.translation.y > playfield.0.max.y {
+            commands.entity(entity).insert(Despawning);
+        }
+    }
+}
+
+fn move_invaders(
+    mut query: Query<(&mut Transform, &BoardId), (With<Invader>, Without<Despawning>)>,
+    mut board_query: Query<(&Board, &mut InvaderDirection, &mut InvaderMoveTimer, &InvaderCount)>,
+    time: Res<Time>,
+) {
+    for (board, mut direction, mut move_timer, invader_count) in board_query.iter_mut() {
+        let on_board = |board_id: &BoardId| *board_id == board.id;
+
+        let current_invader_count = query.iter().filter(|(_, board_id)| on_board(board_id)).count() as f32;
+
+        let new_interval = (move_timer.initial_interval * current_invader_count / invader_count.total as f32)
+            .max(move_timer.minimum_interval);
+
+        move_timer.timer.set_duration(Duration::from_secs_f32(new_interval));
+        move_timer.timer.tick(time.delta());
+
+        let mut move_down = false;
+        let mut new_direction: InvaderDirection = InvaderDirection::Right;
+
+        let largest_x = query
+            .iter()
+            .filter(|(_, board_id)| on_board(board_id))
+            .map(|(t, _)| t.translation.x)
+            .max_by(|a, b| a.total_cmp(b));
+
+        let smallest_x = query
+            .iter()
+            .filter(|(_, board_id)| on_board(board_id))
+            .map(|(t, _)| t.translation.x)
+            .min_by(|a, b| a.total_cmp(b));
+
+        let (left_limit, right_limit) = formation_edge_limits(board);
+
+        if move_timer.timer.finished() {
+            for (mut transform, _) in query.iter_mut().filter(|(_, board_id)| on_board(board_id)) {
+                if let (Some(largest_x), Some(smallest_x)) = (largest_x, smallest_x) {
+                    match *direction {
+                        InvaderDirection::Left => {
+                            if smallest_x < left_limit {
+                                move_down = true;
+                                new_direction = InvaderDirection::Right;
+                            } else {
+                                transform.translation.x -= INVADER_STEP_SIZE;
+                            }
+                        }
+                        InvaderDirection::Right => {
+                            if largest_x > right_limit {
+                                move_down = true;
+                                new_direction = InvaderDirection::Left;
+                            } else {
+                                transform.translation.x += INVADER_STEP_SIZE;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if move_down {
+            for (mut transform, _) in query.iter_mut().filter(|(_, board_id)| on_board(board_id)) {
+                transform.translation.y -= INVADER_VERTICAL_STEP;
+            }
+            *direction = new_direction;
+        }
+    }
+}
+
+/// The leftmost and rightmost invader positions past which the formation drops a row and turns around
+fn formation_edge_limits(board: &Board) -> (f32, f32) {
+    (
+        board.area.min.x + GAP_BETWEEN_INVADERS + INVADER_C_SIZE.x,
+        board.area.max.x - GAP_BETWEEN_INVADERS - INVADER_C_SIZE.x,
+    )
+}
+
+fn animate_invaders(
+    mut query: Query<(&mut Handle<Image>, &mut Invader, &BoardId)>,
+    board_query: Query<(&Board, &InvaderMoveTimer)>,
+    sprites: Sprites,
+) {
+    for (board, animation_timer) in board_query.iter() {
+        if !animation_timer.timer.just_finished() {
+            continue;
+        }
+
+        for (mut texture_handle, mut invader, _) in query.iter_mut().filter(|(_, _, board_id)| **board_id == board.id) {
+            invader.animation_frame = if invader.animation_frame == 1 { 2 } else { 1 };
+
+            let new_texture_path = get_invader_sprite_path(&invader.invader_type, invader.animation_frame);
+
+            *texture_handle = sprites.load(&new_texture_path);
+        }
+    }
+}
+
+fn get_invader_sprite_path(invader_type: &InvaderType, frame: usize) -> String {
+    match invader_type {
+        InvaderType::A => format!("sprites\\invader_a{}.png", frame),
+        InvaderType::B => format!("sprites\\invader_b{}.png", frame),
+        InvaderType::C => format!("sprites\\invader_c{}.png", frame),
+    }
+}
+
+/// How far a shot flying at `speed` moves this tick, ignoring which way
+fn bullet_travel(speed: f32, time: &Time) -> f32 {
+    speed * time.delta_seconds()
+}
+
+// Returns how far along `movement` a point starting at `start` enters `target`, from 0 to 1, or
+// `None` if it doesn't get there. A point already inside enters at 0.
+fn sweep(start: Vec2, movement: Vec2, target: Aabb2d) -> Option<f32> {
+    let mut enter = 0.0_f32;
+    let mut exit = 1.0_f32;
+    for axis in 0..2 {
+        if movement[axis] == 0.0 {
+            if start[axis] < target.min[axis] || start[axis] > target.max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let to_min = (target.min[axis] - start[axis]) / movement[axis];
+        let to_max = (target.max[axis] - start[axis]) / movement[axis];
+        enter = enter.max(to_min.min(to_max));
+        exit = exit.min(to_min.max(to_max));
+    }
+
+    (enter <= exit).then_some(enter)
+}
+
+/// The first collider a shot reaches on its way this tick, if it reaches any. Each collider comes
+/// with how far it moves this tick too. Shots are swept along their whole path so that they can't
+/// skip past anything, even another shot flying the other way. When the shot reaches several at
+/// once it hits the first of them in `colliders`.
+fn first_hit<T>(bullet: Aabb2d, movement: Vec2, colliders: impl Iterator<Item = (T, Aabb2d, Vec2)>) -> Option<T> {
+    // Sweeping the shot's centre, moving as seen from the collider, against the collider grown by
+    // half the shot's size is the same as sweeping the whole shot
+    colliders
+        .filter_map(|(collider, bounding_box, collider_movement)| {
+            let time = sweep(bullet.center(), movement - collider_movement, bounding_box.grow(bullet.half_size()))?;
+            Some((collider, time))
+        })
+        .min_by(|(_, a_time), (_, b_time)| a_time.total_cmp(b_time))
+        .map(|(collider, _)| collider)
+}
+
+/// The area a sprite covers
+fn collider_box(transform: &Transform, sprite: &Sprite) -> Aabb2d {
+    Aabb2d::new(transform.translation.truncate(), sprite.custom_size.unwrap_or(Vec2::new(1.0, 1.0)) / 2.0)
+}
+
+/// Finds what each shot hits on its way this tick, going by its `CollisionMask`. The shot stops
+/// there and the target takes damage, which `handle_deaths` deals with once it's applied.
+fn check_for_collisions(
+    mut commands: Commands,
+    time: Res<Time>,
+    difficulty: Res<ActiveDifficulty>,
+    mut collision_events: EventWriter<CollisionEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    shot_query: Query<
+        (Entity, &Transform, &Sprite, &BoardId, &CollisionMask, Option<&PlayerId>, Has<InvaderBullet>),
+        Without<Despawning>,
+    >,
+    collider_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Sprite,
+            &BoardId,
+            &CollisionLayer,
+            Option<&Health>,
+            Option<&PlayerId>,
+            Has<Bullet>,
+            Has<InvaderBullet>,
+        ),
+        Without<Despawning>,
+    >,
+) {
+    let tuning = &difficulty.tuning;
+    // Player shots fly up and invader shots down. Nothing else moves this tick after the shots
+    // have been checked.
+    let movement = |is_bullet: bool, is_invader_bullet: bool| {
+        if is_invader_bullet {
+            Vec2::NEG_Y * bullet_travel(tuning.invader_bullet_speed, &time)
+        } else if is_bullet {
+            Vec2::Y * bullet_travel(tuning.bullet_speed, &time)
+        } else {
+            Vec2::ZERO
+        }
+    };
+
+    // Sorted so that ties don't depend on query order. A shot reaching several colliders at once
+    // hits the lowest-numbered player's turret, so the lower-numbered player takes the hit when
+    // co-op turrets overlap, then the lowest and leftmost collider.
+    let mut colliders: Vec<_> = collider_query.iter().collect();
+    colliders.sort_by(|(_, a, a_sprite, _, _, _, a_player, ..), (_, b, b_sprite, _, _, _, b_player, ..)| {
+        let (a_min, b_min) = (collider_box(a, a_sprite).min, collider_box(b, b_sprite).min);
+        a_player
+            .map(|player| player.0)
+            .cmp(&b_player.map(|player| player.0))
+            .then(a_min.y.total_cmp(&b_min.y))
+            .then(a_min.x.total_cmp(&b_min.x))
+    });
+
+    // Co-op players can both reach the same invader in a tick, so player shots go first in an
+    // order that doesn't depend on the query to decide who gets it
+    let mut shots: Vec<_> = shot_query.iter().collect();
+    shots.sort_by(|(_, a, _, _, _, a_owner, a_is_invader_bullet), (_, b, _, _, _, b_owner, b_is_invader_bullet)| {
+        a_is_invader_bullet
+            .cmp(b_is_invader_bullet)
+            .then(a_owner.map(|owner| owner.0).cmp(&b_owner.map(|owner| owner.0)))
+            .then(a.translation.x.total_cmp(&b.translation.x))
+            .then(a.translation.y.total_cmp(&b.translation.y))
+    });
+
+    // Damage sent this tick, which `apply_damage` hasn't taken off yet. Anything that's had enough
+    // to finish it off is already gone as far as later shots are concerned.
+    let mut damage_sent: Vec<(Entity, u32)> = Vec::new();
+    let finished_off = |damage_sent: &[(Entity, u32)], entity: Entity, health: Option<&Health>| {
+        health.is_some_and(|health| {
+            let damage: u32 =
+                damage_sent.iter().filter(|(target, _)| *target == entity).map(|(_, amount)| amount).sum();
+            damage >= health.current
+        })
+    };
+
+    for (shot_entity, shot_transform, shot_sprite, shot_board, shot_mask, _, is_invader_bullet) in shots {
+        let targets = colliders
+            .iter()
+            .filter(|(collider_entity, _, _, collider_board, collider_layer, health, ..)| {
+                *collider_entity != shot_entity
+                    && *collider_board == shot_board
+                    && shot_mask.hits(**collider_layer)
+                    && !finished_off(&damage_sent, *collider_entity, *health)
+            })
+            .map(|(collider_entity, collider_transform, collider_sprite, _, _, _, _, is_bullet, is_invader_bullet)| {
+                let collider_box = collider_box(collider_transform, collider_sprite);
+                (*collider_entity, collider_box, movement(*is_bullet, *is_invader_bullet))
+            });
+
+        let shot_box = collider_box(shot_transform, shot_sprite);
+        let Some(target) = first_hit(shot_box, movement(!is_invader_bullet, is_invader_bullet), targets) else {
+            continue;
+        };
+
+        collision_events.send_default();
+        commands.entity(shot_entity).insert(Despawning);
+        damage_events.send(DamageEvent { target, source: shot_entity, amount: SHOT_DAMAGE });
+        damage_sent.push((target, SHOT_DAMAGE));
+    }
+}
+
+/// Scores the invaders that have been shot down, and passes turrets that have been shot on to
+/// `players::handle_turret_hit`
+fn handle_deaths(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    mut invader_killed_events: EventWriter<InvaderKilledEvent>,
+    mut turret_hit_events: EventWriter<TurretHitEvent>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+    mut players: ResMut<Players>,
+    invader_query: Query<&Invader>,
+    turret_query: Query<&PlayerId, With<Turret>>,
+    looks_query: Query<(&Sprite, &Handle<Image>, &Transform)>,
+    // The shot is still around until the end of the tick
+    shooter_query: Query<&PlayerId, With<Bullet>>,
+) {
+    for death in death_events.read() {
+        if let Ok((sprite, texture, transform)) = looks_query.get(death.entity) {
+            explosion_events.send(ExplosionEvent {
+                sprite: sprite.clone(),
+                texture: texture.clone(),
+                transform: *transform,
+            });
+        }
+
+        if let Ok(invader) = invader_query.get(death.entity) {
+            commands.entity(death.entity).insert(Despawning);
+
+            if let Ok(shooter) = shooter_query.get(death.source) {
+                players.states[shooter.0].score += invader.invader_type.points();
+                invader_killed_events.send(InvaderKilledEvent { player: *shooter });
+            }
+        } else if let Ok(player_id) = turret_query.get(death.entity) {
+            // Losing the life and switching turns is handled by `players::handle_turret_hit`
+            turret_hit_events.send(TurretHitEvent { player: *player_id, cause: DeathCause::Shot });
+        }
+    }
+}
+
+/// Despawns everything marked `Despawning` during the tick. Doing it in one place at the end means
+/// that an entity can be hit, cleared away and scrolled off the board in the same tick and still
+/// be despawned just once.
+fn despawn_marked(mut commands: Commands, query: Query<Entity, With<Despawning>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use difficulty::Difficulty;
+
+    const TICK: Duration = Duration::from_micros(15_625);
+
+    fn collision_world() -> World {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(TICK);
+        world.insert_resource(time);
+        world.insert_resource(ActiveDifficulty::new(Difficulty::default()));
+        world.init_resource::<Events<CollisionEvent>>();
+        world.init_resource::<Events<DamageEvent>>();
+        world
+    }
+
+    fn spawn_collider(world: &mut World, position: Vec2, size: Vec2, layer: CollisionLayer) -> Entity {
+        let sprite = Sprite { custom_size: Some(size), ..default() };
+        world.spawn((Transform::from_translation(position.extend(0.)), sprite, BoardId(0), layer)).id()
+    }
+
+    fn spawn_shot(world: &mut World, position: Vec2) -> Entity {
+        let shot = spawn_collider(world, position, BULLET_SIZE, CollisionLayer::PLAYER_SHOT);
+        world.entity_mut(shot).insert((PLAYER_SHOT_MASK, Bullet, PlayerId(0)));
+        shot
+    }
+
+    fn damage_sent(world: &World) -> Vec<(Entity, Entity)> {
+        let events = world.resource::<Events<DamageEvent>>();
+        events.iter_current_update_events().map(|damage| (damage.target, damage.source)).collect()
+    }
+
+    #[test]
+    fn sweep_enters_at_zero_from_inside() {
+        let target = Aabb2d::new(Vec2::ZERO, Vec2::splat(10.));
+        assert_eq!(sweep(Vec2::new(5., 5.), Vec2::Y * 100., target), Some(0.));
+        assert_eq!(sweep(Vec2::new(0., -20.), Vec2::Y * 20., target), Some(0.5));
+        assert_eq!(sweep(Vec2::new(20., -20.), Vec2::Y * 100., target), None);
+    }
+
+    #[test]
+    fn fast_shot_hits_a_thin_invader_it_flies_past_in_one_tick() {
+        let mut world = collision_world();
+        let speed = world.resource::<ActiveDifficulty>().tuning.bullet_speed;
+        let travel = speed * TICK.as_secs_f32();
+        let shot = spawn_shot(&mut world, Vec2::ZERO);
+        // Thinner than the shot and wholly between where it starts and ends the tick
+        let invader = spawn_collider(&mut world, Vec2::Y * travel / 2., Vec2::new(20., 1.), CollisionLayer::INVADER);
+
+        world.run_system_once(check_for_collisions);
+        assert_eq!(damage_sent(&world), vec![(invader, shot)]);
+    }
+
+    #[test]
+    fn shot_touching_two_invaders_hits_only_the_first() {
+        let mut world = collision_world();
+        let shot = spawn_shot(&mut world, Vec2::ZERO);
+        let left = spawn_collider(&mut world, Vec2::new(-5., 0.), Vec2::splat(10.), CollisionLayer::INVADER);
+        spawn_collider(&mut world, Vec2::new(5., 0.), Vec2::splat(10.), CollisionLayer::INVADER);
+
+        world.run_system_once(check_for_collisions);
+        assert_eq!(damage_sent(&world), vec![(left, shot)]);
+        assert_eq!(world.resource::<Events<CollisionEvent>>().len(), 1);
+    }
+
+    #[test]
+    fn shots_flying_at_each_other_collide_head_on() {
+        let mut world = collision_world();
+        let tuning = world.resource::<ActiveDifficulty>().tuning;
+        let closing = (tuning.bullet_speed + tuning.invader_bullet_speed) * TICK.as_secs_f32();
+        let shot = spawn_shot(&mut world, Vec2::ZERO);
+        // Far enough apart that neither reaches where the other starts, but they pass in the tick
+        let gap = (BULLET_SIZE.y + INVADER_BULLET_SIZE.y) / 2. + closing * 0.9;
+        let invader_shot = spawn_collider(&mut world, Vec2::Y * gap, INVADER_BULLET_SIZE, CollisionLayer::INVADER_SHOT);
+        world.entity_mut(invader_shot).insert((INVADER_SHOT_MASK, InvaderBullet));
+
+        world.run_system_once(check_for_collisions);
+        assert_eq!(damage_sent(&world), vec![(invader_shot, shot)]);
+    }
+}
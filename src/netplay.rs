@@ -0,0 +1,537 @@
+//! Versus mode over UDP with rollback.
+//!
+//! Both peers run the full simulation of both boards. Every fixed tick each peer sends its own
+//! input to the other, scheduled `--input-delay` ticks in the future to hide some latency. When
+//! the opponent's input for a tick hasn't arrived yet it is predicted (they keep moving the same
+//! way and don't fire). If a prediction turns out wrong, the game rolls back to the snapshot
+//! taken before that tick and re-simulates up to the present with the real input. A peer never
+//! runs more than `--max-prediction` ticks ahead of the input it has confirmed.
+//!
+//! Every few ticks the peers exchange a checksum of the confirmed game state, and a mismatch is
+//! reported as a desync.
+//!
+//! To try it with two processes on one machine:
+//!
+//! ```text
+//! cargo run -- --bind 127.0.0.1:7001 --connect 127.0.0.1:7002 --player 1
+//! cargo run -- --bind 127.0.0.1:7002 --connect 127.0.0.1:7001 --player 2
+//! ```
+//!
+//! Each side plays with its player's keys, A/D/Space for player 1 and the arrows for player 2 by
+//! default, or `--autopilot` for the local player. Both peers must use the same `--seed` (0 by default)
+//! and the same `--difficulty` preset (normal by default). Their saved difficulty settings don't apply.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    arg_value,
+    input::{autopilot_from_args, InputSource, LocalInputs, PlayerInput, PlayerInputs},
+    pause::PauseDisabled,
+    snapshot::GameSnapshot,
+    GameState, InputSet,
+};
+
+const DEFAULT_INPUT_DELAY: u32 = 2;
+const DEFAULT_MAX_PREDICTION: u32 = 8;
+// How often, in ticks, the peers compare checksums
+const DESYNC_CHECK_INTERVAL: u32 = 16;
+// Upper bound on inputs resent in one packet while waiting for an acknowledgement
+const MAX_INPUTS_PER_PACKET: usize = 64;
+const NO_CHECKSUM: u32 = u32::MAX;
+// Furthest past our own tick that a packet may carry inputs or a checksum for. Honest peers
+// stay within their prediction limit and input delay of each other, far closer than this, so
+// anything beyond it is corrupt and would only make us allocate for ticks that never come.
+const MAX_FRAMES_AHEAD: u32 = 1024;
+
+pub struct NetplayConfig {
+    bind: SocketAddr,
+    remote: SocketAddr,
+    local_player: usize,
+    input_delay: u32,
+    max_prediction: u32,
+}
+
+impl NetplayConfig {
+    /// Netplay is enabled by `--connect <address>`, along with `--bind <address>` and `--player <1|2>`
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let remote = arg_value(args, "--connect")?;
+        let parse_frames = |name: &str, default: u32| {
+            arg_value(args, name)
+                .map_or(default, |value| value.parse().unwrap_or_else(|_| panic!("{name} expects a number of ticks")))
+        };
+
+        Some(NetplayConfig {
+            bind: arg_value(args, "--bind")
+                .unwrap_or("0.0.0.0:7000")
+                .parse()
+                .expect("--bind expects an address like 127.0.0.1:7001"),
+            remote: remote.parse().expect("--connect expects an address like 127.0.0.1:7002"),
+            local_player: match arg_value(args, "--player") {
+                Some("2") => 1,
+                _ => 0,
+            },
+            input_delay: parse_frames("--input-delay", DEFAULT_INPUT_DELAY),
+            max_prediction: parse_frames("--max-prediction", DEFAULT_MAX_PREDICTION).max(1),
+        })
+    }
+
+    /// Who controls each player: the keyboard, or the autopilot with `--autopilot`, for the
+    /// local player, and the opponent's machine for the other
+    pub fn input_sources(&self, args: &[String]) -> Vec<InputSource> {
+        let mut sources = vec![InputSource::External; 2];
+        sources[self.local_player] = autopilot_from_args(args, "--autopilot").unwrap_or(InputSource::Keyboard);
+        sources
+    }
+}
+
+#[derive(Resource)]
+pub struct NetplaySession {
+    socket: UdpSocket,
+    remote: SocketAddr,
+    local_player: usize,
+    max_prediction: u32,
+    /// The tick about to be simulated
+    frame: u32,
+    /// Our input for every tick, including the ones queued up by the input delay
+    local_inputs: Vec<PlayerInput>,
+    /// The opponent's input for every tick we have received it for
+    remote_inputs: Vec<Option<PlayerInput>>,
+    /// The opponent's input each tick was last simulated with, which may have been predicted
+    simulated_remote_inputs: Vec<PlayerInput>,
+    /// Number of leading ticks for which the opponent's input is known
+    confirmed_frames: u32,
+    /// Number of leading ticks of our input the opponent has received
+    remote_ack: u32,
+    /// Game state at the start of each tick that may still need to be rolled back to
+    snapshots: VecDeque<(u32, GameSnapshot)>,
+    local_checksums: BTreeMap<u32, u64>,
+    remote_checksums: BTreeMap<u32, u64>,
+    /// Latest confirmed checksum, resent with every packet
+    last_checksum: Option<(u32, u64)>,
+    next_checksum_frame: u32,
+    stalled: bool,
+    desync_frame: Option<u32>,
+    rollbacks: u32,
+}
+
+pub fn add_netplay(app: &mut App, config: NetplayConfig) {
+    let socket = UdpSocket::bind(config.bind)
+        .unwrap_or_else(|error| panic!("Couldn't bind netplay socket to {}: {error}", config.bind));
+    socket.set_nonblocking(true).expect("Couldn't make the netplay socket non-blocking");
+
+    // The opponent's game can't be frozen, so there's no pausing
+    app.insert_resource(PauseDisabled)
+        .insert_resource(NetplaySession {
+            socket,
+            remote: config.remote,
+            local_player: config.local_player,
+            max_prediction: config.max_prediction,
+            frame: 0,
+            local_inputs: vec![PlayerInput::default(); config.input_delay as usize],
+            remote_inputs: Vec::new(),
+            simulated_remote_inputs: Vec::new(),
+            confirmed_frames: 0,
+            remote_ack: 0,
+            snapshots: VecDeque::new(),
+            local_checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            last_checksum: None,
+            next_checksum_frame: 0,
+            stalled: false,
+            desync_frame: None,
+            rollbacks: 0,
+        })
+        .add_systems(FixedPreUpdate, netplay_tick.after(InputSet).run_if(in_state(GameState::Playing)));
+}
+
+/// Run condition for the gameplay systems, which pause while netplay waits for the opponent
+pub fn simulation_running(session: Option<Res<NetplaySession>>) -> bool {
+    session.is_none_or(|session| !session.stalled)
+}
+
+impl NetplaySession {
+    pub fn status(&self) -> String {
+        let mut status = format!(
+            "NETPLAY P{}   TICK {}   AHEAD {}   ROLLBACKS {}",
+            self.local_player + 1,
+            self.frame,
+            self.frame.saturating_sub(self.confirmed_frames),
+            self.rollbacks,
+        );
+        if self.stalled {
+            status.push_str("   WAITING FOR OPPONENT");
+        }
+        if let Some(frame) = self.desync_frame {
+            status.push_str(&format!("   DESYNC AT TICK {frame}"));
+        }
+        status
+    }
+
+    /// Reads every pending packet and returns the earliest tick that was simulated with a
+    /// wrong prediction, if any
+    fn receive(&mut self) -> Option<u32> {
+        let mut mispredicted: Option<u32> = None;
+        let mut buffer = [0u8; 1024];
+
+        loop {
+            let (length, sender) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                // On some platforms an unreachable peer shows up as an error on the next receive
+                Err(_) => break,
+            };
+
+            if sender != self.remote {
+                continue;
+            }
+
+            let Some(packet) = Packet::decode(&buffer[..length]) else {
+                continue;
+            };
+
+            let frame_limit = self.frame.saturating_add(MAX_FRAMES_AHEAD);
+            let last_frame = packet.first_frame.saturating_add(packet.inputs.len() as u32);
+            let checksum_frame = packet.checksum.map_or(0, |(frame, _)| frame);
+            if last_frame > frame_limit || checksum_frame > frame_limit {
+                warn!("Ignoring a netplay packet for tick {last_frame}, too far past tick {}", self.frame);
+                continue;
+            }
+
+            self.remote_ack = self.remote_ack.max(packet.ack);
+
+            if let Some((frame, checksum)) = packet.checksum {
+                self.remote_checksums.insert(frame, checksum);
+            }
+
+            for (offset, input) in packet.inputs.into_iter().enumerate() {
+                let frame = (packet.first_frame + offset as u32) as usize;
+                if frame >= self.remote_inputs.len() {
+                    self.remote_inputs.resize(frame + 1, None);
+                }
+                if self.remote_inputs[frame].is_some() {
+                    continue;
+                }
+                self.remote_inputs[frame] = Some(input);
+
+                if self.simulated_remote_inputs.get(frame).is_some_and(|simulated| *simulated != input) {
+                    mispredicted = Some(mispredicted.map_or(frame as u32, |earliest| earliest.min(frame as u32)));
+                }
+            }
+        }
+
+        while self.remote_inputs.get(self.confirmed_frames as usize).is_some_and(Option::is_some) {
+            self.confirmed_frames += 1;
+        }
+
+        mispredicted
+    }
+
+    /// Inputs for both players on `frame`, predicting the opponent's if it hasn't arrived
+    fn inputs_for(&mut self, frame: u32) -> Vec<PlayerInput> {
+        let frame = frame as usize;
+        let remote = self.remote_inputs.get(frame).copied().flatten().unwrap_or_else(|| {
+            let last_known = self.remote_inputs[..frame.min(self.remote_inputs.len())]
+                .iter()
+                .rev()
+                .find_map(|input| *input)
+                .unwrap_or_default();
+            PlayerInput { fire: false, ..last_known }
+        });
+
+        if frame >= self.simulated_remote_inputs.len() {
+            self.simulated_remote_inputs.resize(frame + 1, PlayerInput::default());
+        }
+        self.simulated_remote_inputs[frame] = remote;
+
+        let local = self.local_inputs[frame];
+        if self.local_player == 0 {
+            vec![local, remote]
+        } else {
+            vec![remote, local]
+        }
+    }
+
+    fn store_snapshot(&mut self, frame: u32, snapshot: GameSnapshot) {
+        self.snapshots.retain(|(snapshot_frame, _)| *snapshot_frame != frame);
+        self.snapshots.push_back((frame, snapshot));
+    }
+
+    fn snapshot(&self, frame: u32) -> Option<&GameSnapshot> {
+        self.snapshots.iter().find(|(snapshot_frame, _)| *snapshot_frame == frame).map(|(_, snapshot)| snapshot)
+    }
+
+    /// Records checksums of newly confirmed snapshots and compares them with the opponent's
+    fn check_desync(&mut self) {
+        while self.next_checksum_frame <= self.confirmed_frames.min(self.frame) {
+            let frame = self.next_checksum_frame;
+            if let Some(snapshot) = self.snapshot(frame) {
+                let checksum = snapshot.checksum();
+                self.local_checksums.insert(frame, checksum);
+                self.last_checksum = Some((frame, checksum));
+            }
+            self.next_checksum_frame += DESYNC_CHECK_INTERVAL;
+        }
+
+        let compared: Vec<u32> =
+            self.remote_checksums.keys().filter(|frame| self.local_checksums.contains_key(frame)).copied().collect();
+
+        for frame in compared {
+            let remote = self.remote_checksums.remove(&frame);
+            let local = self.local_checksums.remove(&frame);
+            if remote != local && self.desync_frame.is_none() {
+                error!("Netplay desync detected at tick {frame}");
+                self.desync_frame = Some(frame);
+            }
+        }
+    }
+
+    fn send(&self) {
+        let first_frame = self.remote_ack.min(self.local_inputs.len() as u32);
+        let inputs: Vec<PlayerInput> =
+            self.local_inputs[first_frame as usize..].iter().take(MAX_INPUTS_PER_PACKET).copied().collect();
+
+        let packet = Packet { ack: self.confirmed_frames, checksum: self.last_checksum, first_frame, inputs };
+
+        // Lost packets are fine, everything unacknowledged is resent next tick
+        let _ = self.socket.send_to(&packet.encode(), self.remote);
+    }
+
+    /// Drops snapshots that can no longer be rolled back to or checksummed
+    fn prune(&mut self) {
+        let oldest_needed = self.confirmed_frames.min(self.next_checksum_frame);
+        self.snapshots.retain(|(frame, _)| *frame >= oldest_needed);
+    }
+}
+
+/// Advances the netplay session by one tick, before `FixedUpdate` simulates it
+fn netplay_tick(world: &mut World) {
+    world.resource_mut::<NetplaySession>().stalled = false;
+
+    if let Some(frame) = world.resource_mut::<NetplaySession>().receive() {
+        rollback(world, frame);
+    }
+
+    let (frame, stalled) = {
+        let session = world.resource::<NetplaySession>();
+        (session.frame, session.frame >= session.confirmed_frames + session.max_prediction)
+    };
+
+    if stalled {
+        // Too far ahead of the opponent, so wait for their input rather than predicting further
+        world.resource_mut::<NetplaySession>().stalled = true;
+    } else {
+        let local_player = world.resource::<NetplaySession>().local_player;
+        let local_input = world.resource_mut::<LocalInputs>().take(local_player);
+        let snapshot = GameSnapshot::capture(world);
+
+        let mut session = world.resource_mut::<NetplaySession>();
+        session.local_inputs.push(local_input);
+        session.store_snapshot(frame, snapshot);
+        let inputs = session.inputs_for(frame);
+        session.frame += 1;
+
+        world.resource_mut::<PlayerInputs>().0 = inputs;
+    }
+
+    let mut session = world.resource_mut::<NetplaySession>();
+    session.check_desync();
+    session.send();
+    session.prune();
+
+    // The game only ends once the opponent's inputs confirm it
+    let confirmed_frame = session.confirmed_frames.min(session.frame);
+    let game_over = session.snapshot(confirmed_frame).is_some_and(|snapshot| snapshot.players().is_game_over());
+    if game_over {
+        world.resource_mut::<NextState<GameState>>().set(GameState::GameOver);
+    }
+}
+
+/// Restores the snapshot from the start of `from` and re-simulates every tick up to the present
+fn rollback(world: &mut World, from: u32) {
+    let session = world.resource::<NetplaySession>();
+    let Some(snapshot) = session.snapshot(from).cloned() else {
+        error!("Netplay can't roll back to tick {from}, the snapshot is gone");
+        return;
+    };
+    let present = session.frame;
+
+    snapshot.restore(world);
+
+    for frame in from..present {
+        if frame != from {
+            let snapshot = GameSnapshot::capture(world);
+            world.resource_mut::<NetplaySession>().store_snapshot(frame, snapshot);
+        }
+
+        let inputs = world.resource_mut::<NetplaySession>().inputs_for(frame);
+        world.resource_mut::<PlayerInputs>().0 = inputs;
+        world.run_schedule(FixedUpdate);
+    }
+
+    world.resource_mut::<NetplaySession>().rollbacks += 1;
+}
+
+/// Wire format, all little-endian:
+/// ack (u32), checksum frame (u32, `NO_CHECKSUM` if none), checksum (u64),
+/// first input frame (u32), input count (u8), then one byte per input.
+struct Packet {
+    ack: u32,
+    checksum: Option<(u32, u64)>,
+    first_frame: u32,
+    inputs: Vec<PlayerInput>,
+}
+
+impl Packet {
+    fn encode(&self) -> Vec<u8> {
+        let (checksum_frame, checksum) = self.checksum.unwrap_or((NO_CHECKSUM, 0));
+
+        let mut bytes = Vec::with_capacity(21 + self.inputs.len());
+        bytes.extend_from_slice(&self.ack.to_le_bytes());
+        bytes.extend_from_slice(&checksum_frame.to_le_bytes());
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes.extend_from_slice(&self.first_frame.to_le_bytes());
+        bytes.push(self.inputs.len() as u8);
+        bytes.extend(self.inputs.iter().map(|input| input.to_bits()));
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let u32_at = |offset: usize| Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?));
+
+        let ack = u32_at(0)?;
+        let checksum_frame = u32_at(4)?;
+        let checksum = u64::from_le_bytes(bytes.get(8..16)?.try_into().ok()?);
+        let first_frame = u32_at(16)?;
+        let count = *bytes.get(20)? as usize;
+        let inputs = bytes.get(21..21 + count)?.iter().map(|bits| PlayerInput::from_bits(*bits)).collect();
+
+        Some(Packet {
+            ack,
+            checksum: (checksum_frame != NO_CHECKSUM).then_some((checksum_frame, checksum)),
+            first_frame,
+            inputs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        autopilot::AutopilotSkill,
+        headless::{headless_app, start_headless},
+        players::GameMode,
+        Difficulty,
+    };
+
+    fn session() -> NetplaySession {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+        NetplaySession {
+            remote: socket.local_addr().unwrap(),
+            socket,
+            local_player: 0,
+            max_prediction: DEFAULT_MAX_PREDICTION,
+            frame: 0,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            simulated_remote_inputs: Vec::new(),
+            confirmed_frames: 0,
+            remote_ack: 0,
+            snapshots: VecDeque::new(),
+            local_checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            last_checksum: None,
+            next_checksum_frame: 0,
+            stalled: false,
+            desync_frame: None,
+            rollbacks: 0,
+        }
+    }
+
+    /// Plays a seeded versus game headless for `ticks` ticks, with player 1 holding `input`
+    fn snapshot_after(seed: u64, ticks: u32, input: PlayerInput) -> GameSnapshot {
+        let mut app = headless_app(GameMode::Versus, Difficulty::default(), seed);
+        start_headless(&mut app);
+        for _ in 0..ticks {
+            app.world_mut().resource_mut::<PlayerInputs>().0[0] = input;
+            app.update();
+        }
+        GameSnapshot::capture(app.world_mut())
+    }
+
+    #[test]
+    fn packets_survive_encoding() {
+        let inputs = vec![
+            PlayerInput { left: true, ..default() },
+            PlayerInput { right: true, fire: true, ..default() },
+            PlayerInput::default(),
+        ];
+        let packet = Packet { ack: 7, checksum: Some((16, 0xdead_beef_cafe)), first_frame: 40, inputs: inputs.clone() };
+
+        let decoded = Packet::decode(&packet.encode()).unwrap();
+        assert_eq!(decoded.ack, 7);
+        assert_eq!(decoded.checksum, Some((16, 0xdead_beef_cafe)));
+        assert_eq!(decoded.first_frame, 40);
+        assert_eq!(decoded.inputs, inputs);
+
+        let without_checksum = Packet { checksum: None, ..packet };
+        assert_eq!(Packet::decode(&without_checksum.encode()).unwrap().checksum, None);
+    }
+
+    #[test]
+    fn truncated_packets_are_rejected() {
+        let packet = Packet { ack: 0, checksum: None, first_frame: 0, inputs: vec![PlayerInput::default(); 4] };
+        let bytes = packet.encode();
+        for length in 0..bytes.len() {
+            assert!(Packet::decode(&bytes[..length]).is_none(), "decoded {length} of {} bytes", bytes.len());
+        }
+    }
+
+    #[test]
+    fn inputs_far_in_the_future_are_ignored() {
+        let mut session = session();
+        let packet = Packet {
+            ack: 0,
+            checksum: Some((u32::MAX - 1, 0)),
+            first_frame: u32::MAX - 10,
+            inputs: vec![PlayerInput::default(); 4],
+        };
+        session.socket.send_to(&packet.encode(), session.remote).unwrap();
+        let near = Packet { ack: 0, checksum: None, first_frame: 0, inputs: vec![PlayerInput::default(); 2] };
+        session.socket.send_to(&near.encode(), session.remote).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        session.receive();
+        assert_eq!(session.remote_inputs.len(), 2);
+        assert!(session.remote_checksums.is_empty());
+        assert_eq!(session.confirmed_frames, 2);
+    }
+
+    #[test]
+    fn autopilot_plays_the_local_player() {
+        let args: Vec<String> =
+            ["--connect", "127.0.0.1:7001", "--player", "2", "--autopilot", "hard"].map(String::from).to_vec();
+        let config = NetplayConfig::from_args(&args).unwrap();
+        assert_eq!(config.input_sources(&args), [InputSource::External, InputSource::Autopilot(AutopilotSkill::Hard)]);
+    }
+
+    #[test]
+    fn checksums_match_for_the_same_game() {
+        let input = PlayerInput { right: true, fire: true, ..default() };
+        assert_eq!(snapshot_after(3, 120, input).checksum(), snapshot_after(3, 120, input).checksum());
+    }
+
+    #[test]
+    fn checksums_differ_once_games_diverge() {
+        let left = PlayerInput { left: true, ..default() };
+        let right = PlayerInput { right: true, ..default() };
+        assert_ne!(snapshot_after(3, 30, left).checksum(), snapshot_after(3, 30, right).checksum());
+    }
+}
@@ -0,0 +1,243 @@
+//! Pausing, shared by both games.
+//!
+//! Escape or P pauses and opens the pause menu, as does the window losing focus. Pausing stops
+//! virtual time, which freezes everything on the fixed timestep while `Update` and the UI keep
+//! running. In debug builds, pressing `.` while paused runs the fixed schedules for exactly one
+//! tick, for stepping through the gameplay.
+//!
+//! Choosing "Restart" sends a `RestartEvent` for the game to handle, and "Menu" goes back to the launcher.
+
+use bevy::{
+    app::{AppExit, FixedMain},
+    prelude::*,
+    window::WindowFocused,
+};
+
+use crate::{launcher::AppState, settings};
+
+const MENU_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+const BUTTON_COLOUR: Color = Color::srgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED_COLOUR: Color = Color::srgb(0.3, 0.3, 0.3);
+const MENU_TEXT_COLOUR: Color = Color::srgb(1.0, 1.0, 1.0);
+const TITLE_FONT_SIZE: f32 = 40.0;
+const BUTTON_FONT_SIZE: f32 = 24.0;
+#[cfg(debug_assertions)]
+const HINT_FONT_SIZE: f32 = 16.0;
+const BUTTON_SIZE: Vec2 = Vec2::new(200.0, 44.0);
+const BUTTON_GAP: Val = Val::Px(10.0);
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<PauseState>()
+            .add_event::<RestartEvent>()
+            .add_systems(
+                Update,
+                (toggle_pause.run_if(not(settings::menu_open)), pause_on_focus_loss)
+                    .run_if(not(resource_exists::<PauseDisabled>))
+                    .run_if(not(in_state(AppState::Menu))),
+            )
+            .add_systems(Update, (handle_menu_buttons, highlight_buttons).run_if(in_state(PauseState::Paused)))
+            .add_systems(OnEnter(PauseState::Paused), (stop_time, spawn_pause_menu))
+            .add_systems(OnExit(PauseState::Paused), (resume_time, despawn_pause_menu));
+
+        #[cfg(debug_assertions)]
+        app.add_systems(Update, step_one_tick.run_if(in_state(PauseState::Paused)));
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// Sent when the player asks to start the game over from the pause menu
+#[derive(Event, Default)]
+pub struct RestartEvent;
+
+/// While this resource exists the game can't be paused, e.g. in netplay where the opponent can't be frozen
+#[derive(Resource)]
+pub struct PauseDisabled;
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component, Clone, Copy)]
+enum MenuButton {
+    Resume,
+    Restart,
+    Settings,
+    Menu,
+    Quit,
+}
+
+impl MenuButton {
+    fn label(self) -> &'static str {
+        match self {
+            MenuButton::Resume => "RESUME",
+            MenuButton::Restart => "RESTART",
+            MenuButton::Settings => "SETTINGS",
+            MenuButton::Menu => "MENU",
+            MenuButton::Quit => "QUIT",
+        }
+    }
+}
+
+fn toggle_pause(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<PauseState>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    if keyboard_input.any_just_pressed([KeyCode::Escape, KeyCode::KeyP]) {
+        next_state.set(match state.get() {
+            PauseState::Running => PauseState::Paused,
+            PauseState::Paused => PauseState::Running,
+        });
+    }
+}
+
+fn pause_on_focus_loss(mut focus_events: EventReader<WindowFocused>, mut next_state: ResMut<NextState<PauseState>>) {
+    if focus_events.read().any(|event| !event.focused) {
+        next_state.set(PauseState::Paused);
+    }
+}
+
+fn stop_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn spawn_pause_menu(mut commands: Commands) {
+    let text_style = |font_size| TextStyle { font_size, color: MENU_TEXT_COLOUR, ..default() };
+
+    commands
+        .spawn((
+            PauseMenu,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: BUTTON_GAP,
+                    ..default()
+                },
+                background_color: MENU_BACKGROUND.into(),
+                // Above the HUD
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+        ))
+        .with_children(|menu| {
+            menu.spawn(TextBundle::from_section("PAUSED", text_style(TITLE_FONT_SIZE)));
+
+            for button in
+                [MenuButton::Resume, MenuButton::Restart, MenuButton::Settings, MenuButton::Menu, MenuButton::Quit]
+            {
+                spawn_menu_button(menu, button.label(), button);
+            }
+
+            #[cfg(debug_assertions)]
+            menu.spawn(TextBundle::from_section("PRESS . TO STEP ONE TICK", text_style(HINT_FONT_SIZE)));
+        });
+}
+
+fn despawn_pause_menu(mut commands: Commands, menu_query: Query<Entity, With<PauseMenu>>) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+/// Spawns a button in the style of the game's menus, labelled `label` and tagged with `marker`
+pub fn spawn_menu_button(parent: &mut ChildBuilder, label: &str, marker: impl Component) {
+    spawn_sized_menu_button(parent, label, BUTTON_SIZE, marker);
+}
+
+/// Spawns a menu button of a different size to the rest
+pub fn spawn_sized_menu_button(parent: &mut ChildBuilder, label: &str, size: Vec2, marker: impl Component) {
+    parent
+        .spawn((
+            marker,
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(size.x),
+                    height: Val::Px(size.y),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: BUTTON_COLOUR.into(),
+                ..default()
+            },
+        ))
+        .with_children(|button| {
+            button.spawn(TextBundle::from_section(
+                label,
+                TextStyle { font_size: BUTTON_FONT_SIZE, color: MENU_TEXT_COLOUR, ..default() },
+            ));
+        });
+}
+
+/// Lights up menu buttons under the cursor
+pub fn highlight_buttons(
+    mut button_query: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<Button>)>,
+) {
+    for (interaction, mut background) in button_query.iter_mut() {
+        *background = match interaction {
+            Interaction::Hovered | Interaction::Pressed => BUTTON_HOVERED_COLOUR.into(),
+            Interaction::None => BUTTON_COLOUR.into(),
+        };
+    }
+}
+
+fn handle_menu_buttons(
+    mut commands: Commands,
+    button_query: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut restart_events: EventWriter<RestartEvent>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            MenuButton::Resume => next_state.set(PauseState::Running),
+            MenuButton::Restart => {
+                restart_events.send_default();
+                next_state.set(PauseState::Running);
+            }
+            MenuButton::Settings => settings::open_menu(&mut commands),
+            MenuButton::Menu => {
+                next_app_state.set(AppState::Menu);
+                next_state.set(PauseState::Running);
+            }
+            MenuButton::Quit => {
+                app_exit_events.send(AppExit::Success);
+            }
+        }
+    }
+}
+
+/// Runs the fixed schedules for one tick while virtual time stays paused
+#[cfg(debug_assertions)]
+fn step_one_tick(world: &mut World) {
+    if !world.resource::<ButtonInput<KeyCode>>().just_pressed(KeyCode::Period) {
+        return;
+    }
+
+    let timestep = world.resource::<Time<Fixed>>().timestep();
+    world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    world.run_schedule(FixedMain);
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
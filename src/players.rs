@@ -0,0 +1,708 @@
+//! Per-player score, lives and wave, and the 2-player modes.
+//!
+//! In alternating mode only one player is on the field at a time. When the active player dies,
+//! their board (surviving invaders and the formation's march state) is saved and the next
+//! player's board is restored, just like on the arcade cabinet.
+//!
+//! In co-op mode both players have a turret on the same board and the game ends once both
+//! have run out of lives.
+//!
+//! In versus mode each player defends their own board, and every few invaders a player shoots
+//! sends an extra invader to the opponent. The first player to run out of lives loses.
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    arg_value,
+    damage::Health,
+    difficulty::{ActiveDifficulty, Difficulty},
+    launcher::AppState,
+    netplay::NetplaySession,
+    pause::RestartEvent,
+    spawn_invader, spawn_turret, spawn_wave, Board, BoardId, Boards, Bullet, Despawning, GameOver, GameRng, GameState,
+    Invader, InvaderBullet, InvaderDirection, InvaderKilledEvent, InvaderMoveTimer, InvaderShootTimer, InvaderType,
+    PlayerDied, PlayerId, Playfield, ScoreChanged, Sprites, Turret, GAP_BETWEEN_INVADERS, HUD_HEIGHT, INVADER_A_SIZE,
+    TURRET_PADDING, TURRET_SIZE,
+};
+
+const HUD_FONT_SIZE: f32 = 20.0;
+const HUD_TEXT_PADDING: Val = Val::Px(5.0);
+const HUD_COLOUR: Color = Color::srgb(1.0, 1.0, 1.0);
+// How many invaders a versus player has to shoot to send one to their opponent
+const VERSUS_KILLS_PER_EXTRA_INVADER: u32 = 4;
+
+/// Sent when a player loses a life
+#[derive(Event)]
+pub struct TurretHitEvent {
+    pub player: PlayerId,
+    pub cause: DeathCause,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause {
+    /// Hit by an invader bullet, which costs one life
+    Shot,
+    /// The invaders reached the turret, which ends the player's game
+    Invasion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    OnePlayer,
+    TwoPlayerAlternating,
+    TwoPlayerCoop,
+    Versus,
+}
+
+impl GameMode {
+    /// `--two-player` starts the arcade's alternating mode, where players take turns after each
+    /// death, `--coop` puts both players on the field at once and `--versus` gives each player
+    /// their own board. Netplay (`--connect`) is always versus.
+    pub fn from_args(args: &[String]) -> Self {
+        let has_flag = |flag: &str| args.iter().any(|arg| arg == flag);
+
+        if has_flag("--versus") || arg_value(args, "--connect").is_some() {
+            GameMode::Versus
+        } else if has_flag("--coop") {
+            GameMode::TwoPlayerCoop
+        } else if has_flag("--two-player") {
+            GameMode::TwoPlayerAlternating
+        } else {
+            GameMode::OnePlayer
+        }
+    }
+
+    pub fn player_count(self) -> usize {
+        match self {
+            GameMode::OnePlayer => 1,
+            GameMode::TwoPlayerAlternating | GameMode::TwoPlayerCoop | GameMode::Versus => 2,
+        }
+    }
+
+    pub fn board_count(self) -> usize {
+        match self {
+            GameMode::Versus => self.player_count(),
+            GameMode::OnePlayer | GameMode::TwoPlayerAlternating | GameMode::TwoPlayerCoop => 1,
+        }
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct Players {
+    pub mode: GameMode,
+    pub states: Vec<PlayerState>,
+    /// Index into `states` of the player currently on the field in alternating mode
+    pub active: usize,
+}
+
+#[derive(Clone)]
+pub struct PlayerState {
+    pub score: u32,
+    pub lives: u32,
+    pub wave: u32,
+    /// Invaders shot so far, which in versus mode decides when to send one to the opponent
+    pub kills: u32,
+    /// The player's board while they are waiting for their turn.
+    /// `None` means they start on a fresh wave.
+    pub board: Option<BoardSnapshot>,
+    /// How the player most recently lost a life
+    pub death_cause: Option<DeathCause>,
+    /// Whether the player has had the difficulty's bonus life yet
+    pub bonus_life_awarded: bool,
+}
+
+/// Everything needed to put a player's board back exactly as they left it
+#[derive(Clone)]
+pub struct BoardSnapshot {
+    invaders: Vec<InvaderSnapshot>,
+    direction: InvaderDirection,
+    move_timer: InvaderMoveTimer,
+    shoot_timer: InvaderShootTimer,
+}
+
+#[derive(Clone)]
+struct InvaderSnapshot {
+    invader_type: InvaderType,
+    position: Vec2,
+    animation_frame: usize,
+}
+
+#[derive(Component)]
+pub struct HudUi;
+
+impl Players {
+    pub fn new(mode: GameMode, starting_lives: u32) -> Self {
+        Players {
+            mode,
+            states: (0..mode.player_count())
+                .map(|_| PlayerState {
+                    score: 0,
+                    lives: starting_lives,
+                    wave: 1,
+                    kills: 0,
+                    board: None,
+                    death_cause: None,
+                    bonus_life_awarded: false,
+                })
+                .collect(),
+            active: 0,
+        }
+    }
+
+    pub fn board_count(&self) -> usize {
+        self.mode.board_count()
+    }
+
+    /// The board a player's turret plays on
+    pub fn board_of(&self, player: usize) -> BoardId {
+        match self.mode {
+            GameMode::Versus => BoardId(player),
+            GameMode::OnePlayer | GameMode::TwoPlayerAlternating | GameMode::TwoPlayerCoop => BoardId(0),
+        }
+    }
+
+    /// The wave being played on a board. Co-op players who are out stop counting waves, so the
+    /// board is on the furthest wave of anyone on it.
+    pub fn wave_on(&self, board: BoardId) -> u32 {
+        match self.mode {
+            GameMode::TwoPlayerAlternating => self.states[self.active].wave,
+            GameMode::OnePlayer | GameMode::TwoPlayerCoop | GameMode::Versus => (0..self.states.len())
+                .filter(|&index| self.board_of(index) == board)
+                .map(|index| self.states[index].wave)
+                .max()
+                .unwrap_or(1),
+        }
+    }
+
+    /// Players who currently have a turret on the field
+    fn on_field(&self) -> Vec<usize> {
+        match self.mode {
+            GameMode::TwoPlayerCoop | GameMode::Versus => {
+                (0..self.states.len()).filter(|&index| self.states[index].lives > 0).collect()
+            }
+            GameMode::OnePlayer | GameMode::TwoPlayerAlternating => vec![self.active],
+        }
+    }
+
+    /// Where a player's turret starts and respawns
+    fn turret_start_x(&self, player: usize, playfield: &Playfield) -> f32 {
+        let board_area = playfield.board_area(self.board_of(player), self.board_count());
+        let offset = match self.mode {
+            GameMode::TwoPlayerCoop => (player as f32 - 0.5) * board_area.width() / 2.,
+            GameMode::OnePlayer | GameMode::TwoPlayerAlternating | GameMode::Versus => 0.,
+        };
+        board_area.center().x + offset
+    }
+
+    /// The player who should play after `current`, trying the others first.
+    /// Returns `current` when nobody else has lives left, or `None` if the game is over.
+    fn next_player(&self, current: usize) -> Option<usize> {
+        let player_count = self.states.len();
+        (1..=player_count).map(|offset| (current + offset) % player_count).find(|&index| self.states[index].lives > 0)
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        match self.mode {
+            // Versus ends as soon as either player is out
+            GameMode::Versus => self.states.iter().any(|player| player.lives == 0),
+            GameMode::OnePlayer | GameMode::TwoPlayerAlternating | GameMode::TwoPlayerCoop => {
+                self.states.iter().all(|player| player.lives == 0)
+            }
+        }
+    }
+}
+
+impl BoardSnapshot {
+    fn capture<'a>(
+        invaders: impl Iterator<Item = (&'a Transform, &'a Invader)>,
+        direction: &InvaderDirection,
+        move_timer: &InvaderMoveTimer,
+        shoot_timer: &InvaderShootTimer,
+    ) -> Self {
+        BoardSnapshot {
+            invaders: invaders
+                .map(|(transform, invader)| InvaderSnapshot {
+                    invader_type: invader.invader_type,
+                    position: transform.translation.truncate(),
+                    animation_frame: invader.animation_frame,
+                })
+                .collect(),
+            direction: *direction,
+            move_timer: move_timer.clone(),
+            shoot_timer: shoot_timer.clone(),
+        }
+    }
+}
+
+pub fn spawn_turrets(commands: &mut Commands, sprites: &Sprites, players: &Players, playfield: &Playfield) {
+    for player in players.on_field() {
+        let position = Vec2::new(players.turret_start_x(player, playfield), playfield.turret_y());
+        spawn_turret(commands, sprites, players.board_of(player), PlayerId(player), position);
+    }
+}
+
+pub fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        HudUi,
+        StateScoped(AppState::SpaceInvaders),
+        TextBundle::from_section("", TextStyle { font_size: HUD_FONT_SIZE, color: HUD_COLOUR, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: HUD_TEXT_PADDING,
+                left: HUD_TEXT_PADDING,
+                ..default()
+            }),
+    ));
+}
+
+pub fn update_hud(
+    players: Res<Players>,
+    difficulty: Res<ActiveDifficulty>,
+    state: Res<State<GameState>>,
+    netplay: Option<Res<NetplaySession>>,
+    mut query: Query<&mut Text, With<HudUi>>,
+) {
+    let mut text = query.single_mut();
+
+    let mut lines: Vec<String> = players
+        .states
+        .iter()
+        .enumerate()
+        .map(|(index, player)| {
+            // Only mark whose turn it is when players take turns
+            let marker =
+                if players.mode == GameMode::TwoPlayerAlternating && index == players.active { ">" } else { " " };
+            format!(
+                "{marker} PLAYER {}   SCORE {:05}   LIVES {}   WAVE {}",
+                index + 1,
+                player.score,
+                player.lives,
+                player.wave,
+            )
+        })
+        .collect();
+
+    if let Some(session) = &netplay {
+        lines.push(session.status());
+    }
+
+    if *state.get() == GameState::GameOver {
+        if players.mode == GameMode::Versus {
+            let survivors: Vec<_> =
+                (0..players.states.len()).filter(|&index| players.states[index].lives > 0).collect();
+            lines.push(match survivors[..] {
+                [winner] => format!("PLAYER {} WINS", winner + 1),
+                _ => "DRAW".to_string(),
+            });
+        } else {
+            lines.push("GAME OVER".to_string());
+        }
+        lines.push(format!("DIFFICULTY {}", difficulty.difficulty.name()));
+
+        // Netplay games can't be restarted from one side only
+        if netplay.is_none() {
+            lines.push("PRESS ENTER TO PLAY AGAIN".to_string());
+        }
+    }
+
+    text.sections[0].value = lines.join("\n");
+}
+
+pub fn handle_turret_hit(
+    mut commands: Commands,
+    mut turret_hit_events: EventReader<TurretHitEvent>,
+    mut players: ResMut<Players>,
+    boards: Res<Boards>,
+    mut board_query: Query<(&Board, &mut InvaderDirection, &mut InvaderMoveTimer, &mut InvaderShootTimer)>,
+    invader_query: Query<(Entity, &Transform, &Invader), Without<Despawning>>,
+    bullet_query: Query<(Entity, &BoardId), Or<(With<Bullet>, With<InvaderBullet>)>>,
+    mut turret_query: Query<(Entity, &mut Transform, &mut PlayerId, &mut Health), (With<Turret>, Without<Invader>)>,
+    playfield: Res<Playfield>,
+    difficulty: Res<ActiveDifficulty>,
+    sprites: Sprites,
+) {
+    // Several bullets can land on the same tick, but each player only dies once.
+    // An invasion outranks being shot.
+    let mut hits: Vec<(PlayerId, DeathCause)> =
+        turret_hit_events.read().map(|event| (event.player, event.cause)).collect();
+    hits.sort_by_key(|(player_id, cause)| (player_id.0, *cause != DeathCause::Invasion));
+    hits.dedup_by_key(|(player_id, _)| *player_id);
+
+    if hits.is_empty() {
+        return;
+    }
+
+    for (player_id, cause) in &hits {
+        let player = &mut players.states[player_id.0];
+        player.lives = match cause {
+            DeathCause::Shot => player.lives.saturating_sub(1),
+            DeathCause::Invasion => 0,
+        };
+        player.death_cause = Some(*cause);
+    }
+
+    let hit_players: Vec<PlayerId> = hits.iter().map(|(player_id, _)| *player_id).collect();
+
+    // Clear the shots from every board where someone died
+    let hit_boards: Vec<BoardId> = hit_players.iter().map(|player_id| players.board_of(player_id.0)).collect();
+    for (bullet_entity, board_id) in bullet_query.iter() {
+        if hit_boards.contains(board_id) {
+            commands.entity(bullet_entity).insert(Despawning);
+        }
+    }
+
+    for (turret_entity, mut turret_transform, player_id, mut health) in turret_query.iter_mut() {
+        if !hit_players.contains(&player_id) {
+            continue;
+        }
+
+        if players.mode == GameMode::TwoPlayerCoop && players.states[player_id.0].lives == 0 {
+            commands.entity(turret_entity).insert(Despawning);
+        } else {
+            turret_transform.translation.x = players.turret_start_x(player_id.0, &playfield);
+            health.restore();
+        }
+    }
+
+    if players.mode != GameMode::TwoPlayerAlternating {
+        return;
+    }
+
+    let current = players.active;
+    let Some(next) = players.next_player(current) else {
+        return;
+    };
+
+    if next == current {
+        return;
+    }
+
+    // Put the current board away and bring out the next player's
+    let board_entity = boards.0[0];
+    let Ok((board, mut direction, mut move_timer, mut shoot_timer)) = board_query.get_mut(board_entity) else {
+        return;
+    };
+
+    players.states[current].board = Some(BoardSnapshot::capture(
+        invader_query.iter().map(|(_, transform, invader)| (transform, invader)),
+        &direction,
+        &move_timer,
+        &shoot_timer,
+    ));
+
+    for (invader_entity, _, _) in invader_query.iter() {
+        commands.entity(invader_entity).insert(Despawning);
+    }
+
+    match players.states[next].board.take() {
+        Some(saved_board) => {
+            for invader in saved_board.invaders {
+                spawn_invader(
+                    &mut commands,
+                    &sprites,
+                    board.id,
+                    invader.invader_type,
+                    invader.position,
+                    invader.animation_frame,
+                );
+            }
+            *direction = saved_board.direction;
+            *move_timer = saved_board.move_timer;
+            *shoot_timer = saved_board.shoot_timer;
+        }
+        None => {
+            spawn_wave(&mut commands, &sprites, board_entity, *board, &difficulty.tuning, players.states[next].wave)
+        }
+    }
+
+    // The single turret changes hands
+    for (_, _, mut player_id, _) in turret_query.iter_mut() {
+        *player_id = PlayerId(next);
+    }
+
+    players.active = next;
+}
+
+/// Gives each player still in the game an extra life the first time their score reaches the
+/// difficulty's bonus life score
+pub fn award_bonus_lives(mut players: ResMut<Players>, difficulty: Res<ActiveDifficulty>) {
+    let bonus_life_score = difficulty.tuning.bonus_life_score;
+    if bonus_life_score == 0 {
+        return;
+    }
+
+    let earned: Vec<usize> = (0..players.states.len())
+        .filter(|&index| {
+            let player = &players.states[index];
+            !player.bonus_life_awarded && player.lives > 0 && player.score >= bonus_life_score
+        })
+        .collect();
+
+    for index in earned {
+        let player = &mut players.states[index];
+        player.lives += 1;
+        player.bonus_life_awarded = true;
+    }
+}
+
+/// Once an invader gets down to the turrets, every player defending that board is overrun
+pub fn check_invasion(
+    mut turret_hit_events: EventWriter<TurretHitEvent>,
+    invader_query: Query<(&Transform, &BoardId, &Invader), Without<Despawning>>,
+    turret_query: Query<(&BoardId, &PlayerId), With<Turret>>,
+    playfield: Res<Playfield>,
+) {
+    let turret_top = playfield.0.min.y + TURRET_PADDING + TURRET_SIZE.y;
+
+    let mut invaded_boards: Vec<BoardId> = invader_query
+        .iter()
+        .filter(|(transform, _, invader)| transform.translation.y - invader.invader_type.size().y / 2. <= turret_top)
+        .map(|(_, board_id, _)| *board_id)
+        .collect();
+    invaded_boards.sort_by_key(|board_id| board_id.0);
+    invaded_boards.dedup();
+
+    for (board_id, player_id) in turret_query.iter() {
+        if invaded_boards.contains(board_id) {
+            turret_hit_events.send(TurretHitEvent { player: *player_id, cause: DeathCause::Invasion });
+        }
+    }
+}
+
+pub fn check_game_over(players: Res<Players>, mut next_state: ResMut<NextState<GameState>>) {
+    if players.is_game_over() {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+/// In versus mode, sends an invader to the opponent's board for every few invaders a player shoots.
+/// It joins the top of a random column of the opponent's formation.
+pub fn send_extra_invaders(
+    mut commands: Commands,
+    mut invader_killed_events: EventReader<InvaderKilledEvent>,
+    mut players: ResMut<Players>,
+    mut rng: ResMut<GameRng>,
+    invader_query: Query<(&Transform, &BoardId), (With<Invader>, Without<Despawning>)>,
+    playfield: Res<Playfield>,
+    sprites: Sprites,
+) {
+    for event in invader_killed_events.read() {
+        let kills = &mut players.states[event.player.0].kills;
+        *kills += 1;
+
+        if players.mode != GameMode::Versus
+            || !players.states[event.player.0].kills.is_multiple_of(VERSUS_KILLS_PER_EXTRA_INVADER)
+        {
+            continue;
+        }
+
+        let opponent = (event.player.0 + 1) % players.states.len();
+        let opponent_board = players.board_of(opponent);
+
+        // Sorted so the random pick doesn't depend on query order
+        let mut columns: Vec<f32> = invader_query
+            .iter()
+            .filter(|(_, board_id)| **board_id == opponent_board)
+            .map(|(transform, _)| transform.translation.x)
+            .collect();
+        columns.sort_by(f32::total_cmp);
+        columns.dedup();
+
+        let Some(&column_x) = columns.choose(&mut rng.0) else {
+            continue;
+        };
+
+        let column_top = invader_query
+            .iter()
+            .filter(|(transform, board_id)| **board_id == opponent_board && transform.translation.x == column_x)
+            .map(|(transform, _)| transform.translation.y)
+            .fold(f32::MIN, f32::max);
+
+        let row_height = INVADER_A_SIZE.y + GAP_BETWEEN_INVADERS;
+        let y = column_top + row_height;
+
+        // No room above the formation yet
+        if y > playfield.0.max.y - HUD_HEIGHT - row_height {
+            continue;
+        }
+
+        spawn_invader(&mut commands, &sprites, opponent_board, InvaderType::C, Vec2::new(column_x, y), 1);
+    }
+}
+
+/// Starts the next wave on any board where every invader has been shot
+pub fn advance_wave(
+    mut commands: Commands,
+    invader_query: Query<&BoardId, (With<Invader>, Without<Despawning>)>,
+    board_query: Query<(Entity, &Board)>,
+    mut players: ResMut<Players>,
+    difficulty: Res<ActiveDifficulty>,
+    sprites: Sprites,
+) {
+    for (board_entity, board) in board_query.iter() {
+        if invader_query.iter().any(|board_id| *board_id == board.id) {
+            continue;
+        }
+
+        for player in players.on_field() {
+            if players.board_of(player) == board.id {
+                players.states[player].wave += 1;
+            }
+        }
+        spawn_wave(&mut commands, &sprites, board_entity, *board, &difficulty.tuning, players.wave_on(board.id));
+    }
+}
+
+/// Starts over when Enter is pressed on the game over screen, or on request from the pause menu,
+/// at the difficulty the settings are at now
+pub fn restart_game(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut restart_events: EventReader<RestartEvent>,
+    mut players: ResMut<Players>,
+    difficulty: Res<Difficulty>,
+    mut active_difficulty: ResMut<ActiveDifficulty>,
+    board_query: Query<(Entity, &Board)>,
+    gameplay_query: Query<Entity, Or<(With<Invader>, With<Turret>, With<Bullet>, With<InvaderBullet>)>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    playfield: Res<Playfield>,
+    sprites: Sprites,
+) {
+    let requested = restart_events.read().count() > 0;
+    let play_again = *state.get() == GameState::GameOver && keyboard_input.just_pressed(KeyCode::Enter);
+    if !requested && !play_again {
+        return;
+    }
+
+    *active_difficulty = ActiveDifficulty::new(difficulty.clone());
+    let tuning = &active_difficulty.tuning;
+    *players = Players::new(players.mode, tuning.starting_lives);
+
+    for entity in gameplay_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    for (board_entity, board) in board_query.iter() {
+        spawn_wave(&mut commands, &sprites, board_entity, *board, tuning, players.wave_on(board.id));
+    }
+    spawn_turrets(&mut commands, &sprites, &players, &playfield);
+    next_state.set(GameState::Playing);
+}
+
+/// Tells the host app about score and life changes, comparing against the last frame so that
+/// netplay rollbacks don't report anything twice
+pub fn send_player_events(
+    players: Res<Players>,
+    difficulty: Res<ActiveDifficulty>,
+    mut last_seen: Local<Vec<(u32, u32)>>,
+    mut score_events: EventWriter<ScoreChanged>,
+    mut death_events: EventWriter<PlayerDied>,
+) {
+    if !players.is_changed() {
+        return;
+    }
+
+    last_seen.resize(players.states.len(), (0, difficulty.tuning.starting_lives));
+    for (player, (state, (score, lives))) in players.states.iter().zip(last_seen.iter_mut()).enumerate() {
+        if state.score != *score {
+            score_events.send(ScoreChanged { player, score: state.score });
+        }
+        if state.lives < *lives {
+            death_events.send(PlayerDied { player, lives: state.lives });
+        }
+        *score = state.score;
+        *lives = state.lives;
+    }
+}
+
+pub fn send_game_over(
+    players: Res<Players>,
+    difficulty: Res<ActiveDifficulty>,
+    mut game_over_events: EventWriter<GameOver>,
+) {
+    game_over_events.send(GameOver {
+        scores: players.states.iter().map(|player| player.score).collect(),
+        difficulty: Some(difficulty.difficulty.clone()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::headless::{headless_app, start_headless};
+
+    /// Loses `player` a life by being shot, straight away rather than on the next tick
+    fn hit(app: &mut App, player: usize) {
+        let world = app.world_mut();
+        world.resource_mut::<Events<TurretHitEvent>>().clear();
+        world.send_event(TurretHitEvent { player: PlayerId(player), cause: DeathCause::Shot });
+        world.run_system_once(handle_turret_hit);
+    }
+
+    /// Where each invader on the field is, in a stable order
+    fn invader_positions(world: &mut World) -> Vec<(Vec2, usize)> {
+        let mut query = world.query_filtered::<(&Transform, &Invader), Without<Despawning>>();
+        let mut positions: Vec<_> = query
+            .iter(world)
+            .map(|(transform, invader)| (transform.translation.truncate(), invader.animation_frame))
+            .collect();
+        positions.sort_by(|(a, _), (b, _)| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        positions
+    }
+
+    fn board_state(world: &mut World) -> (InvaderDirection, f32, f32) {
+        let mut query = world.query::<(&InvaderDirection, &InvaderMoveTimer, &InvaderShootTimer)>();
+        let (direction, move_timer, shoot_timer) = query.single(world);
+        (*direction, move_timer.timer.elapsed_secs(), shoot_timer.0.elapsed_secs())
+    }
+
+    #[test]
+    fn alternating_players_get_their_own_board_back() {
+        let mut app = headless_app(GameMode::TwoPlayerAlternating, Difficulty::default(), 7);
+        start_headless(&mut app);
+        for _ in 0..120 {
+            app.update();
+        }
+
+        // Make player 1's board stand out from a fresh wave
+        {
+            let world = app.world_mut();
+            let mut query = world.query::<(&mut InvaderDirection, &mut InvaderShootTimer)>();
+            let (mut direction, mut shoot_timer) = query.single_mut(world);
+            *direction = InvaderDirection::Left;
+            shoot_timer.0.set_elapsed(std::time::Duration::from_millis(370));
+        }
+        let invaders = invader_positions(app.world_mut());
+        let board = board_state(app.world_mut());
+
+        hit(&mut app, 0);
+        assert_eq!(app.world().resource::<Players>().active, 1);
+        assert_ne!(invader_positions(app.world_mut()), invaders);
+
+        hit(&mut app, 1);
+        assert_eq!(app.world().resource::<Players>().active, 0);
+        assert_eq!(invader_positions(app.world_mut()), invaders);
+        assert_eq!(board_state(app.world_mut()), board);
+    }
+
+    #[test]
+    fn coop_player_out_of_lives_leaves_the_other_playing() {
+        let mut app = headless_app(GameMode::TwoPlayerCoop, Difficulty::default(), 7);
+        start_headless(&mut app);
+        let lives = app.world().resource::<Players>().states[0].lives;
+        for _ in 0..lives {
+            hit(&mut app, 0);
+        }
+
+        let players = app.world().resource::<Players>();
+        assert_eq!(players.states[0].lives, 0);
+        assert!(!players.is_game_over());
+        let world = app.world_mut();
+        let mut query = world.query_filtered::<&PlayerId, (With<Turret>, Without<Despawning>)>();
+        assert_eq!(query.iter(world).map(|player| player.0).collect::<Vec<_>>(), [1]);
+    }
+}
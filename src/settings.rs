@@ -0,0 +1,896 @@
+//! Settings, kept between runs.
+//!
+//! `Settings` holds everything the player can change: volumes, key bindings, the window mode and
+//! scaling, the Space Invaders theme and difficulty, and the accessibility options. It's loaded from
+//! `settings.json` in the user's config folder when the app starts, and saved there whenever it
+//! changes, or when the settings screen closes for changes made on it. The resources the games
+//! read, like `KeyBindings` and `Accessibility`, are kept in step with it, so a game embedded
+//! without `SettingsPlugin` just plays with their defaults. Command line options override the
+//! saved settings, and are saved along with them if anything is changed.
+//!
+//! The file records the version of its layout. Files from older versions are migrated one
+//! version at a time, starting from `accessibility.json` at version 0, which held just the
+//! accessibility options. A file that can't be read, or that's from a newer version, is ignored
+//! in favour of the defaults, and renamed with a `.bak` on the end so that saving can't overwrite
+//! it.
+//!
+//! The settings screen is opened from the launcher and pause menus. It has pages for general
+//! settings, tuning the difficulty, accessibility and each game's keys. A new difficulty applies
+//! from the next game, and tuned values are marked with a `*`. Clicking a key binding binds the next key
+//! pressed, or Escape leaves it as it was, and otherwise Escape closes the screen.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    prelude::*,
+    reflect::Enum,
+    ui::{FocusPolicy, RelativeCursorPosition},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    accessibility::{Accessibility, MIN_TIME_SCALE},
+    difficulty::{Difficulty, TuningValue},
+    display::{DisplaySettings, Scaling},
+    input::{Action, Binding, KeyBindings},
+    launcher::AppState,
+    netplay::NetplaySession,
+    pause::{spawn_menu_button, spawn_sized_menu_button, PauseState},
+    theme::{SelectedTheme, ThemeNames, DEFAULT_THEME},
+};
+
+const APP_FOLDER: &str = "bevy_experiment";
+const FILE_NAME: &str = "settings.json";
+// Where the accessibility options were saved before there were settings
+const LEGACY_ACCESSIBILITY_FILE: &str = "accessibility.json";
+// The layout of the settings file. Bump it and add a step to `migrate` whenever older files
+// won't load as they are.
+const VERSION: u64 = 2;
+
+const SLIDER_STEP: f32 = 0.05;
+
+const MENU_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.9);
+const MENU_TEXT_COLOUR: Color = Color::srgb(1.0, 1.0, 1.0);
+const SLIDER_TRACK_COLOUR: Color = Color::srgb(0.15, 0.15, 0.15);
+const SLIDER_FILL_COLOUR: Color = Color::srgb(0.4, 0.4, 0.4);
+const TITLE_FONT_SIZE: f32 = 40.0;
+const LABEL_FONT_SIZE: f32 = 24.0;
+// Narrow enough for a row to fit on a portrait board
+const LABEL_WIDTH: Val = Val::Px(200.0);
+const SLIDER_SIZE: Vec2 = Vec2::new(200.0, 44.0);
+const ROW_GAP: Val = Val::Px(10.0);
+// The tuning page's rows are shorter, to fit every value on a portrait board
+const TUNING_BUTTON_SIZE: Vec2 = Vec2::new(36.0, 36.0);
+
+/// Everything the player can change, saved between runs
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioSettings,
+    pub bindings: KeyBindings,
+    pub display: DisplaySettings,
+    /// Name of the Space Invaders theme
+    pub theme: String,
+    /// What Space Invaders games start at
+    pub difficulty: Difficulty,
+    pub accessibility: Accessibility,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            audio: AudioSettings::default(),
+            bindings: KeyBindings::default(),
+            display: DisplaySettings::default(),
+            theme: DEFAULT_THEME.to_string(),
+            difficulty: Difficulty::default(),
+            accessibility: Accessibility::default(),
+        }
+    }
+}
+
+/// Volumes, from silent at 0 to full at 1
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Applies to every sound, on top of its own volume
+    pub master_volume: f32,
+    pub effects_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings { master_volume: 1.0, effects_volume: 1.0 }
+    }
+}
+
+/// Why a settings file couldn't be loaded
+#[derive(Debug)]
+pub enum SettingsError {
+    Json(serde_json::Error),
+    MissingVersion,
+    /// Written by a newer version of the game, which might lay the settings out differently
+    TooNew(u64),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Json(error) => write!(f, "the settings are malformed: {error}"),
+            SettingsError::MissingVersion => write!(f, "the settings don't say which version they are"),
+            SettingsError::TooNew(version) => {
+                write!(f, "the settings are version {version}, but only up to {VERSION} can be read")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+/// How the settings are written to the file, with their version first
+#[derive(Serialize)]
+struct SettingsFile<'a> {
+    version: u64,
+    #[serde(flatten)]
+    settings: &'a Settings,
+}
+
+impl Settings {
+    /// The saved settings, or the defaults if there aren't any or they can't be read. Settings
+    /// from an older version are saved again once they've been migrated.
+    pub fn load() -> Self {
+        config_dir().map_or_else(Settings::default, |dir| Settings::load_from(&dir))
+    }
+
+    fn load_from(dir: &Path) -> Self {
+        let path = dir.join(FILE_NAME);
+        let legacy_path = dir.join(LEGACY_ACCESSIBILITY_FILE);
+
+        let (read_path, loaded) = if let Ok(text) = fs::read_to_string(&path) {
+            (path, Settings::parse(&text))
+        } else if let Ok(text) = fs::read_to_string(&legacy_path) {
+            let loaded = serde_json::from_str(&text).map_err(SettingsError::Json).and_then(|value| migrate(0, value));
+            (legacy_path, loaded)
+        } else {
+            return Settings::default();
+        };
+
+        match loaded {
+            Ok((settings, migrated)) => {
+                if migrated {
+                    info!("Migrated {} to version {VERSION} of the settings", read_path.display());
+                    settings.save_to(dir);
+                }
+                settings
+            }
+            Err(error) => {
+                // Moved out of the way rather than overwritten, so that the player can get it back
+                let mut backup_path = read_path.clone().into_os_string();
+                backup_path.push(".bak");
+                let backup_path = PathBuf::from(backup_path);
+                match fs::rename(&read_path, &backup_path) {
+                    Ok(()) => warn!("Ignoring {}, moved to {}: {error}", read_path.display(), backup_path.display()),
+                    Err(rename_error) => {
+                        warn!("Ignoring {}: {error}, and couldn't move it aside: {rename_error}", read_path.display())
+                    }
+                }
+                Settings::default()
+            }
+        }
+    }
+
+    /// Reads the contents of a settings file, migrating them if they're from an older version.
+    /// Also returns whether they were migrated.
+    pub fn parse(text: &str) -> Result<(Settings, bool), SettingsError> {
+        let value: Value = serde_json::from_str(text).map_err(SettingsError::Json)?;
+        let version = value.get("version").and_then(Value::as_u64).ok_or(SettingsError::MissingVersion)?;
+        migrate(version, value)
+    }
+
+    pub fn save(&self) {
+        let Some(dir) = config_dir() else {
+            warn!("Couldn't find a config folder to save the settings in");
+            return;
+        };
+        self.save_to(&dir);
+    }
+
+    fn save_to(&self, dir: &Path) {
+        let file = SettingsFile { version: VERSION, settings: self };
+        let text = serde_json::to_string_pretty(&file).expect("Settings are always serialisable");
+        write_file(&dir.join(FILE_NAME), &text);
+    }
+
+    fn clamped(self) -> Self {
+        Settings {
+            audio: AudioSettings {
+                master_volume: self.audio.master_volume.clamp(0.0, 1.0),
+                effects_volume: self.audio.effects_volume.clamp(0.0, 1.0),
+            },
+            difficulty: self.difficulty.clamped(),
+            accessibility: self.accessibility.clamped(),
+            ..self
+        }
+    }
+}
+
+/// Brings `value`, laid out as at `version`, up to the current version a version at a time.
+/// Also returns whether it needed migrating.
+fn migrate(version: u64, mut value: Value) -> Result<(Settings, bool), SettingsError> {
+    if version > VERSION {
+        return Err(SettingsError::TooNew(version));
+    }
+
+    for step in version..VERSION {
+        value = match step {
+            // `accessibility.json` held just the accessibility options
+            0 => json!({ "accessibility": value }),
+            // Every game was played at Normal before there were difficulties
+            1 => {
+                if let Some(object) = value.as_object_mut() {
+                    object.insert("difficulty".to_string(), json!({ "preset": "normal" }));
+                }
+                value
+            }
+            _ => unreachable!("there's a migration from every version before the current one"),
+        };
+    }
+
+    let settings: Settings = serde_json::from_value(value).map_err(SettingsError::Json)?;
+    Ok((settings.clamped(), version < VERSION))
+}
+
+/// The per-user folder settings are kept in
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).or_else(|| home().map(|home| home.join(".config")))
+    };
+    base.map(|base| base.join(APP_FOLDER))
+}
+
+/// Saves `text` as `file_name` in the config folder, warning if it can't
+pub(crate) fn write_config_file(file_name: &str, text: &str) {
+    let Some(dir) = config_dir() else {
+        warn!("Couldn't find a config folder to save {file_name} in");
+        return;
+    };
+    write_file(&dir.join(file_name), text);
+}
+
+fn write_file(path: &Path, text: &str) {
+    // Written alongside and then moved over the old file, so a crash part way through writing
+    // can't lose what was saved before
+    let mut temporary_path = path.to_path_buf().into_os_string();
+    temporary_path.push(".tmp");
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&temporary_path, text))
+        .and_then(|()| fs::rename(&temporary_path, path));
+    if let Err(error) = result {
+        warn!("Couldn't save {}: {error}", path.display());
+    }
+}
+
+/// Keeps the games in step with `settings`, saves them when they change, and adds the settings
+/// screen
+pub struct SettingsPlugin {
+    pub settings: Settings,
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = &self.settings;
+        app.insert_resource(settings.clone())
+            .insert_resource(settings.audio)
+            .insert_resource(settings.bindings)
+            .insert_resource(settings.accessibility)
+            .add_systems(
+                Update,
+                (sync_display, apply_settings.run_if(resource_changed::<Settings>), save_changes).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    handle_buttons,
+                    drag_sliders,
+                    read_binding_key.run_if(resource_exists::<AwaitingKey>),
+                    close_on_escape,
+                    update_labels,
+                )
+                    .chain()
+                    .run_if(menu_open),
+            )
+            .add_systems(OnExit(PauseState::Paused), close_menu)
+            .add_systems(OnExit(AppState::Menu), close_menu);
+    }
+}
+
+/// Copies the display settings back into the settings when F11 changes them
+fn sync_display(display: Option<Res<DisplaySettings>>, mut settings: ResMut<Settings>) {
+    if let Some(display) = display.filter(|display| display.is_changed()) {
+        if settings.display != *display {
+            settings.display = *display;
+        }
+    }
+}
+
+/// Passes the settings on to the resources the games read
+fn apply_settings(
+    settings: Res<Settings>,
+    mut audio: ResMut<AudioSettings>,
+    mut bindings: ResMut<KeyBindings>,
+    mut accessibility: ResMut<Accessibility>,
+    global_volume: Option<ResMut<GlobalVolume>>,
+    display: Option<ResMut<DisplaySettings>>,
+    selected_theme: Option<ResMut<SelectedTheme>>,
+    difficulty: Option<ResMut<Difficulty>>,
+    netplay: Option<Res<NetplaySession>>,
+) {
+    audio.set_if_neq(settings.audio);
+    bindings.set_if_neq(settings.bindings);
+    accessibility.set_if_neq(settings.accessibility);
+
+    if let Some(mut global_volume) = global_volume {
+        if global_volume.volume.get() != settings.audio.master_volume {
+            *global_volume = GlobalVolume::new(settings.audio.master_volume);
+        }
+    }
+    if let Some(mut display) = display {
+        display.set_if_neq(settings.display);
+    }
+    if let Some(mut selected_theme) = selected_theme {
+        selected_theme.set_if_neq(SelectedTheme(settings.theme.clone()));
+    }
+    // Netplay peers stick to the difficulty they agreed on the command line
+    if let Some(mut difficulty) = difficulty.filter(|_| netplay.is_none()) {
+        difficulty.set_if_neq(settings.difficulty.clone());
+    }
+}
+
+/// Saves changes made outside the settings screen straight away. The screen saves its changes
+/// when it closes, rather than on every step of a slider.
+fn save_changes(settings: Res<Settings>, menu_query: Query<(), With<SettingsMenu>>) {
+    if settings.is_changed() && !settings.is_added() && menu_query.is_empty() {
+        settings.save();
+    }
+}
+
+/// Whether the settings screen is open, for keeping other menus from reacting to its keys
+pub fn menu_open(menu_query: Query<(), With<SettingsMenu>>) -> bool {
+    !menu_query.is_empty()
+}
+
+/// The settings screen, showing `page`
+#[derive(Component)]
+pub struct SettingsMenu {
+    page: Page,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Page {
+    General,
+    Tuning,
+    Accessibility,
+    InvadersKeys,
+    BreakoutKeys,
+}
+
+impl Page {
+    const ALL: [Page; 5] = [Page::General, Page::Tuning, Page::Accessibility, Page::InvadersKeys, Page::BreakoutKeys];
+
+    fn title(self) -> &'static str {
+        match self {
+            Page::General => "SETTINGS",
+            Page::Tuning => "TUNING",
+            Page::Accessibility => "ACCESSIBILITY",
+            Page::InvadersKeys => "INVADERS KEYS",
+            Page::BreakoutKeys => "BREAKOUT KEYS",
+        }
+    }
+
+    fn next(self) -> Page {
+        let index = Page::ALL.iter().position(|page| *page == self).unwrap_or(0);
+        Page::ALL[(index + 1) % Page::ALL.len()]
+    }
+
+    /// Each row's label and what it changes
+    fn rows(self) -> Vec<(String, Control)> {
+        let button = |label: &str, button| (label.to_string(), Control::Button(button));
+        let slider = |label: &str, slider| (label.to_string(), Control::Slider(slider));
+        let binding = |label: String, binding| (label, Control::Button(SettingsButton::Bind(binding)));
+
+        match self {
+            Page::General => vec![
+                slider("VOLUME", Slider::MasterVolume),
+                slider("EFFECTS", Slider::EffectsVolume),
+                button("FULLSCREEN", SettingsButton::Fullscreen),
+                button("SCALING", SettingsButton::Scaling),
+                button("THEME", SettingsButton::Theme),
+                button("DIFFICULTY", SettingsButton::Difficulty),
+            ],
+            Page::Tuning => {
+                TuningValue::ALL.into_iter().map(|value| (value.name().to_string(), Control::Tune(value))).collect()
+            }
+            Page::Accessibility => vec![
+                button("COLOURS", SettingsButton::Palette),
+                button("FLASHING", SettingsButton::Flashing),
+                button("AUTO-FIRE", SettingsButton::AutoFire),
+                slider("GAME SPEED", Slider::TimeScale),
+            ],
+            Page::InvadersKeys => (0..2)
+                .flat_map(|player| {
+                    Action::ALL.map(|action| {
+                        binding(format!("P{} {}", player + 1, action_name(action)), Binding::Invaders(player, action))
+                    })
+                })
+                .collect(),
+            Page::BreakoutKeys => Action::ALL
+                .into_iter()
+                .map(|action| binding(action_name(action).to_string(), Binding::Breakout(action)))
+                .collect(),
+        }
+    }
+}
+
+enum Control {
+    Button(SettingsButton),
+    Slider(Slider),
+    /// A difficulty value, with buttons to step it down and up
+    Tune(TuningValue),
+}
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum SettingsButton {
+    Fullscreen,
+    Scaling,
+    Theme,
+    Difficulty,
+    Palette,
+    Flashing,
+    AutoFire,
+    Bind(Binding),
+    /// Steps a difficulty value by this many of its steps
+    Tune(TuningValue, i32),
+    NextPage,
+    Back,
+}
+
+/// A slider's track, which is dragged along to set a value from its minimum up to 1
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum Slider {
+    MasterVolume,
+    EffectsVolume,
+    TimeScale,
+}
+
+impl Slider {
+    fn min(self) -> f32 {
+        match self {
+            Slider::MasterVolume | Slider::EffectsVolume => 0.0,
+            Slider::TimeScale => MIN_TIME_SCALE,
+        }
+    }
+
+    fn value_mut(self, settings: &mut Settings) -> &mut f32 {
+        match self {
+            Slider::MasterVolume => &mut settings.audio.master_volume,
+            Slider::EffectsVolume => &mut settings.audio.effects_volume,
+            Slider::TimeScale => &mut settings.accessibility.time_scale,
+        }
+    }
+
+    fn value(self, settings: &Settings) -> f32 {
+        match self {
+            Slider::MasterVolume => settings.audio.master_volume,
+            Slider::EffectsVolume => settings.audio.effects_volume,
+            Slider::TimeScale => settings.accessibility.time_scale,
+        }
+    }
+}
+
+#[derive(Component)]
+struct SliderFill(Slider);
+
+#[derive(Component)]
+struct SliderText(Slider);
+
+#[derive(Component)]
+struct TuningText(TuningValue);
+
+/// A binding waiting for a key to be pressed
+#[derive(Resource)]
+struct AwaitingKey(Binding);
+
+fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Left => "LEFT",
+        Action::Right => "RIGHT",
+        Action::Fire => "FIRE",
+    }
+}
+
+/// How a key is shown on the screen, e.g. `A` for `KeyA`
+fn key_label(key: KeyCode) -> String {
+    let name = key.variant_name();
+    let name = ["Key", "Digit", "Arrow"].iter().find_map(|prefix| name.strip_prefix(prefix)).unwrap_or(name);
+    name.to_uppercase()
+}
+
+/// Opens the settings screen over whatever menu is showing
+pub fn open_menu(commands: &mut Commands) {
+    spawn_menu(commands, Page::General);
+}
+
+fn spawn_menu(commands: &mut Commands, page: Page) {
+    let text_style = |font_size| TextStyle { font_size, color: MENU_TEXT_COLOUR, ..default() };
+    let row = || NodeBundle {
+        style: Style { align_items: AlignItems::Center, column_gap: ROW_GAP, ..default() },
+        ..default()
+    };
+
+    commands
+        .spawn((
+            SettingsMenu { page },
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: ROW_GAP,
+                    ..default()
+                },
+                background_color: MENU_BACKGROUND.into(),
+                // Above the pause menu, and keeps the cursor off the buttons underneath
+                z_index: ZIndex::Global(2),
+                focus_policy: FocusPolicy::Block,
+                ..default()
+            },
+        ))
+        .with_children(|menu| {
+            menu.spawn(TextBundle::from_section(page.title(), text_style(TITLE_FONT_SIZE)));
+
+            // Buttons and sliders show their values, filled in by `update_labels`
+            for (label, control) in page.rows() {
+                menu.spawn(row()).with_children(|row| {
+                    row.spawn(
+                        TextBundle::from_section(label, text_style(LABEL_FONT_SIZE))
+                            .with_style(Style { width: LABEL_WIDTH, ..default() }),
+                    );
+                    match control {
+                        Control::Button(button) => spawn_menu_button(row, "", button),
+                        Control::Slider(slider) => {
+                            row.spawn((
+                                slider,
+                                Interaction::default(),
+                                RelativeCursorPosition::default(),
+                                NodeBundle {
+                                    style: Style {
+                                        width: Val::Px(SLIDER_SIZE.x),
+                                        height: Val::Px(SLIDER_SIZE.y),
+                                        align_items: AlignItems::Center,
+                                        justify_content: JustifyContent::Center,
+                                        ..default()
+                                    },
+                                    background_color: SLIDER_TRACK_COLOUR.into(),
+                                    focus_policy: FocusPolicy::Block,
+                                    ..default()
+                                },
+                            ))
+                            .with_children(|track| {
+                                track.spawn((
+                                    SliderFill(slider),
+                                    NodeBundle {
+                                        style: Style {
+                                            position_type: PositionType::Absolute,
+                                            left: Val::Px(0.0),
+                                            height: Val::Percent(100.0),
+                                            ..default()
+                                        },
+                                        background_color: SLIDER_FILL_COLOUR.into(),
+                                        ..default()
+                                    },
+                                ));
+                                track.spawn((
+                                    SliderText(slider),
+                                    TextBundle::from_section("", text_style(LABEL_FONT_SIZE)),
+                                ));
+                            });
+                        }
+                        Control::Tune(value) => {
+                            row.spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(SLIDER_SIZE.x),
+                                    align_items: AlignItems::Center,
+                                    justify_content: JustifyContent::SpaceBetween,
+                                    ..default()
+                                },
+                                ..default()
+                            })
+                            .with_children(|control| {
+                                let text = TextBundle::from_section("", text_style(LABEL_FONT_SIZE));
+                                spawn_sized_menu_button(
+                                    control,
+                                    "-",
+                                    TUNING_BUTTON_SIZE,
+                                    SettingsButton::Tune(value, -1),
+                                );
+                                control.spawn((TuningText(value), text));
+                                spawn_sized_menu_button(
+                                    control,
+                                    "+",
+                                    TUNING_BUTTON_SIZE,
+                                    SettingsButton::Tune(value, 1),
+                                );
+                            });
+                        }
+                    }
+                });
+            }
+
+            menu.spawn(row()).with_children(|row| {
+                spawn_menu_button(row, "MORE", SettingsButton::NextPage);
+                spawn_menu_button(row, "BACK", SettingsButton::Back);
+            });
+        });
+}
+
+/// Closes the settings screen if it's open, saving the settings
+fn close_menu(mut commands: Commands, menu_query: Query<Entity, With<SettingsMenu>>, settings: Res<Settings>) {
+    close(&mut commands, menu_query.iter(), &settings);
+}
+
+fn close(commands: &mut Commands, menus: impl Iterator<Item = Entity>, settings: &Settings) {
+    let mut closed = false;
+    for menu in menus {
+        commands.entity(menu).despawn_recursive();
+        closed = true;
+    }
+    if closed {
+        commands.remove_resource::<AwaitingKey>();
+        settings.save();
+    }
+}
+
+fn handle_buttons(
+    mut commands: Commands,
+    button_query: Query<(&Interaction, &SettingsButton), Changed<Interaction>>,
+    menu_query: Query<(Entity, &SettingsMenu)>,
+    theme_names: Option<Res<ThemeNames>>,
+    mut settings: ResMut<Settings>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match *button {
+            SettingsButton::Fullscreen => settings.display.fullscreen = !settings.display.fullscreen,
+            SettingsButton::Scaling => {
+                settings.display.scaling = match settings.display.scaling {
+                    Scaling::Integer => Scaling::Fit,
+                    Scaling::Fit => Scaling::Integer,
+                }
+            }
+            SettingsButton::Theme => {
+                // The themes aren't known until the manifest has loaded
+                let names = theme_names.as_ref().map_or(&[][..], |names| &names.0);
+                let next = names.iter().position(|name| *name == settings.theme).map_or(0, |index| index + 1);
+                if !names.is_empty() {
+                    settings.theme = names[next % names.len()].clone();
+                }
+            }
+            SettingsButton::Difficulty => settings.difficulty = Difficulty::from(settings.difficulty.preset.next()),
+            SettingsButton::Tune(value, steps) => settings.difficulty.adjust(value, steps),
+            SettingsButton::Palette => settings.accessibility.palette = settings.accessibility.palette.next(),
+            SettingsButton::Flashing => {
+                settings.accessibility.reduce_flashing = !settings.accessibility.reduce_flashing
+            }
+            SettingsButton::AutoFire => settings.accessibility.auto_fire = !settings.accessibility.auto_fire,
+            SettingsButton::Bind(binding) => commands.insert_resource(AwaitingKey(binding)),
+            SettingsButton::NextPage => {
+                for (menu, SettingsMenu { page }) in menu_query.iter() {
+                    commands.entity(menu).despawn_recursive();
+                    spawn_menu(&mut commands, page.next());
+                }
+                commands.remove_resource::<AwaitingKey>();
+            }
+            SettingsButton::Back => close(&mut commands, menu_query.iter().map(|(menu, _)| menu), &settings),
+        }
+    }
+}
+
+/// Sets a slider's value from wherever it's held, in `SLIDER_STEP`s
+fn drag_sliders(slider_query: Query<(&Interaction, &RelativeCursorPosition, &Slider)>, mut settings: ResMut<Settings>) {
+    for (interaction, cursor, slider) in slider_query.iter() {
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let min = slider.min();
+        let unstepped = min + position.x.clamp(0.0, 1.0) * (1.0 - min);
+        let value = (unstepped / SLIDER_STEP).round() * SLIDER_STEP;
+        if slider.value(&settings) != value {
+            *slider.value_mut(&mut settings) = value;
+        }
+    }
+}
+
+/// Binds the next key pressed to the binding waiting for one, unless it's Escape
+fn read_binding_key(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    awaiting: Res<AwaitingKey>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some(&key) = keyboard_input.get_just_pressed().next() else {
+        return;
+    };
+
+    if key != KeyCode::Escape && !matches!(key, KeyCode::Unidentified(_)) {
+        settings.bindings.bind(awaiting.0, key);
+    }
+    commands.remove_resource::<AwaitingKey>();
+    // Used up, so Escape doesn't go on to close the screen
+    keyboard_input.clear_just_pressed(key);
+}
+
+fn close_on_escape(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<ButtonInput<KeyCode>>,
+    menu_query: Query<Entity, With<SettingsMenu>>,
+    settings: Res<Settings>,
+) {
+    if keyboard_input.clear_just_pressed(KeyCode::Escape) {
+        close(&mut commands, menu_query.iter(), &settings);
+    }
+}
+
+fn update_labels(
+    settings: Res<Settings>,
+    awaiting: Option<Res<AwaitingKey>>,
+    button_query: Query<(&SettingsButton, &Children)>,
+    mut text_query: Query<&mut Text, (Without<SliderText>, Without<TuningText>)>,
+    mut slider_text_query: Query<(&mut Text, &SliderText), Without<TuningText>>,
+    mut tuning_text_query: Query<(&mut Text, &TuningText), Without<SliderText>>,
+    mut fill_query: Query<(&mut Style, &SliderFill)>,
+) {
+    let on_off = |on| if on { "ON" } else { "OFF" }.to_string();
+    let tuned = |value: String, overridden| if overridden { format!("{value}*") } else { value };
+    for (button, children) in button_query.iter() {
+        let value = match *button {
+            SettingsButton::Fullscreen => on_off(settings.display.fullscreen),
+            SettingsButton::Scaling => match settings.display.scaling {
+                Scaling::Integer => "WHOLE PIXELS".to_string(),
+                Scaling::Fit => "FIT".to_string(),
+            },
+            SettingsButton::Theme => settings.theme.to_uppercase(),
+            SettingsButton::Difficulty => {
+                tuned(settings.difficulty.preset.name().to_string(), !settings.difficulty.overrides.is_empty())
+            }
+            SettingsButton::Palette => settings.accessibility.palette.name().to_string(),
+            SettingsButton::Flashing => on_off(!settings.accessibility.reduce_flashing),
+            SettingsButton::AutoFire => on_off(settings.accessibility.auto_fire),
+            SettingsButton::Bind(binding) if awaiting.as_ref().is_some_and(|awaiting| awaiting.0 == binding) => {
+                "PRESS A KEY".to_string()
+            }
+            SettingsButton::Bind(binding) => key_label(settings.bindings.key(binding)),
+            SettingsButton::Tune(..) | SettingsButton::NextPage | SettingsButton::Back => continue,
+        };
+        if let Ok(mut text) = text_query.get_mut(children[0]) {
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+        }
+    }
+
+    for (mut text, SliderText(slider)) in slider_text_query.iter_mut() {
+        let value = format!("{:.0}%", slider.value(&settings) * 100.0);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+
+    let tuning = settings.difficulty.tuning();
+    for (mut text, TuningText(value)) in tuning_text_query.iter_mut() {
+        let label = tuned(value.format(tuning.get(*value)), settings.difficulty.overrides.contains_key(value));
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+    }
+
+    for (mut style, SliderFill(slider)) in fill_query.iter_mut() {
+        let width = Val::Percent((slider.value(&settings) - slider.min()) / (1.0 - slider.min()) * 100.0);
+        if style.width != width {
+            style.width = width;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{accessibility::Palette, difficulty::Preset};
+
+    /// An empty config folder of its own for each test
+    fn config_folder(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{APP_FOLDER}_{test}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn saved_version(dir: &Path) -> Option<u64> {
+        let text = fs::read_to_string(dir.join(FILE_NAME)).ok()?;
+        serde_json::from_str::<Value>(&text).ok()?.get("version")?.as_u64()
+    }
+
+    #[test]
+    fn accessibility_file_is_migrated() {
+        let dir = config_folder("version_0");
+        fs::write(dir.join(LEGACY_ACCESSIBILITY_FILE), r#"{ "palette": "high_contrast", "auto_fire": true }"#).unwrap();
+
+        let settings = Settings::load_from(&dir);
+        assert_eq!(settings.accessibility.palette, Palette::HighContrast);
+        assert!(settings.accessibility.auto_fire);
+        assert_eq!(settings.difficulty, Difficulty::from(Preset::Normal));
+        assert_eq!(saved_version(&dir), Some(VERSION));
+        assert_eq!(Settings::load_from(&dir), settings);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn version_1_is_migrated() {
+        let dir = config_folder("version_1");
+        let text = r#"{ "version": 1, "audio": { "master_volume": 0.5 }, "theme": "classic" }"#;
+        fs::write(dir.join(FILE_NAME), text).unwrap();
+
+        let settings = Settings::load_from(&dir);
+        assert_eq!(settings.audio.master_volume, 0.5);
+        assert_eq!(settings.theme, "classic");
+        assert_eq!(settings.difficulty, Difficulty::from(Preset::Normal));
+        assert_eq!(saved_version(&dir), Some(VERSION));
+        assert_eq!(Settings::load_from(&dir), settings);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unreadable_files_are_moved_aside() {
+        let too_new = format!(r#"{{ "version": {}, "theme": "future" }}"#, VERSION + 1);
+        for (test, text) in [("too_new", too_new.as_str()), ("malformed", "{ \"version\": 2, ")] {
+            let dir = config_folder(test);
+            fs::write(dir.join(FILE_NAME), text).unwrap();
+
+            assert_eq!(Settings::load_from(&dir), Settings::default(), "{test}");
+            assert!(!dir.join(FILE_NAME).exists(), "{test}");
+            assert_eq!(fs::read_to_string(dir.join("settings.json.bak")).unwrap(), text, "{test}");
+
+            // Saving afterwards leaves the unreadable file alone
+            Settings::default().save_to(&dir);
+            assert_eq!(fs::read_to_string(dir.join("settings.json.bak")).unwrap(), text, "{test}");
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn too_new_is_reported() {
+        let text = format!(r#"{{ "version": {} }}"#, VERSION + 1);
+        assert!(matches!(Settings::parse(&text), Err(SettingsError::TooNew(version)) if version == VERSION + 1));
+        assert!(matches!(Settings::parse("{}"), Err(SettingsError::MissingVersion)));
+        assert!(matches!(Settings::parse("{"), Err(SettingsError::Json(_))));
+    }
+}
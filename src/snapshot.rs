@@ -0,0 +1,268 @@
+//! Whole-game snapshots for netplay rollback.
+//!
+//! A snapshot holds every piece of gameplay state: the turrets, invaders and bullets on each
+//! board and the health they have left, each board's formation state, the players and the random
+//! number generator. Restoring one despawns the gameplay entities and respawns them from the
+//! snapshot, so gameplay systems must not depend on entity ids or query order.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bevy::{ecs::system::SystemState, prelude::*};
+
+use crate::{
+    damage::Health, players::Players, spawn_bullet, spawn_invader, spawn_invader_bullet, spawn_turret, Board, BoardId,
+    Bullet, GameRng, Invader, InvaderBullet, InvaderCount, InvaderDirection, InvaderMoveTimer, InvaderShootTimer,
+    InvaderType, PlayerId, ShootCooldown, Sprites, Turret,
+};
+
+#[derive(Clone)]
+pub struct GameSnapshot {
+    players: Players,
+    rng: GameRng,
+    boards: Vec<FormationSnapshot>,
+    entities: Vec<EntitySnapshot>,
+}
+
+#[derive(Clone)]
+struct FormationSnapshot {
+    board: Entity,
+    board_id: BoardId,
+    direction: InvaderDirection,
+    move_timer: InvaderMoveTimer,
+    shoot_timer: InvaderShootTimer,
+    count: InvaderCount,
+}
+
+#[derive(Clone)]
+struct EntitySnapshot {
+    board_id: BoardId,
+    position: Vec2,
+    health: Option<Health>,
+    kind: EntityKind,
+}
+
+#[derive(Clone)]
+enum EntityKind {
+    Turret { player_id: PlayerId, cooldown: ShootCooldown },
+    Invader { invader_type: InvaderType, animation_frame: usize },
+    Bullet { player_id: PlayerId },
+    InvaderBullet,
+}
+
+impl GameSnapshot {
+    pub fn capture(world: &mut World) -> Self {
+        let mut entities = Vec::new();
+
+        let mut turret_query =
+            world.query_filtered::<(&Transform, &BoardId, Option<&Health>, &PlayerId, &ShootCooldown), With<Turret>>();
+        for (transform, board_id, health, player_id, cooldown) in turret_query.iter(world) {
+            entities.push(EntitySnapshot {
+                board_id: *board_id,
+                position: transform.translation.truncate(),
+                health: health.copied(),
+                kind: EntityKind::Turret { player_id: *player_id, cooldown: cooldown.clone() },
+            });
+        }
+
+        let mut invader_query = world.query::<(&Transform, &BoardId, Option<&Health>, &Invader)>();
+        for (transform, board_id, health, invader) in invader_query.iter(world) {
+            entities.push(EntitySnapshot {
+                board_id: *board_id,
+                position: transform.translation.truncate(),
+                health: health.copied(),
+                kind: EntityKind::Invader {
+                    invader_type: invader.invader_type,
+                    animation_frame: invader.animation_frame,
+                },
+            });
+        }
+
+        let mut bullet_query =
+            world.query_filtered::<(&Transform, &BoardId, Option<&Health>, &PlayerId), With<Bullet>>();
+        for (transform, board_id, health, player_id) in bullet_query.iter(world) {
+            entities.push(EntitySnapshot {
+                board_id: *board_id,
+                position: transform.translation.truncate(),
+                health: health.copied(),
+                kind: EntityKind::Bullet { player_id: *player_id },
+            });
+        }
+
+        let mut invader_bullet_query =
+            world.query_filtered::<(&Transform, &BoardId, Option<&Health>), With<InvaderBullet>>();
+        for (transform, board_id, health) in invader_bullet_query.iter(world) {
+            entities.push(EntitySnapshot {
+                board_id: *board_id,
+                position: transform.translation.truncate(),
+                health: health.copied(),
+                kind: EntityKind::InvaderBullet,
+            });
+        }
+
+        let mut board_query =
+            world.query::<(Entity, &Board, &InvaderDirection, &InvaderMoveTimer, &InvaderShootTimer, &InvaderCount)>();
+        let boards = board_query
+            .iter(world)
+            .map(|(board, board_info, direction, move_timer, shoot_timer, count)| FormationSnapshot {
+                board,
+                board_id: board_info.id,
+                direction: *direction,
+                move_timer: move_timer.clone(),
+                shoot_timer: shoot_timer.clone(),
+                count: *count,
+            })
+            .collect();
+
+        GameSnapshot {
+            players: world.resource::<Players>().clone(),
+            rng: world.resource::<GameRng>().clone(),
+            boards,
+            entities,
+        }
+    }
+
+    pub fn restore(&self, world: &mut World) {
+        let mut gameplay_query = world.query_filtered::<Entity, (With<BoardId>, Without<Board>)>();
+        let gameplay_entities: Vec<Entity> = gameplay_query.iter(world).collect();
+        for entity in gameplay_entities {
+            world.despawn(entity);
+        }
+
+        for formation in &self.boards {
+            world.entity_mut(formation.board).insert((
+                formation.direction,
+                formation.move_timer.clone(),
+                formation.shoot_timer.clone(),
+                formation.count,
+            ));
+        }
+
+        *world.resource_mut::<Players>() = self.players.clone();
+        *world.resource_mut::<GameRng>() = self.rng.clone();
+
+        let mut system_state = SystemState::<(Commands, Sprites)>::new(world);
+        let (mut commands, sprites) = system_state.get_mut(world);
+        for entity in &self.entities {
+            let spawned = match &entity.kind {
+                EntityKind::Turret { player_id, cooldown } => {
+                    let turret = spawn_turret(&mut commands, &sprites, entity.board_id, *player_id, entity.position);
+                    commands.entity(turret).insert(cooldown.clone());
+                    turret
+                }
+                EntityKind::Invader { invader_type, animation_frame } => spawn_invader(
+                    &mut commands,
+                    &sprites,
+                    entity.board_id,
+                    *invader_type,
+                    entity.position,
+                    *animation_frame,
+                ),
+                EntityKind::Bullet { player_id } => {
+                    spawn_bullet(&mut commands, &sprites, entity.board_id, *player_id, entity.position)
+                }
+                EntityKind::InvaderBullet => {
+                    spawn_invader_bullet(&mut commands, &sprites, entity.board_id, entity.position)
+                }
+            };
+            if let Some(health) = entity.health {
+                commands.entity(spawned).insert(health);
+            }
+        }
+        system_state.apply(world);
+    }
+
+    pub fn players(&self) -> &Players {
+        &self.players
+    }
+
+    /// A hash of the gameplay state that doesn't depend on entity ids or query order,
+    /// so two peers running the same simulation get the same value
+    pub fn checksum(&self) -> u64 {
+        let mut entity_hashes: Vec<u64> = self
+            .entities
+            .iter()
+            .map(|entity| {
+                let mut hasher = DefaultHasher::new();
+                entity.board_id.hash(&mut hasher);
+                entity.position.x.to_bits().hash(&mut hasher);
+                entity.position.y.to_bits().hash(&mut hasher);
+                entity.health.map(|health| (health.current, health.max)).hash(&mut hasher);
+                match &entity.kind {
+                    EntityKind::Turret { player_id, cooldown } => {
+                        (0u8, player_id.0, cooldown.0.elapsed()).hash(&mut hasher);
+                    }
+                    EntityKind::Invader { invader_type, animation_frame } => {
+                        (1u8, invader_type, animation_frame).hash(&mut hasher);
+                    }
+                    EntityKind::Bullet { player_id } => (2u8, player_id.0).hash(&mut hasher),
+                    EntityKind::InvaderBullet => 3u8.hash(&mut hasher),
+                }
+                hasher.finish()
+            })
+            .collect();
+        entity_hashes.sort_unstable();
+
+        let mut formation_hashes: Vec<u64> = self
+            .boards
+            .iter()
+            .map(|formation| {
+                let mut hasher = DefaultHasher::new();
+                formation.board_id.hash(&mut hasher);
+                formation.direction.hash(&mut hasher);
+                formation.move_timer.timer.elapsed().hash(&mut hasher);
+                formation.move_timer.timer.duration().hash(&mut hasher);
+                formation.shoot_timer.0.elapsed().hash(&mut hasher);
+                formation.count.total.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+        formation_hashes.sort_unstable();
+
+        let mut hasher = DefaultHasher::new();
+        entity_hashes.hash(&mut hasher);
+        formation_hashes.hash(&mut hasher);
+        for player in &self.players.states {
+            (player.score, player.lives, player.wave, player.kills).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        headless::{headless_app, start_headless},
+        players::GameMode,
+        Difficulty,
+    };
+
+    #[test]
+    fn damaged_invader_comes_back_damaged() {
+        let mut app = headless_app(GameMode::OnePlayer, Difficulty::default(), 5);
+        start_headless(&mut app);
+        let undamaged = GameSnapshot::capture(app.world_mut());
+
+        let world = app.world_mut();
+        let (invader, position) = world
+            .query_filtered::<(Entity, &Transform), With<Invader>>()
+            .iter(world)
+            .map(|(invader, transform)| (invader, transform.translation.truncate()))
+            .next()
+            .unwrap();
+        let damaged = Health { current: 2, max: 3 };
+        world.entity_mut(invader).insert(damaged);
+        let snapshot = GameSnapshot::capture(world);
+        assert_ne!(snapshot.checksum(), undamaged.checksum());
+
+        undamaged.restore(world);
+        snapshot.restore(world);
+        let health = world
+            .query_filtered::<(&Transform, &Health), With<Invader>>()
+            .iter(world)
+            .find(|(transform, _)| transform.translation.truncate() == position)
+            .map(|(_, health)| *health);
+        assert_eq!(health, Some(damaged));
+        assert_eq!(GameSnapshot::capture(world).checksum(), snapshot.checksum());
+    }
+}
@@ -0,0 +1,273 @@
+//! Themes for Space Invaders: which sprites it's drawn with, and in what colours.
+//!
+//! Themes are listed in `themes.json` under the asset root. Each names the folder its sprites are
+//! loaded from, laid out like the `sprites` folder, so a theme can reskin the game with a new set
+//! of sprites. It also gives a background colour, a colour for each kind of sprite and any number
+//! of colour bands. A band tints everything whose centre is inside it, like the strips of
+//! cellophane stuck over the arcade cabinet's screen: green at the bottom over the turrets and
+//! red at the top where the UFO flies. Bands are measured up each board from its bottom edge, as
+//! fractions of its height.
+//!
+//! The theme is picked by name in the `Settings`, "classic" by default, or with `--theme`. Until the manifest has loaded, or if it
+//! has no theme by that name, the sprites are drawn as they are on a plain background. The
+//! accessibility palettes take precedence over the theme's colours.
+
+use std::{fmt, path::PathBuf};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    accessibility::Accessibility, display::PlayfieldBackground, get_invader_sprite_path, AssetRoot, Board, BoardId,
+    Bullet, Invader, InvaderBullet, Sprites, Turret, BULLET_SPRITE, INVADER_BULLET_SPRITE, TURRET_SPRITE,
+};
+
+pub const DEFAULT_THEME: &str = "classic";
+const MANIFEST_FILE: &str = "themes.json";
+
+/// Every theme there is, as listed in `themes.json`
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct ThemeManifest {
+    pub themes: Vec<Theme>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    /// Folder under the asset root that the theme's `sprites` folder is in
+    #[serde(default)]
+    pub sprites: PathBuf,
+    pub background: HexColour,
+    #[serde(default)]
+    pub colours: ThemeColours,
+    #[serde(default)]
+    pub bands: Vec<ColourBand>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: String::new(),
+            sprites: PathBuf::new(),
+            background: HexColour(ClearColor::default().0),
+            colours: ThemeColours::default(),
+            bands: Vec::new(),
+        }
+    }
+}
+
+/// What each kind of sprite is tinted, white if the theme doesn't say
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ThemeColours {
+    pub turret: HexColour,
+    pub player_shot: HexColour,
+    /// One for each `InvaderType`, A to C
+    pub invaders: [HexColour; 3],
+    pub invader_shot: HexColour,
+}
+
+/// A strip across every board that tints whatever is in it
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ColourBand {
+    pub from: f32,
+    pub to: f32,
+    pub colour: HexColour,
+}
+
+/// A colour written as `#rrggbb` in the manifest
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub struct HexColour(pub Color);
+
+impl Default for HexColour {
+    fn default() -> Self {
+        HexColour(Color::WHITE)
+    }
+}
+
+impl TryFrom<String> for HexColour {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        Srgba::hex(&text).map(|colour| HexColour(colour.into())).map_err(|_| format!("{text} isn't a #rrggbb colour"))
+    }
+}
+
+#[derive(Debug)]
+pub enum ThemeManifestError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ThemeManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThemeManifestError::Io(error) => write!(f, "couldn't read the theme manifest: {error}"),
+            ThemeManifestError::Json(error) => write!(f, "the theme manifest is malformed: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ThemeManifestError {}
+
+impl From<std::io::Error> for ThemeManifestError {
+    fn from(error: std::io::Error) -> Self {
+        ThemeManifestError::Io(error)
+    }
+}
+
+#[derive(Default)]
+struct ThemeManifestLoader;
+
+impl AssetLoader for ThemeManifestLoader {
+    type Asset = ThemeManifest;
+    type Settings = ();
+    type Error = ThemeManifestError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<ThemeManifest, ThemeManifestError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        serde_json::from_slice(&bytes).map_err(ThemeManifestError::Json)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+}
+
+/// The name of the theme to play with
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct SelectedTheme(pub String);
+
+/// The theme being drawn with, once it's been found in the manifest
+#[derive(Resource, Default, Debug, PartialEq)]
+pub struct ActiveTheme(pub Theme);
+
+/// The name of every theme in the manifest, in its order, once it's loaded
+#[derive(Resource, Default, Debug)]
+pub struct ThemeNames(pub Vec<String>);
+
+#[derive(Resource)]
+struct ThemeManifestHandle(Handle<ThemeManifest>);
+
+/// Loads the theme manifest and keeps Space Invaders drawn in the selected theme
+pub(crate) fn add_themes(app: &mut App, theme: &str) {
+    app.init_asset::<ThemeManifest>()
+        .init_asset_loader::<ThemeManifestLoader>()
+        .insert_resource(SelectedTheme(theme.to_string()))
+        .init_resource::<ThemeNames>()
+        .add_systems(Startup, load_manifest)
+        .add_systems(
+            Update,
+            (choose_theme, retexture_sprites.run_if(resource_changed::<ActiveTheme>), tint_sprites).chain(),
+        );
+}
+
+fn load_manifest(mut commands: Commands, asset_server: Res<AssetServer>, asset_root: Res<AssetRoot>) {
+    commands.insert_resource(ThemeManifestHandle(asset_server.load(asset_root.0.join(MANIFEST_FILE))));
+}
+
+/// Looks the selected theme up in the manifest whenever either changes
+fn choose_theme(
+    selected: Res<SelectedTheme>,
+    manifest_handle: Res<ThemeManifestHandle>,
+    manifests: Res<Assets<ThemeManifest>>,
+    mut manifest_events: EventReader<AssetEvent<ThemeManifest>>,
+    mut active: ResMut<ActiveTheme>,
+    mut names: ResMut<ThemeNames>,
+) {
+    let manifest_changed = manifest_events.read().any(|event| event.is_loaded_with_dependencies(&manifest_handle.0));
+    if !manifest_changed && !selected.is_changed() {
+        return;
+    }
+    let Some(manifest) = manifests.get(&manifest_handle.0) else {
+        return;
+    };
+
+    if manifest_changed {
+        names.0 = manifest.themes.iter().map(|theme| theme.name.clone()).collect();
+    }
+
+    match manifest.themes.iter().find(|theme| theme.name == selected.0) {
+        Some(theme) if active.0 != *theme => active.0 = theme.clone(),
+        Some(_) => {}
+        None => warn!("There's no theme called {}", selected.0),
+    }
+}
+
+/// Swaps every sprite's image for the active theme's
+fn retexture_sprites(
+    sprites: Sprites,
+    mut sprite_query: Query<(&mut Handle<Image>, AnyOf<(&Turret, &Invader, &Bullet, &InvaderBullet)>)>,
+) {
+    for (mut texture, (turret, invader, bullet, _)) in sprite_query.iter_mut() {
+        *texture = match (turret, invader, bullet) {
+            (Some(_), ..) => sprites.load(TURRET_SPRITE),
+            (_, Some(invader), _) => {
+                sprites.load(&get_invader_sprite_path(&invader.invader_type, invader.animation_frame))
+            }
+            (_, _, Some(_)) => sprites.load(BULLET_SPRITE),
+            _ => sprites.load(INVADER_BULLET_SPRITE),
+        };
+    }
+}
+
+/// Colours everything in the accessibility palette if one is picked, or the theme's colours and
+/// bands if not
+fn tint_sprites(
+    accessibility: Res<Accessibility>,
+    theme: Res<ActiveTheme>,
+    board_query: Query<&Board>,
+    mut sprite_query: Query<(
+        &mut Sprite,
+        &Transform,
+        Option<&BoardId>,
+        AnyOf<(&Turret, &Invader, &Bullet, &InvaderBullet, &PlayfieldBackground)>,
+    )>,
+) {
+    let theme = &theme.0;
+    let palette = accessibility.palette.colours();
+    let board_areas: Vec<(BoardId, Rect)> = board_query.iter().map(|board| (board.id, board.area)).collect();
+
+    for (mut sprite, transform, board_id, (turret, invader, bullet, invader_bullet, _)) in sprite_query.iter_mut() {
+        let colour = match &palette {
+            Some(palette) => match (turret, invader, bullet, invader_bullet) {
+                (Some(_), ..) => palette.turret,
+                (_, Some(invader), ..) => palette.invaders[invader.invader_type as usize],
+                (_, _, Some(_), _) => palette.player_shot,
+                (_, _, _, Some(_)) => palette.invader_shot,
+                _ => palette.background.unwrap_or(theme.background.0),
+            },
+            None => {
+                let colours = &theme.colours;
+                let colour = match (turret, invader, bullet, invader_bullet) {
+                    (Some(_), ..) => colours.turret.0,
+                    (_, Some(invader), ..) => colours.invaders[invader.invader_type as usize].0,
+                    (_, _, Some(_), _) => colours.player_shot.0,
+                    (_, _, _, Some(_)) => colours.invader_shot.0,
+                    _ => theme.background.0,
+                };
+
+                let height = board_id
+                    .and_then(|board_id| board_areas.iter().find(|(id, _)| id == board_id))
+                    .map(|(_, area)| (transform.translation.y - area.min.y) / area.height());
+                let band =
+                    height.and_then(|height| theme.bands.iter().find(|band| band.from <= height && height < band.to));
+                band.map_or(colour, |band| band.colour.0)
+            }
+        };
+
+        if sprite.color != colour {
+            sprite.color = colour;
+        }
+    }
+}